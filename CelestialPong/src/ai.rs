@@ -0,0 +1,160 @@
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::paddle::wrap_angle;
+use crate::prediction::predict_path;
+use crate::world::World;
+
+/// How well a computer controlled paddle plays, from the easiest.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Medium,
+    Hard,
+}
+
+/// What a difficulty allows the computer to do.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AiLimits {
+    // Seconds of ball motion predicted
+    pub horizon: f32,
+    // Seconds between two decisions of where to go
+    pub reaction_delay: f32,
+    // Fraction of the paddle speed used
+    pub speed: f32,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard];
+
+    pub fn limits(self) -> AiLimits {
+        match self {
+            Difficulty::Easy => AiLimits {
+                horizon: 0.5,
+                reaction_delay: 0.5,
+                speed: 0.5,
+            },
+            Difficulty::Medium => AiLimits {
+                horizon: 1.,
+                reaction_delay: 0.2,
+                speed: 0.75,
+            },
+            Difficulty::Hard => AiLimits {
+                horizon: 2.,
+                reaction_delay: 0.05,
+                speed: 1.,
+            },
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+        }
+    }
+}
+
+/// Computer player steering one of the world paddles towards where the balls will cross
+/// its circle.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AiPaddle {
+    // Index in `World::paddles`
+    pub paddle: usize,
+    pub difficulty: Difficulty,
+    // Angle the paddle is heading to, its sector center when no ball is coming
+    pub target: Option<f32>,
+    // Seconds before the next decision
+    pub cooldown: f32,
+    path: Vec<Vec2>,
+}
+
+impl AiPaddle {
+    pub fn new(paddle: usize, difficulty: Difficulty) -> AiPaddle {
+        AiPaddle {
+            paddle,
+            difficulty,
+            target: None,
+            cooldown: 0.,
+            path: Vec::new(),
+        }
+    }
+
+    /// One computer player for every paddle the scenario gives a difficulty.
+    pub fn for_world(world: &World) -> Vec<AiPaddle> {
+        world
+            .scenario
+            .paddles
+            .iter()
+            .enumerate()
+            .filter_map(|(index, desc)| Some(AiPaddle::new(index, desc.ai?)))
+            .collect()
+    }
+
+    /// Decides where to go when the reaction delay is over, and sets the paddle input
+    /// for a step of `dt`.
+    pub fn update(&mut self, world: &mut World, dt: f32) {
+        let limits = self.difficulty.limits();
+        self.cooldown -= dt;
+        if self.cooldown <= 0. {
+            self.cooldown += limits.reaction_delay;
+            self.target = self.intercept(world, limits.horizon);
+        }
+
+        let paddle = &mut world.paddles[self.paddle];
+        let target = self.target.unwrap_or(paddle.sector_center());
+        let mut difference = target - paddle.angle;
+        if paddle.arc.is_none() {
+            difference = wrap_angle(difference);
+        }
+        // Full speed when far, slowing down so the paddle stops on the target
+        let full_step = paddle.speed / paddle.orbit * dt;
+        paddle.input = if full_step > 0. {
+            (difference / full_step).clamp(-limits.speed, limits.speed)
+        } else {
+            0.
+        };
+    }
+
+    /// Angle at which the first ball predicted to cross the paddle circle within `horizon`
+    /// seconds does so, None when none does in the paddle arc.
+    pub fn intercept(&mut self, world: &World, horizon: f32) -> Option<f32> {
+        let paddle = world.paddles[self.paddle];
+        let center = world.static_bodies[paddle.around].ball.position;
+        let steps = (horizon / world.physics.dt).ceil() as usize;
+
+        let mut best: Option<(usize, f32)> = None;
+        for ball in world.balls.values() {
+            if ball.landed_on.is_some() {
+                continue;
+            }
+            self.path.clear();
+            predict_path(world, ball, steps, &mut self.path);
+
+            for (step, pair) in self.path.windows(2).enumerate() {
+                if best.is_some_and(|(best_step, _)| best_step <= step) {
+                    break;
+                }
+                let (from, to) = (pair[0].distance(center), pair[1].distance(center));
+                if (from - paddle.orbit).signum() == (to - paddle.orbit).signum() {
+                    continue;
+                }
+
+                let t = (paddle.orbit - from) / (to - from);
+                let crossing = pair[0].lerp(pair[1], t) - center;
+                let angle = paddle.nearest_angle(crossing.y.atan2(crossing.x));
+                let reachable = paddle
+                    .arc
+                    .is_none_or(|(min, max)| (min..=max).contains(&angle));
+                if reachable {
+                    best = Some((step, angle));
+                    break;
+                }
+            }
+        }
+        best.map(|(_, angle)| angle)
+    }
+}
@@ -0,0 +1,222 @@
+// Generational arena, values keep the same id for their whole life and ids of removed
+// values are never given back by lookups even once their slot is reused.
+
+use std::ops::{Index, IndexMut};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ArenaId {
+    index: u32,
+    generation: u32,
+}
+
+impl ArenaId {
+    pub fn new(index: u32, generation: u32) -> ArenaId {
+        ArenaId { index, generation }
+    }
+
+    /// Slot of the value, unique among live values and below `Arena::slot_count`.
+    pub fn index(self) -> usize {
+        self.index as usize
+    }
+
+    pub fn generation(self) -> u32 {
+        self.generation
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Slot<T> {
+    // Incremented every time the value is removed
+    pub generation: u32,
+    pub value: Option<T>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Arena<T> {
+    slots: Vec<Slot<T>>,
+    // Empty slots, the last one is reused first
+    free: Vec<u32>,
+    len: usize,
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Arena<T> {
+        Arena::new()
+    }
+}
+
+impl<T> Arena<T> {
+    pub fn new() -> Arena<T> {
+        Arena {
+            slots: Vec::new(),
+            free: Vec::new(),
+            len: 0,
+        }
+    }
+
+    /// Rebuilds an arena from its slots and free list, as given by `slots` and `free_slots`.
+    /// Returns None when they do not match.
+    pub fn from_slots(slots: Vec<Slot<T>>, free: Vec<u32>) -> Option<Arena<T>> {
+        let empty = slots.iter().filter(|slot| slot.value.is_none()).count();
+        let mut listed = vec![false; slots.len()];
+        for &index in &free {
+            let slot = slots.get(index as usize)?;
+            if slot.value.is_some() || std::mem::replace(&mut listed[index as usize], true) {
+                return None;
+            }
+        }
+        if free.len() != empty {
+            return None;
+        }
+
+        let len = slots.len() - empty;
+        Some(Arena { slots, free, len })
+    }
+
+    pub fn slots(&self) -> &[Slot<T>] {
+        &self.slots
+    }
+
+    pub fn free_slots(&self) -> &[u32] {
+        &self.free
+    }
+
+    /// Number of slots, every id index is below it.
+    pub fn slot_count(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn insert(&mut self, value: T) -> ArenaId {
+        self.len += 1;
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.value = Some(value);
+                ArenaId::new(index, slot.generation)
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    value: Some(value),
+                });
+                ArenaId::new(self.slots.len() as u32 - 1, 0)
+            }
+        }
+    }
+
+    pub fn remove(&mut self, id: ArenaId) -> Option<T> {
+        let slot = self.slots.get_mut(id.index())?;
+        if slot.generation != id.generation {
+            return None;
+        }
+
+        let value = slot.value.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(id.index);
+        self.len -= 1;
+        Some(value)
+    }
+
+    /// Removes every value, ids given before stay invalid. Slots are then reused from the
+    /// first one, in the same order as in a new arena.
+    pub fn clear(&mut self) {
+        for slot in &mut self.slots {
+            if slot.value.take().is_some() {
+                slot.generation = slot.generation.wrapping_add(1);
+            }
+        }
+        self.free = (0..self.slots.len() as u32).rev().collect();
+        self.len = 0;
+    }
+
+    pub fn contains(&self, id: ArenaId) -> bool {
+        self.get(id).is_some()
+    }
+
+    pub fn get(&self, id: ArenaId) -> Option<&T> {
+        let slot = self.slots.get(id.index())?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        slot.value.as_ref()
+    }
+
+    pub fn get_mut(&mut self, id: ArenaId) -> Option<&mut T> {
+        let slot = self.slots.get_mut(id.index())?;
+        if slot.generation != id.generation {
+            return None;
+        }
+        slot.value.as_mut()
+    }
+
+    /// Both values at once, None if either is missing or both ids are the same.
+    pub fn get2_mut(&mut self, a: ArenaId, b: ArenaId) -> Option<(&mut T, &mut T)> {
+        if a.index == b.index || !self.contains(a) || !self.contains(b) {
+            return None;
+        }
+
+        let (first, second) = match a.index < b.index {
+            true => {
+                let (left, right) = self.slots.split_at_mut(b.index());
+                (&mut left[a.index()], &mut right[0])
+            }
+            false => {
+                let (left, right) = self.slots.split_at_mut(a.index());
+                (&mut right[0], &mut left[b.index()])
+            }
+        };
+        Some((first.value.as_mut()?, second.value.as_mut()?))
+    }
+
+    /// Values with their id, in slot order.
+    pub fn iter(&self) -> impl Iterator<Item = (ArenaId, &T)> + '_ {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let id = ArenaId::new(index as u32, slot.generation);
+            slot.value.as_ref().map(|value| (id, value))
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (ArenaId, &mut T)> + '_ {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                let id = ArenaId::new(index as u32, slot.generation);
+                slot.value.as_mut().map(|value| (id, value))
+            })
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = ArenaId> + '_ {
+        self.iter().map(|(id, _)| id)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &T> + '_ {
+        self.slots.iter().filter_map(|slot| slot.value.as_ref())
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut T> + '_ {
+        self.slots.iter_mut().filter_map(|slot| slot.value.as_mut())
+    }
+}
+
+impl<T> Index<ArenaId> for Arena<T> {
+    type Output = T;
+
+    fn index(&self, id: ArenaId) -> &T {
+        self.get(id).expect("no value for this arena id")
+    }
+}
+
+impl<T> IndexMut<ArenaId> for Arena<T> {
+    fn index_mut(&mut self, id: ArenaId) -> &mut T {
+        self.get_mut(id).expect("no value for this arena id")
+    }
+}
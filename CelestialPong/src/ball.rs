@@ -1,6 +1,13 @@
 use macroquad::prelude::*;
 
-use crate::quad_tree::{self, Rect};
+use crate::arena::ArenaId;
+use crate::capsule::segments_distance_squared;
+use crate::material::{Contact, Material};
+use crate::quad_tree;
+use crate::static_body::Landing;
+
+/// Stable handle on a ball of the world, stays valid until the ball is removed.
+pub type BallId = ArenaId;
 
 #[derive(Clone, Copy, Debug)]
 pub struct Ball {
@@ -10,18 +17,13 @@ pub struct Ball {
     pub radius: f32,
     pub mass: f32,
     pub color: Color,
-    playing_field: Rect,
+    pub material: Material,
+    // Set once the ball has landed on a static body, it then moves with it
+    pub landed_on: Option<Landing>,
 }
 
 impl Ball {
-    pub fn new(
-        position: Vec2,
-        velocity: Vec2,
-        radius: f32,
-        mass: f32,
-        color: Color,
-        playing_field: Rect,
-    ) -> Ball {
+    pub fn new(position: Vec2, velocity: Vec2, radius: f32, mass: f32, color: Color) -> Ball {
         Ball {
             position,
             prev_position: position - velocity,
@@ -29,14 +31,21 @@ impl Ball {
             radius,
             mass,
             color,
-            playing_field,
+            material: Material::default(),
+            landed_on: None,
         }
     }
 
     pub fn get_collision_area(&self) -> quad_tree::Rect {
-        let p = self.position;
-        let s = self.radius * 4.;
-        quad_tree::Rect::new(p.x, p.y, s, s)
+        quad_tree::Rect::around(self.position, Vec2::splat(self.radius))
+    }
+
+    // Area covered by the ball moving from `start` to its position, grown by `margin`
+    pub fn get_swept_area(&self, start: Vec2, margin: f32) -> quad_tree::Rect {
+        let min = self.position.min(start) - Vec2::splat(self.radius + margin);
+        let max = self.position.max(start) + Vec2::splat(self.radius + margin);
+        let center = (min + max) / 2.;
+        quad_tree::Rect::new(center.x, center.y, max.x - min.x, max.y - min.y)
     }
 
     pub fn draw(&self) {
@@ -46,22 +55,8 @@ impl Ball {
 
     pub fn update(&mut self, dt: f32, acc: Vec2) {
         self.velocity += acc * dt;
-        let pos = self.position;
-
-        if pos.x < self.playing_field.left && self.velocity.x < 0.
-            || pos.x > self.playing_field.right && self.velocity.x > 0.
-        {
-            self.velocity.x *= -1.;
-        }
-
-        if pos.y < self.playing_field.up && self.velocity.y < 0.
-            || pos.y > self.playing_field.down && self.velocity.y > 0.
-        {
-            self.velocity.y *= -1.;
-        }
-
         self.prev_position = self.position;
-        self.position = pos + self.velocity * dt;
+        self.position += self.velocity * dt;
     }
 
     pub fn update_verlet(&mut self, dt: f32, acc: Vec2) {
@@ -80,43 +75,96 @@ impl Ball {
         other.position.distance(self.position) <= other.radius + self.radius
     }
 
-    // Does collision effect for both self and the other object
-    // Based on https://www.vobarian.com/collisions/2dcollisions2.pdf
-    // The individual steps from the document are commented
-    pub fn collide(&mut self, other: &mut Ball, dt: f32) {
-        const HEAT_DISIPATION: f32 = 0.999;
-        let pos_diff = self.position - other.position;
-
-        // 1
-        let unit_normal = pos_diff.normalize();
-        let unit_tangent = Vec2::from((-unit_normal.y, unit_normal.x));
-
-        // 3
-        let v1n = self.velocity.dot(unit_normal);
-        let v1t = self.velocity.dot(unit_tangent) * HEAT_DISIPATION;
-        let v2n = other.velocity.dot(unit_normal);
-        let v2t = other.velocity.dot(unit_tangent) * HEAT_DISIPATION;
-
-        // 5
-        let new_v1n =
-            (v1n * (self.mass - other.mass) + 2. * other.mass * v2n) / (self.mass + other.mass);
-        let new_v2n =
-            (v2n * (other.mass - self.mass) + 2. * self.mass * v1n) / (self.mass + other.mass);
-
-        // 6
-        let final_v1n = new_v1n * unit_normal;
-        let final_v1t = v1t * unit_tangent;
-        let final_v2n = new_v2n * unit_normal;
-        let final_v2t = v2t * unit_tangent;
-
-        // 7
-        let final_v1 = final_v1n + final_v1t;
-        let final_v2 = final_v2n + final_v2t;
-
-        // The if statement makes them not get stuck in each other
-        if (self.velocity - other.velocity).dot(self.position - other.position) < 0. {
-            self.set_velocity(final_v1, dt);
-            other.set_velocity(final_v2, dt);
+    // Continuous collision detection: self moved in a straight line from `start` to its
+    // position during the step, and other from `other_start`. Returns the fraction of the
+    // step at which they first touch.
+    pub fn time_of_impact(&self, start: Vec2, other: &Ball, other_start: Vec2) -> Option<f32> {
+        let contact = self.radius + other.radius;
+
+        // If the two paths never get close enough the balls cannot have met
+        let paths_distance =
+            segments_distance_squared(start, self.position, other_start, other.position);
+        if paths_distance > contact * contact {
+            return None;
+        }
+
+        // Solve |d0 + v * t| = contact for the relative motion
+        let d0 = start - other_start;
+        let v = (self.position - other.position) - d0;
+        let c = d0.length_squared() - contact * contact;
+        if c <= 0. {
+            return Some(0.);
+        }
+
+        let a = v.length_squared();
+        let b = 2. * d0.dot(v);
+        let discriminant = b * b - 4. * a * c;
+        if a <= f32::EPSILON || discriminant < 0. {
+            return None;
         }
+
+        let t = (-b - discriminant.sqrt()) / (2. * a);
+        (0. ..=1.).contains(&t).then_some(t)
+    }
+
+    // Does collision effect for both self and the other object
+    // With a restitution of 1 and no friction it is a perfectly elastic collision,
+    // see https://www.vobarian.com/collisions/2dcollisions2.pdf
+    // Returns the speed at which they were approaching, None if they were moving apart.
+    pub fn collide(&mut self, other: &mut Ball, dt: f32) -> Option<f32> {
+        let contact = self.material.combine(&other.material);
+        // Zero for balls at the same place, which then do not collide
+        let unit_normal = (self.position - other.position).normalize_or_zero();
+        let inverse_mass = 1. / self.mass + 1. / other.mass;
+        let relative_velocity = self.velocity - other.velocity;
+
+        // Returning early makes them not get stuck in each other
+        let impulse = contact_impulse(relative_velocity, unit_normal, contact, inverse_mass)?;
+
+        self.set_velocity(self.velocity + impulse / self.mass, dt);
+        other.set_velocity(other.velocity - impulse / other.mass, dt);
+        Some(-relative_velocity.dot(unit_normal))
+    }
+
+    // Bounces off a body too heavy to be pushed back. Returns the speed at which the ball
+    // was approaching the surface, or None if it was already moving away.
+    pub fn bounce_off(&mut self, body: &Ball, dt: f32) -> Option<f32> {
+        let contact = self.material.combine(&body.material);
+        let unit_normal = (self.position - body.position).normalize_or_zero();
+        let relative_velocity = self.velocity - body.velocity;
+        let impulse = contact_impulse(relative_velocity, unit_normal, contact, 1. / self.mass)?;
+
+        self.set_velocity(self.velocity + impulse / self.mass, dt);
+        Some(-relative_velocity.dot(unit_normal))
     }
 }
+
+// Impulse to apply to the first of two touching objects, the second one gets the opposite.
+// Along the normal it is scaled by the restitution, along the tangent it is bounded by
+// Coulomb friction. None when the objects are already separating.
+fn contact_impulse(
+    relative_velocity: Vec2,
+    unit_normal: Vec2,
+    contact: Contact,
+    inverse_mass: f32,
+) -> Option<Vec2> {
+    let normal_speed = relative_velocity.dot(unit_normal);
+    // Also rejects NaN, so a degenerate normal never corrupts the velocities
+    if normal_speed.is_nan() || normal_speed >= 0. {
+        return None;
+    }
+
+    let normal_impulse = -(1. + contact.restitution) * normal_speed / inverse_mass;
+
+    // Friction can at most stop the sliding, never reverse it
+    let tangent_velocity = relative_velocity - normal_speed * unit_normal;
+    let sliding_speed = tangent_velocity.length();
+    let tangent_impulse = if sliding_speed > f32::EPSILON {
+        let magnitude = (contact.friction * normal_impulse).min(sliding_speed / inverse_mass);
+        -tangent_velocity / sliding_speed * magnitude
+    } else {
+        Vec2::ZERO
+    };
+
+    Some(normal_impulse * unit_normal + tangent_impulse)
+}
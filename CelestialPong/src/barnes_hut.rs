@@ -0,0 +1,198 @@
+// Barnes-Hut approximation of the gravity field
+// https://en.wikipedia.org/wiki/Barnes%E2%80%93Hut_simulation
+
+use macroquad::prelude::*;
+
+use crate::gravity::{get_gravity_acceleration, Attractor};
+
+const NO_BODY: u32 = u32::MAX;
+// Past this depth attractors share a leaf, happens when they are at the same position
+const MAX_DEPTH: usize = 32;
+
+#[derive(Clone, Copy, Debug)]
+struct Node {
+    center: Vec2,
+    half_size: f32,
+    mass: f32,
+    // Sum of mass * position while building, center of mass once built
+    center_of_mass: Vec2,
+    // Index of the first of the 4 consecutive children, 0 for leaves
+    children: u32,
+    // Head of the list of attractors in this leaf, linked by `next_body`
+    first_body: u32,
+}
+
+impl Node {
+    fn new(center: Vec2, half_size: f32) -> Node {
+        Node {
+            center,
+            half_size,
+            mass: 0.,
+            center_of_mass: Vec2::ZERO,
+            children: 0,
+            first_body: NO_BODY,
+        }
+    }
+
+    fn contains(&self, position: Vec2) -> bool {
+        (position - self.center).abs().max_element() <= self.half_size
+    }
+
+    fn quadrant(&self, position: Vec2) -> u32 {
+        (position.x >= self.center.x) as u32 + 2 * (position.y >= self.center.y) as u32
+    }
+}
+
+/// Quad tree storing the total mass and center of mass of every node, so far away
+/// groups of attractors can be approximated by a single one.
+/// Nodes are kept between builds so rebuilding every step does not allocate.
+#[derive(Clone, Debug, Default)]
+pub struct BarnesHutTree {
+    nodes: Vec<Node>,
+    next_body: Vec<u32>,
+}
+
+impl BarnesHutTree {
+    pub fn new() -> BarnesHutTree {
+        BarnesHutTree::default()
+    }
+
+    pub fn build(&mut self, attractors: &[Attractor]) {
+        self.nodes.clear();
+        self.next_body.clear();
+        self.next_body.resize(attractors.len(), NO_BODY);
+
+        let Some(first) = attractors.first() else {
+            return;
+        };
+
+        let (min, max) = attractors
+            .iter()
+            .fold((first.position, first.position), |(min, max), a| {
+                (min.min(a.position), max.max(a.position))
+            });
+        let half_size = ((max - min).max_element() / 2.).max(1.);
+        self.nodes.push(Node::new((min + max) / 2., half_size));
+
+        for index in 0..attractors.len() {
+            self.insert(0, index as u32, attractors, 0);
+        }
+
+        for node in self.nodes.iter_mut() {
+            if node.mass > 0. {
+                node.center_of_mass /= node.mass;
+            }
+        }
+    }
+
+    fn insert(&mut self, node_index: usize, body: u32, attractors: &[Attractor], depth: usize) {
+        let attractor = attractors[body as usize];
+        let node = &mut self.nodes[node_index];
+        node.mass += attractor.mass;
+        node.center_of_mass += attractor.position * attractor.mass;
+
+        if node.children != 0 {
+            let child = (node.children + node.quadrant(attractor.position)) as usize;
+            self.insert(child, body, attractors, depth + 1);
+            return;
+        }
+
+        if node.first_body == NO_BODY {
+            node.first_body = body;
+            return;
+        }
+
+        if depth >= MAX_DEPTH {
+            self.next_body[body as usize] = node.first_body;
+            node.first_body = body;
+            return;
+        }
+
+        // Split the leaf and push both attractors down
+        let previous = node.first_body;
+        node.first_body = NO_BODY;
+        self.subdivide(node_index);
+
+        let node = self.nodes[node_index];
+        let child = node.children + node.quadrant(attractors[previous as usize].position);
+        self.insert(child as usize, previous, attractors, depth + 1);
+        let child = node.children + node.quadrant(attractor.position);
+        self.insert(child as usize, body, attractors, depth + 1);
+    }
+
+    fn subdivide(&mut self, node_index: usize) {
+        let node = self.nodes[node_index];
+        let quarter = node.half_size / 2.;
+        self.nodes[node_index].children = self.nodes.len() as u32;
+        for offset in [
+            Vec2::new(-quarter, -quarter),
+            Vec2::new(quarter, -quarter),
+            Vec2::new(-quarter, quarter),
+            Vec2::new(quarter, quarter),
+        ] {
+            self.nodes.push(Node::new(node.center + offset, quarter));
+        }
+    }
+
+    /// Approximate acceleration at `position`, ignoring the attractor at index `skip`.
+    /// A node is used as a single attractor when its size over its distance is below `theta`,
+    /// a `theta` of 0 gives the exact sum.
+    pub fn acceleration(
+        &self,
+        position: Vec2,
+        attractors: &[Attractor],
+        skip: Option<usize>,
+        theta: f32,
+        gravity: f32,
+        softening: f32,
+    ) -> Vec2 {
+        if self.nodes.is_empty() {
+            return Vec2::ZERO;
+        }
+
+        let skip_position = skip.map(|index| attractors[index].position);
+        let mut acceleration = Vec2::ZERO;
+        let mut stack = [0u32; MAX_DEPTH * 3 + 4];
+        let mut stack_size = 1;
+        while stack_size > 0 {
+            stack_size -= 1;
+            let node = &self.nodes[stack[stack_size] as usize];
+            if node.mass <= 0. {
+                continue;
+            }
+
+            if node.children == 0 {
+                let mut body = node.first_body;
+                while body != NO_BODY {
+                    if skip != Some(body as usize) {
+                        let attractor = &attractors[body as usize];
+                        acceleration +=
+                            get_gravity_acceleration(position, attractor, gravity, softening);
+                    }
+                    body = self.next_body[body as usize];
+                }
+                continue;
+            }
+
+            // Nodes holding the query point or the skipped attractor are always opened
+            let distance = node.center_of_mass.distance(position);
+            let far_enough = node.half_size * 2. < theta * distance
+                && !node.contains(position)
+                && !skip_position.is_some_and(|p| node.contains(p));
+            if far_enough {
+                let group = Attractor {
+                    position: node.center_of_mass,
+                    mass: node.mass,
+                };
+                acceleration += get_gravity_acceleration(position, &group, gravity, softening);
+            } else {
+                for child in 0..4 {
+                    stack[stack_size] = node.children + child;
+                    stack_size += 1;
+                }
+            }
+        }
+
+        acceleration
+    }
+}
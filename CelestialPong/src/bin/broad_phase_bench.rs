@@ -0,0 +1,95 @@
+// Runs a scenario with every broad phase and reports how long the steps take and how
+// many overlapping pairs each one finds.
+// Usage: broad_phase_bench [scenario.toml] [steps]
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use celestial_pong::broad_phase::BroadPhaseKind;
+use celestial_pong::scenario::{BallPopulation, Scenario};
+use celestial_pong::world::World;
+
+const DEFAULT_SCENARIO: &str = "scenarios/broad_phase_bench.toml";
+const DEFAULT_STEPS: usize = 200;
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let path = PathBuf::from(args.next().unwrap_or_else(|| DEFAULT_SCENARIO.to_owned()));
+    let steps = match args.next().map(|steps| steps.parse::<usize>()) {
+        Some(Ok(steps)) => steps,
+        Some(Err(err)) => {
+            eprintln!("Invalid step count: {}", err);
+            return;
+        }
+        None => DEFAULT_STEPS,
+    };
+
+    let scenario = match Scenario::load(&path) {
+        Ok(scenario) => scenario,
+        Err(err) => {
+            eprintln!("Invalid scenario: {}", err);
+            return;
+        }
+    };
+
+    // The grid of the scenario if it has one, otherwise cells fitting the largest ball
+    let cell_size = match scenario.physics.broad_phase {
+        BroadPhaseKind::Grid { cell_size } => cell_size,
+        _ => {
+            let largest = scenario
+                .balls
+                .iter()
+                .map(|population| match population {
+                    BallPopulation::Explicit { radius, .. } => *radius,
+                    BallPopulation::Orbital { radius, .. } => *radius,
+                    BallPopulation::Elements { radius, .. } => *radius,
+                })
+                .fold(1., f32::max);
+            largest * 4.
+        }
+    };
+
+    let kinds = [
+        BroadPhaseKind::QuadTree,
+        BroadPhaseKind::Grid { cell_size },
+        BroadPhaseKind::SortAndSweep,
+    ];
+
+    println!("{} for {} steps", path.display(), steps);
+    println!(
+        "{:<20}{:>12}{:>12}{:>14}{:>14}{:>8}",
+        "broad phase", "step (ms)", "pairs (ms)", "pairs (mean)", "pairs (last)", "balls"
+    );
+    for kind in kinds {
+        let mut scenario = scenario.clone();
+        scenario.physics.broad_phase = kind;
+        let mut world = World::new(scenario);
+
+        let mut step_time = Duration::ZERO;
+        let mut pair_time = Duration::ZERO;
+        let mut pairs = Vec::new();
+        let mut pair_count = 0;
+        for _ in 0..steps {
+            let start = Instant::now();
+            world.step(world.physics.dt);
+            step_time += start.elapsed();
+
+            pairs.clear();
+            let start = Instant::now();
+            world.broad_phase.overlapping_pairs(&mut pairs);
+            pair_time += start.elapsed();
+            pair_count += pairs.len();
+        }
+
+        let per_step = |time: Duration| time.as_secs_f64() * 1000. / steps.max(1) as f64;
+        println!(
+            "{:<20}{:>12.3}{:>12.3}{:>14.1}{:>14}{:>8}",
+            world.broad_phase.name(),
+            per_step(step_time),
+            per_step(pair_time),
+            pair_count as f64 / steps.max(1) as f64,
+            pairs.len(),
+            world.balls.len()
+        );
+    }
+}
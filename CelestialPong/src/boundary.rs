@@ -0,0 +1,96 @@
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::ball::Ball;
+use crate::quad_tree::Rect;
+
+/// What happens to balls reaching the edge of the playing field.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BoundaryPolicy {
+    /// Balls bounce off the edges
+    #[default]
+    Reflect,
+    /// Balls leaving on one side come back on the opposite one
+    Wrap,
+    /// Balls leaving the field are removed
+    Despawn,
+    /// There are no edges, the spatial index grows to hold every ball
+    Unbounded,
+}
+
+impl BoundaryPolicy {
+    /// Keeps the ball inside `field` for the reflecting and wrapping policies.
+    /// Returns true if the ball is outside the field after that.
+    pub fn apply(self, ball: &mut Ball, field: &Rect, dt: f32) -> bool {
+        let min = Vec2::new(field.left, field.up);
+        let max = Vec2::new(field.right, field.down);
+        match self {
+            BoundaryPolicy::Reflect => {
+                let mut velocity = ball.velocity;
+                for axis in 0..2 {
+                    if ball.position[axis] < min[axis] && velocity[axis] < 0.
+                        || ball.position[axis] > max[axis] && velocity[axis] > 0.
+                    {
+                        velocity[axis] *= -1.;
+                    }
+                }
+                // Rect::contains excludes the right and bottom edges, stay just inside them
+                // so the broad phase keeps the ball
+                let inside = max - f32::EPSILON * max.abs().max(Vec2::ONE);
+                let position = ball.position.clamp(min, inside);
+                if position != ball.position || velocity != ball.velocity {
+                    ball.position = position;
+                    ball.set_velocity(velocity, dt);
+                }
+                false
+            }
+            BoundaryPolicy::Wrap => {
+                let wrapped = Vec2::new(
+                    wrap(ball.position.x, min.x, max.x),
+                    wrap(ball.position.y, min.y, max.y),
+                );
+                if wrapped != ball.position {
+                    ball.position = wrapped;
+                    ball.set_velocity(ball.velocity, dt);
+                }
+                false
+            }
+            BoundaryPolicy::Despawn | BoundaryPolicy::Unbounded => !field.contains(ball.position),
+        }
+    }
+}
+
+fn wrap(value: f32, min: f32, max: f32) -> f32 {
+    let wrapped = (value - min).rem_euclid(max - min) + min;
+    // Rounding can land exactly on the excluded edge
+    if wrapped >= max {
+        min
+    } else {
+        wrapped
+    }
+}
+
+/// Smallest area grown from `field` by doubling its size that contains every position,
+/// so the spatial index of an unbounded world does not change at every step.
+pub fn grown_area(field: &Rect, positions: impl Iterator<Item = Vec2>) -> Rect {
+    let center = Vec2::new(field.x, field.y);
+    let half_size = Vec2::new(field.half_width, field.half_height);
+    let reach = positions
+        .filter(|position| position.is_finite())
+        .fold(Vec2::ZERO, |reach, position| {
+            reach.max((position - center).abs())
+        });
+
+    let mut scale = 1.;
+    // Rect::contains excludes the right and bottom edges
+    while reach.cmpge(half_size * scale).any() && scale < 1e12 {
+        scale *= 2.;
+    }
+    Rect::new(
+        center.x,
+        center.y,
+        half_size.x * scale * 2.,
+        half_size.y * scale * 2.,
+    )
+}
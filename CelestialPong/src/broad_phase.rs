@@ -0,0 +1,136 @@
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::quad_tree::{Collider, QuadTree, QuadTreeConfig, QuadTreeEntry, Rect};
+use crate::sort_and_sweep::SortAndSweep;
+use crate::spatial_hash::SpatialHashGrid;
+
+/// Spatial index of the bounding boxes of balls and static bodies, used to find the
+/// pairs that may collide without testing every pair.
+///
+/// Changes made by `update` and `remove` are only seen by queries once `refresh` is
+/// called, so structures can batch them.
+pub trait BroadPhase {
+    fn name(&self) -> &'static str;
+
+    /// Area entries must be centered in, None when there is no limit.
+    fn area(&self) -> Option<&Rect>;
+
+    /// Removes every entry, keeping the memory for later use.
+    fn clear(&mut self);
+
+    /// Moves the entry of `payload` to `bounds`, adding it if it is missing.
+    /// Returns false, and removes the entry, if it cannot be stored there.
+    fn update(&mut self, payload: Collider, bounds: Rect) -> bool;
+
+    /// Returns false if there was no entry for `payload`.
+    fn remove(&mut self, payload: Collider) -> bool;
+
+    /// Makes the changes since the last call visible to queries.
+    fn refresh(&mut self) {}
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Pushes every entry whose bounds overlap `query`.
+    fn query_entries(&self, query: &Rect, result: &mut Vec<QuadTreeEntry>);
+
+    /// Pushes every entry whose bounds are at most `radius` away from `center`.
+    fn query_radius_entries(&self, center: Vec2, radius: f32, result: &mut Vec<QuadTreeEntry>) {
+        let start = result.len();
+        self.query_entries(&Rect::around(center, Vec2::splat(radius)), result);
+        let mut kept = start;
+        for index in start..result.len() {
+            if result[index].bounds.distance_squared(center) <= radius * radius {
+                result[kept] = result[index];
+                kept += 1;
+            }
+        }
+        result.truncate(kept);
+    }
+
+    /// Pushes every pair of entries whose bounds overlap, once, smallest payload first.
+    fn overlapping_pairs(&self, result: &mut Vec<(Collider, Collider)>);
+
+    fn debug_draw(&self) {}
+}
+
+// Pair with the smallest payload first
+pub(crate) fn ordered(a: Collider, b: Collider) -> (Collider, Collider) {
+    (a.min(b), a.max(b))
+}
+
+/// Broad phase structure used by a world, set per scenario.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum BroadPhaseKind {
+    /// Loose quad tree shaped by `physics.quad_tree`, fits any mix of sizes
+    #[default]
+    QuadTree,
+    /// Uniform grid hashed by cell, best when everything has about the size of a cell
+    Grid { cell_size: f32 },
+    /// Entries sorted along the x axis, best for many similar small entries
+    SortAndSweep,
+}
+
+impl BroadPhaseKind {
+    /// A new empty structure. `area` is the area the quad tree covers.
+    pub fn build(self, area: Rect, quad_tree: QuadTreeConfig) -> Box<dyn BroadPhase> {
+        match self {
+            BroadPhaseKind::QuadTree => Box::new(QuadTree::with_config(area, quad_tree)),
+            BroadPhaseKind::Grid { cell_size } => Box::new(SpatialHashGrid::new(cell_size)),
+            BroadPhaseKind::SortAndSweep => Box::new(SortAndSweep::new()),
+        }
+    }
+}
+
+impl BroadPhase for QuadTree {
+    fn name(&self) -> &'static str {
+        "Quad tree"
+    }
+
+    fn area(&self) -> Option<&Rect> {
+        Some(QuadTree::area(self))
+    }
+
+    fn clear(&mut self) {
+        QuadTree::clear(self)
+    }
+
+    fn update(&mut self, payload: Collider, bounds: Rect) -> bool {
+        QuadTree::update(self, payload, bounds)
+    }
+
+    fn remove(&mut self, payload: Collider) -> bool {
+        QuadTree::remove(self, payload)
+    }
+
+    fn len(&self) -> usize {
+        QuadTree::len(self)
+    }
+
+    fn query_entries(&self, query: &Rect, result: &mut Vec<QuadTreeEntry>) {
+        QuadTree::query_entries(self, query, result)
+    }
+
+    fn query_radius_entries(&self, center: Vec2, radius: f32, result: &mut Vec<QuadTreeEntry>) {
+        QuadTree::query_radius_entries(self, center, radius, result)
+    }
+
+    fn overlapping_pairs(&self, result: &mut Vec<(Collider, Collider)>) {
+        for entry in self.entries() {
+            for other in self.query(&entry.bounds) {
+                if entry.payload < other.payload {
+                    result.push((entry.payload, other.payload));
+                }
+            }
+        }
+    }
+
+    fn debug_draw(&self) {
+        QuadTree::debug_draw(self)
+    }
+}
@@ -1,18 +1,58 @@
 use macroquad::prelude::*;
 
+use crate::ball::Ball;
+use crate::material::Material;
+use crate::quad_tree::Rect;
+
+/// Every point within `radius` of the segment `p1`-`p2`. Walls and paddles are capsules,
+/// gravity does not move them, only their velocity does. Balls bounce off them as if
+/// they were infinitely heavy.
 #[derive(Clone, Copy, Debug)]
-struct Capsule {
-    p1: Vec2,
-    p2: Vec2,
-    radius: f32,
-    color: Color,
+pub struct Capsule {
+    pub p1: Vec2,
+    pub p2: Vec2,
+    pub radius: f32,
+    pub color: Color,
+    // Added to both ends every step
+    pub velocity: Vec2,
+    pub material: Material,
+    // Fraction of its velocity along the segment given to the balls bouncing off it,
+    // paddles use it to deflect balls
+    pub transfer: f32,
+}
+
+/// Where a ball touches a capsule.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CapsuleContact {
+    // Closest point of the capsule segment to the ball center
+    pub point: Vec2,
+    // Unit vector from the capsule towards the ball
+    pub normal: Vec2,
+    // How deep the ball is inside the capsule
+    pub penetration: f32,
+}
+
+/// Closest points of two segments, S1(s) = P1 + s * (Q1 - P1) and S2(t) = P2 + t * (Q2 - P2).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClosestPoints {
+    pub s: f32,
+    pub t: f32,
+    // S1(s) and S2(t)
+    pub c1: Vec2,
+    pub c2: Vec2,
+}
+
+impl ClosestPoints {
+    pub fn distance_squared(&self) -> f32 {
+        self.c1.distance_squared(self.c2)
+    }
 }
 
 // From : https://arrowinmyknee.com/2021/03/15/some-math-about-capsule-collision/
-// Computes closest points C1 and C2 of S1(s)=P1+s*(Q1-P1) and
-// S2(t)=P2+t*(Q2-P2), returning s and t. Function result is squared
-// distance between between S1(s) and S2(t)
-fn distance_point_segment_squared(p1: Vec2, q1: Vec2, p2: Vec2, q2: Vec2) -> f32 {
+/// Computes closest points C1 and C2 of S1(s)=P1+s*(Q1-P1) and S2(t)=P2+t*(Q2-P2).
+/// Segments of length 0 are points. When the segments are parallel and several pairs of
+/// points are the closest, the one closest to P1 is given.
+pub fn closest_points(p1: Vec2, q1: Vec2, p2: Vec2, q2: Vec2) -> ClosestPoints {
     let d1 = q1 - p1; // Direction vector of segment S1
     let d2 = q2 - p2; // Direction vector of segment S2
     let r = p1 - p2;
@@ -25,10 +65,9 @@ fn distance_point_segment_squared(p1: Vec2, q1: Vec2, p2: Vec2, q2: Vec2) -> f32
     // Check if either or both segments degenerate into points
     if a <= f32::EPSILON && e <= f32::EPSILON {
         // Both segments degenerate into points
-        return Vec2::dot(p1 - p2, p1 - p2);
-    }
-
-    if a <= f32::EPSILON {
+        s = 0.;
+        t = 0.;
+    } else if a <= f32::EPSILON {
         // First segment degenerates into a point
         s = 0.0;
         t = f / e; // s = 0 => t = (b*s + f) / e = f / e
@@ -68,25 +107,214 @@ fn distance_point_segment_squared(p1: Vec2, q1: Vec2, p2: Vec2, q2: Vec2) -> f32
         }
     }
 
-    let c1 = p1 + d1 * s;
-    let c2 = p2 + d2 * t;
-    return Vec2::dot(c1 - c2, c1 - c2);
+    ClosestPoints {
+        s,
+        t,
+        c1: p1 + d1 * s,
+        c2: p2 + d2 * t,
+    }
+}
+
+/// Squared distance between the segments P1-Q1 and P2-Q2.
+pub fn segments_distance_squared(p1: Vec2, q1: Vec2, p2: Vec2, q2: Vec2) -> f32 {
+    closest_points(p1, q1, p2, q2).distance_squared()
+}
+
+// First distance in front of `origin`, in multiples of `direction`, at which the ray
+// meets the circle. The origin is outside of it.
+fn ray_circle_distance(origin: Vec2, direction: Vec2, center: Vec2, radius: f32) -> Option<f32> {
+    let offset = origin - center;
+    let a = direction.length_squared();
+    if a <= f32::EPSILON {
+        return None;
+    }
+    let b = offset.dot(direction);
+    let c = offset.length_squared() - radius * radius;
+    let discriminant = b * b - a * c;
+    if discriminant < 0. {
+        return None;
+    }
+    let t = (-b - discriminant.sqrt()) / a;
+    (t >= 0.).then_some(t)
 }
 
 impl Capsule {
-    fn new(p1: Vec2, p2: Vec2, r: f32, color: Color) -> Capsule {
-        return Capsule {
-            p1: p1,
-            p2: p2,
+    pub fn new(p1: Vec2, p2: Vec2, r: f32, color: Color) -> Capsule {
+        Capsule {
+            p1,
+            p2,
             radius: r,
-            color: color,
+            color,
+            velocity: Vec2::ZERO,
+            material: Material::default(),
+            transfer: 0.,
+        }
+    }
+
+    pub fn bounds(&self) -> Rect {
+        let min = self.p1.min(self.p2) - Vec2::splat(self.radius);
+        let max = self.p1.max(self.p2) + Vec2::splat(self.radius);
+        Rect::from_edges(min.x, max.x, min.y, max.y)
+    }
+
+    /// Moves both ends by `offset`.
+    pub fn translate(&mut self, offset: Vec2) {
+        self.p1 += offset;
+        self.p2 += offset;
+    }
+
+    /// Point of the segment closest to `point`.
+    pub fn closest_point(&self, point: Vec2) -> Vec2 {
+        let segment = self.p2 - self.p1;
+        let length_squared = segment.length_squared();
+        if length_squared <= f32::EPSILON {
+            return self.p1;
+        }
+        let t = ((point - self.p1).dot(segment) / length_squared).clamp(0., 1.);
+        self.p1 + segment * t
+    }
+
+    pub fn contains_point(&self, point: Vec2) -> bool {
+        point.distance_squared(self.closest_point(point)) <= self.radius * self.radius
+    }
+
+    pub fn overlaps_circle(&self, center: Vec2, radius: f32) -> bool {
+        let reach = self.radius + radius;
+        center.distance_squared(self.closest_point(center)) <= reach * reach
+    }
+
+    pub fn overlaps_rect(&self, rect: &Rect) -> bool {
+        rect.segment_distance_squared(self.p1, self.p2) <= self.radius * self.radius
+    }
+
+    /// Distance along the ray, in multiples of `direction`, at which it enters the capsule,
+    /// 0 when `origin` is inside. None when it misses within `max_distance`.
+    pub fn ray_distance(&self, origin: Vec2, direction: Vec2, max_distance: f32) -> Option<f32> {
+        if self.contains_point(origin) {
+            return Some(0.);
+        }
+
+        let mut nearest: Option<f32> = None;
+        for center in [self.p1, self.p2] {
+            if let Some(t) = ray_circle_distance(origin, direction, center, self.radius) {
+                nearest = Some(nearest.map_or(t, |nearest| nearest.min(t)));
+            }
+        }
+
+        // The sides, as a box in the frame of the segment
+        let segment = self.p2 - self.p1;
+        let length = segment.length();
+        if length > f32::EPSILON {
+            let axis = segment / length;
+            let normal = axis.perp();
+            let local = |v: Vec2| Vec2::new(v.dot(axis), v.dot(normal));
+            let sides = Rect::from_edges(0., length, -self.radius, self.radius);
+            if let Some(t) =
+                sides.ray_distance(local(origin - self.p1), local(direction), max_distance)
+            {
+                nearest = Some(nearest.map_or(t, |nearest| nearest.min(t)));
+            }
+        }
+        nearest.filter(|t| *t <= max_distance)
+    }
+
+    // Closest point of the segment and unit vector from it towards `position`
+    fn surface_normal(&self, position: Vec2) -> (Vec2, Vec2) {
+        let point = self.closest_point(position);
+        // A position on the segment is pushed out sideways
+        let side = (self.p2 - self.p1)
+            .perp()
+            .try_normalize()
+            .unwrap_or(Vec2::Y);
+        (point, (position - point).try_normalize().unwrap_or(side))
+    }
+
+    pub fn contact(&self, ball: &Ball) -> Option<CapsuleContact> {
+        let (point, normal) = self.surface_normal(ball.position);
+        let distance = ball.position.distance(point);
+        let reach = self.radius + ball.radius;
+        (distance <= reach).then_some(CapsuleContact {
+            point,
+            normal,
+            penetration: reach - distance,
+        })
+    }
+
+    /// Continuous collision detection: the capsule moved by `displacement` during the step
+    /// and the ball in a straight line from `start` to its position. Returns the fraction
+    /// of the step at which they first touch.
+    pub fn time_of_impact(&self, displacement: Vec2, start: Vec2, ball: &Ball) -> Option<f32> {
+        // In the frame of the capsule at the end of the step
+        let from = start + displacement;
+        let path = ball.position - from;
+        let reach = self.radius + ball.radius;
+        let gap = |t: f32| {
+            let position = from + path * t;
+            position.distance(self.closest_point(position)) - reach
         };
+
+        let segment_distance = segments_distance_squared(from, ball.position, self.p1, self.p2);
+        if segment_distance > reach * reach {
+            return None;
+        }
+
+        // Conservative advancement: the ball cannot reach the capsule before covering the gap
+        let speed = path.length();
+        let mut t = 0.;
+        for _ in 0..32 {
+            let gap = gap(t);
+            if gap <= reach * 1e-3 {
+                return Some(t);
+            }
+            if speed <= f32::EPSILON {
+                return None;
+            }
+            t += gap / speed;
+            if t > 1. {
+                return None;
+            }
+        }
+        Some(t)
+    }
+
+    /// Bounces `ball` off the capsule with the restitution and friction of both materials,
+    /// then adds the `transfer` part of the capsule velocity along its segment.
+    /// Returns the speed at which the ball was approaching the surface, or None if it was
+    /// already moving away.
+    pub fn bounce(&self, ball: &mut Ball, dt: f32) -> Option<f32> {
+        let (_, normal) = self.surface_normal(ball.position);
+        // The part of the capsule touched behaves like a ball centered on the segment
+        let mut surface = Ball::new(
+            ball.position - normal * (self.radius + ball.radius),
+            self.velocity,
+            self.radius,
+            f32::INFINITY,
+            self.color,
+        );
+        surface.material = self.material;
+        let speed = ball.bounce_off(&surface, dt)?;
+
+        if let Some(axis) = (self.p2 - self.p1).try_normalize() {
+            let carried = axis * self.velocity.dot(axis) * self.transfer;
+            ball.set_velocity(ball.velocity + carried, dt);
+        }
+        Some(speed)
+    }
+
+    /// Pushes `ball` out of the capsule by `correction` times the overlap.
+    pub fn push_out(&self, ball: &mut Ball, correction: f32, dt: f32) {
+        if let Some(contact) = self.contact(ball) {
+            if contact.penetration > 0. {
+                ball.position += contact.normal * contact.penetration * correction;
+                ball.set_velocity(ball.velocity, dt);
+            }
+        }
     }
 
-    fn draw(&self) {
+    pub fn draw(&self) {
         draw_circle_lines(self.p1.x, self.p1.y, self.radius, 2., self.color);
         draw_circle_lines(self.p2.x, self.p2.y, self.radius, 2., self.color);
-        let dir = (self.p2 - self.p1).normalize();
+        let dir = (self.p2 - self.p1).try_normalize().unwrap_or(Vec2::X);
         let cr = vec2(dir.y, -dir.x) * self.radius;
         draw_line(
             self.p1.x + cr.x,
@@ -106,9 +334,9 @@ impl Capsule {
         );
     }
 
-    fn overlap(caps1: Capsule, caps2: Capsule) -> bool {
-        let dist = distance_point_segment_squared(caps1.p1, caps1.p2, caps2.p1, caps2.p2);
+    pub fn overlap(caps1: Capsule, caps2: Capsule) -> bool {
+        let dist = segments_distance_squared(caps1.p1, caps1.p2, caps2.p1, caps2.p2);
         let r = caps1.radius + caps2.radius;
-        return dist <= (r * r);
+        dist <= (r * r)
     }
 }
@@ -0,0 +1,142 @@
+use std::io::{self, Write};
+use std::path::Path;
+
+use macroquad::prelude::*;
+
+use crate::ball::Ball;
+use crate::world::World;
+
+/// Conserved quantities of a world at a given step, used to check a run is physically sane.
+/// Sums are done in f64 so thousands of balls do not drown the drift in rounding.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Diagnostics {
+    pub step: u64,
+    pub kinetic_energy: f64,
+    // Against the static bodies, plus between balls and between bodies when they attract
+    pub potential_energy: f64,
+    pub momentum: DVec2,
+    // About the primary body (the first static body), in its frame
+    pub angular_momentum: f64,
+}
+
+fn pair_potential(a: &Ball, b: &Ball, gravity: f64, softening: f64) -> f64 {
+    let distance_squared = a
+        .position
+        .as_dvec2()
+        .distance_squared(b.position.as_dvec2());
+    let distance = (distance_squared + softening * softening).sqrt();
+    if distance <= f64::EPSILON {
+        return 0.;
+    }
+
+    -gravity * a.mass as f64 * b.mass as f64 / distance
+}
+
+impl Diagnostics {
+    pub fn measure(world: &World) -> Diagnostics {
+        let physics = &world.physics;
+        let gravity = physics.gravity as f64;
+        let softening = physics.softening as f64;
+
+        let bodies = || world.static_bodies.iter().map(|body| &body.ball);
+        let (origin, origin_velocity) = match bodies().next() {
+            Some(primary) => (primary.position.as_dvec2(), primary.velocity.as_dvec2()),
+            None => (DVec2::ZERO, DVec2::ZERO),
+        };
+
+        let mut diagnostics = Diagnostics {
+            step: world.step_count,
+            ..Default::default()
+        };
+
+        for body in world.balls.values().chain(bodies()) {
+            let mass = body.mass as f64;
+            let velocity = body.velocity.as_dvec2();
+            diagnostics.kinetic_energy += 0.5 * mass * velocity.length_squared();
+            diagnostics.momentum += velocity * mass;
+            diagnostics.angular_momentum +=
+                mass * (body.position.as_dvec2() - origin).perp_dot(velocity - origin_velocity);
+        }
+
+        for (index, ball) in world.balls.values().enumerate() {
+            for body in bodies() {
+                diagnostics.potential_energy += pair_potential(ball, body, gravity, softening);
+            }
+
+            if physics.n_body {
+                for other in world.balls.values().skip(index + 1) {
+                    diagnostics.potential_energy += pair_potential(ball, other, gravity, softening);
+                }
+            }
+        }
+
+        if physics.dynamic_bodies {
+            for (index, body) in bodies().enumerate() {
+                for other in bodies().skip(index + 1) {
+                    diagnostics.potential_energy += pair_potential(body, other, gravity, softening);
+                }
+            }
+        }
+
+        diagnostics
+    }
+
+    pub fn total_energy(&self) -> f64 {
+        self.kinetic_energy + self.potential_energy
+    }
+}
+
+/// Time series of diagnostics, one sample per recorded step.
+#[derive(Clone, Debug, Default)]
+pub struct DiagnosticsLog {
+    pub samples: Vec<Diagnostics>,
+}
+
+impl DiagnosticsLog {
+    pub fn new() -> DiagnosticsLog {
+        DiagnosticsLog::default()
+    }
+
+    pub fn record(&mut self, diagnostics: Diagnostics) {
+        self.samples.push(diagnostics);
+    }
+
+    /// Relative drift of the total energy between the first and last samples.
+    pub fn energy_drift(&self) -> Option<f64> {
+        let first = self.samples.first()?.total_energy();
+        let last = self.samples.last()?.total_energy();
+        if first == 0. {
+            return None;
+        }
+
+        Some((last - first) / first.abs())
+    }
+
+    pub fn write_csv<W: Write>(&self, mut out: W) -> io::Result<()> {
+        writeln!(
+            out,
+            "step,kinetic_energy,potential_energy,total_energy,momentum_x,momentum_y,angular_momentum"
+        )?;
+        for sample in &self.samples {
+            writeln!(
+                out,
+                "{},{},{},{},{},{},{}",
+                sample.step,
+                sample.kinetic_energy,
+                sample.potential_energy,
+                sample.total_energy(),
+                sample.momentum.x,
+                sample.momentum.y,
+                sample.angular_momentum
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut file = io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_csv(&mut file)?;
+        file.flush()
+    }
+}
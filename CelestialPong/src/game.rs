@@ -0,0 +1,295 @@
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::paddle::wrap_angle;
+use crate::world::{World, WorldEvent};
+
+/// How points are scored and when a match ends. Players are the paddles of the world,
+/// a world without paddles is a single player game.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct GameRules {
+    // Point against the player whose sector a ball leaves the field through, only with
+    // the despawn and unbounded boundaries where balls can leave it
+    #[serde(default = "default_true")]
+    pub escape_scores: bool,
+    // Point against the player whose sector a ball falls into a static body from
+    #[serde(default = "default_true")]
+    pub absorb_scores: bool,
+    // Points a player can concede before losing the match, no limit when missing
+    #[serde(default)]
+    pub lives: Option<u32>,
+    // Points played before the match ends, no limit when missing
+    #[serde(default)]
+    pub match_points: Option<u32>,
+    // Seconds the world waits before every rally
+    #[serde(default = "default_serve_delay")]
+    pub serve_delay: f32,
+    // Seconds the world stays frozen after a point
+    #[serde(default = "default_point_delay")]
+    pub point_delay: f32,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_serve_delay() -> f32 {
+    1.
+}
+
+fn default_point_delay() -> f32 {
+    1.5
+}
+
+impl Default for GameRules {
+    fn default() -> GameRules {
+        GameRules {
+            escape_scores: true,
+            absorb_scores: true,
+            lives: None,
+            match_points: None,
+            serve_delay: default_serve_delay(),
+            point_delay: default_point_delay(),
+        }
+    }
+}
+
+/// Where a match is. During a match the world only moves in rallies.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GamePhase {
+    /// Waiting for the players to start the match, the world runs freely meanwhile
+    Title,
+    /// Counting down to the next rally
+    Serve {
+        remaining: f32,
+    },
+    Rally,
+    /// A point was just scored against `against`
+    PointScored {
+        against: usize,
+        remaining: f32,
+    },
+    /// Results are shown until the match is restarted. No winner when the best players
+    /// are tied, or in a single player game.
+    MatchOver {
+        winner: Option<usize>,
+    },
+}
+
+/// A match played on a world: its phase and what every player did so far.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Game {
+    pub rules: GameRules,
+    pub phase: GamePhase,
+    // Points conceded by each player
+    pub conceded: Vec<u32>,
+    // Balls each player hit back with their paddle
+    pub hits: Vec<u32>,
+    // Seconds spent in rallies
+    pub duration: f32,
+}
+
+impl Game {
+    pub fn new(rules: GameRules, players: usize) -> Game {
+        Game {
+            rules,
+            phase: GamePhase::Title,
+            conceded: vec![0; players.max(1)],
+            hits: vec![0; players.max(1)],
+            duration: 0.,
+        }
+    }
+
+    /// A match with the rules of the world scenario, one player per paddle.
+    pub fn for_world(world: &World) -> Game {
+        Game::new(world.scenario.game, world.paddles.len())
+    }
+
+    pub fn players(&self) -> usize {
+        self.conceded.len()
+    }
+
+    /// Points scored by `player`: every point conceded by the others.
+    pub fn score(&self, player: usize) -> u32 {
+        let total: u32 = self.conceded.iter().sum();
+        total - self.conceded[player]
+    }
+
+    pub fn points_played(&self) -> u32 {
+        self.conceded.iter().sum()
+    }
+
+    /// Points `player` can still concede, None without a limit.
+    pub fn lives_left(&self, player: usize) -> Option<u32> {
+        self.rules
+            .lives
+            .map(|lives| lives.saturating_sub(self.conceded[player]))
+    }
+
+    /// Leaves the title screen.
+    pub fn start(&mut self) {
+        if self.phase == GamePhase::Title {
+            self.phase = GamePhase::Serve {
+                remaining: self.rules.serve_delay,
+            };
+        }
+    }
+
+    /// Clears the scores and goes back to the title screen with a fresh world.
+    pub fn restart(&mut self, world: &mut World) {
+        *self = Game::new(self.rules, self.players());
+        world.reset();
+    }
+
+    /// Player guarding the part of the field `position` is in: the one whose paddle sector
+    /// is closest in angle around its body.
+    pub fn player_at(world: &World, position: Vec2) -> usize {
+        let angle_to = |index: usize| {
+            let paddle = &world.paddles[index];
+            let center = world.static_bodies[paddle.around].ball.position;
+            let angle = (position - center).y.atan2((position - center).x);
+            wrap_angle(angle - paddle.sector_center()).abs()
+        };
+        (0..world.paddles.len())
+            .min_by(|&a, &b| angle_to(a).total_cmp(&angle_to(b)))
+            .unwrap_or(0)
+    }
+
+    /// Advances the match by `dt`, stepping the world during rallies.
+    pub fn step(&mut self, world: &mut World, dt: f32) {
+        match self.phase {
+            GamePhase::Title | GamePhase::MatchOver { .. } => {}
+            GamePhase::Serve { remaining } => {
+                if remaining > dt {
+                    self.phase = GamePhase::Serve {
+                        remaining: remaining - dt,
+                    };
+                    return;
+                }
+                // Every ball was lost, the next rally gets new ones, different from the
+                // last ones as the rng is not reseeded
+                if world.balls_in_play() == 0 {
+                    world.respawn_balls();
+                }
+                self.phase = GamePhase::Rally;
+            }
+            GamePhase::Rally => self.rally(world, dt),
+            GamePhase::PointScored { against, remaining } => {
+                self.phase = if remaining > dt {
+                    GamePhase::PointScored {
+                        against,
+                        remaining: remaining - dt,
+                    }
+                } else {
+                    match self.is_over() {
+                        true => GamePhase::MatchOver {
+                            winner: self.leader(),
+                        },
+                        false => GamePhase::Serve {
+                            remaining: self.rules.serve_delay,
+                        },
+                    }
+                };
+            }
+        }
+    }
+
+    /// Advances the match by a displayed frame lasting `frame_time`. Countdowns follow the
+    /// frame time whatever the simulation speed, rallies step the world `steps` times by
+    /// `dt`, calling `before_step` first to steer the paddles. The sub-steps stop as soon
+    /// as a point is scored. Before the match starts the world is stepped the same way,
+    /// without scoring.
+    pub fn frame(
+        &mut self,
+        world: &mut World,
+        frame_time: f32,
+        steps: usize,
+        dt: f32,
+        mut before_step: impl FnMut(&mut World),
+    ) {
+        match self.phase {
+            GamePhase::Title => {
+                for _ in 0..steps {
+                    before_step(world);
+                    world.step(dt);
+                }
+                return;
+            }
+            GamePhase::Rally => {}
+            _ => {
+                self.step(world, frame_time);
+                return;
+            }
+        }
+        for _ in 0..steps {
+            before_step(world);
+            self.step(world, dt);
+            if self.phase != GamePhase::Rally {
+                break;
+            }
+        }
+    }
+
+    fn rally(&mut self, world: &mut World, dt: f32) {
+        // Events stay in the world until drained by the caller, only look at this step ones
+        let seen = world.events().len();
+        world.step(dt);
+        self.duration += dt;
+
+        let mut first_point = None;
+        for index in seen..world.events().len() {
+            let (ball, position) = match world.events()[index] {
+                WorldEvent::BouncedOffCapsule { capsule, .. } => {
+                    if let Some(player) = world.paddles.iter().position(|p| p.capsule == capsule) {
+                        self.hits[player] += 1;
+                    }
+                    continue;
+                }
+                WorldEvent::Absorbed { ball, position, .. } if self.rules.absorb_scores => {
+                    (ball, position)
+                }
+                WorldEvent::Escaped { ball, position } if self.rules.escape_scores => {
+                    (ball, position)
+                }
+                _ => continue,
+            };
+
+            // Escaped balls stay in unbounded worlds, they are out of play once they scored
+            world.despawn(ball);
+            let player = Game::player_at(world, position).min(self.players() - 1);
+            self.conceded[player] += 1;
+            first_point.get_or_insert(player);
+        }
+
+        if let Some(against) = first_point {
+            self.phase = GamePhase::PointScored {
+                against,
+                remaining: self.rules.point_delay,
+            };
+        }
+    }
+
+    fn is_over(&self) -> bool {
+        let out_of_lives = (0..self.players()).any(|player| self.lives_left(player) == Some(0));
+        let played_out = self
+            .rules
+            .match_points
+            .is_some_and(|points| self.points_played() >= points);
+        out_of_lives || played_out
+    }
+
+    // Player who conceded the fewest points, None on a tie or alone
+    fn leader(&self) -> Option<usize> {
+        if self.players() < 2 {
+            return None;
+        }
+        let fewest = *self.conceded.iter().min()?;
+        let mut best = (0..self.players()).filter(|&player| self.conceded[player] == fewest);
+        let leader = best.next();
+        match best.next() {
+            Some(_) => None,
+            None => leader,
+        }
+    }
+}
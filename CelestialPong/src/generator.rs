@@ -0,0 +1,184 @@
+use std::f32::consts::TAU;
+
+use ::rand::Rng;
+use macroquad::prelude::*;
+use rand_chacha::ChaCha20Rng;
+use serde::Deserialize;
+
+use crate::orbit::orbit_state;
+use crate::static_body::StaticBody;
+
+/// How the orbits of a population of balls are drawn. Every element is uniform between
+/// the bounds of its range, distances are from the body the balls orbit and angles are
+/// in degrees. Balls turn the way circular orbits do, towards increasing angles, unless
+/// they are retrograde.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum OrbitDistribution {
+    /// Ranges of orbital elements. Orbits with an eccentricity above 1 are hyperbolas,
+    /// their semi-major axis is then the distance from their center to the periapsis.
+    /// Parabolas have no semi-major axis, so the eccentricity range must not include 1.
+    /// `retrograde` is the fraction of balls turning the other way.
+    Ranges {
+        semi_major_axis: [f32; 2],
+        #[serde(default)]
+        eccentricity: [f32; 2],
+        #[serde(default = "full_turn")]
+        argument_of_periapsis: [f32; 2],
+        #[serde(default = "full_turn")]
+        true_anomaly: [f32; 2],
+        #[serde(default)]
+        retrograde: f32,
+    },
+    /// Slightly eccentric orbits between `inner` and `outer`, all over their ellipse.
+    AsteroidBelt {
+        inner: f32,
+        outer: f32,
+        #[serde(default = "default_belt_eccentricity")]
+        max_eccentricity: f32,
+    },
+    /// Circular orbits between `inner` and `outer`.
+    Ring { inner: f32, outer: f32 },
+    /// Hyperbolic paths coming in from `distance`, towards a periapsis in `periapsis`.
+    /// Eccentricities must be above 1 and distances no closer than the periapsis.
+    CometSwarm {
+        periapsis: [f32; 2],
+        #[serde(default = "default_comet_eccentricity")]
+        eccentricity: [f32; 2],
+        distance: [f32; 2],
+        #[serde(default = "default_half")]
+        retrograde: f32,
+    },
+    /// Balls around the L4 and L5 points, 60 degrees ahead and behind the static body
+    /// `planet` on its orbit, on circular orbits turning the way it does. Scattered by
+    /// `spread` degrees along the orbit and by `width` across it. Meant for dynamic
+    /// bodies, where the planet moves around the body the balls orbit. Without them the
+    /// planet does not move, so `retrograde` must tell which way the orbits turn.
+    Trojans {
+        planet: usize,
+        #[serde(default = "default_trojan_spread")]
+        spread: f32,
+        #[serde(default)]
+        width: f32,
+        #[serde(default)]
+        retrograde: Option<bool>,
+    },
+}
+
+fn full_turn() -> [f32; 2] {
+    [0., 360.]
+}
+
+fn default_belt_eccentricity() -> f32 {
+    0.1
+}
+
+fn default_comet_eccentricity() -> [f32; 2] {
+    [1.1, 1.5]
+}
+
+fn default_half() -> f32 {
+    0.5
+}
+
+fn default_trojan_spread() -> f32 {
+    10.
+}
+
+fn uniform(rng: &mut ChaCha20Rng, [min, max]: [f32; 2]) -> f32 {
+    min + (max - min) * rng.gen::<f32>()
+}
+
+impl OrbitDistribution {
+    /// Draws the position and velocity of a ball orbiting `bodies[around]`.
+    pub fn sample(
+        &self,
+        bodies: &[StaticBody],
+        around: usize,
+        gravity: f32,
+        rng: &mut ChaCha20Rng,
+    ) -> (Vec2, Vec2) {
+        let body = &bodies[around].ball;
+        let mu = gravity * body.mass;
+        let (position, velocity) = match *self {
+            OrbitDistribution::Ranges {
+                semi_major_axis,
+                eccentricity,
+                argument_of_periapsis,
+                true_anomaly,
+                retrograde,
+            } => orbit_state(
+                mu,
+                uniform(rng, semi_major_axis),
+                uniform(rng, eccentricity),
+                uniform(rng, argument_of_periapsis).to_radians(),
+                uniform(rng, true_anomaly).to_radians(),
+                rng.gen::<f32>() < retrograde,
+            ),
+            OrbitDistribution::AsteroidBelt {
+                inner,
+                outer,
+                max_eccentricity,
+            } => orbit_state(
+                mu,
+                uniform(rng, [inner, outer]),
+                uniform(rng, [0., max_eccentricity]),
+                uniform(rng, [0., TAU]),
+                uniform(rng, [0., TAU]),
+                false,
+            ),
+            OrbitDistribution::Ring { inner, outer } => orbit_state(
+                mu,
+                uniform(rng, [inner, outer]),
+                0.,
+                0.,
+                uniform(rng, [0., TAU]),
+                false,
+            ),
+            OrbitDistribution::CometSwarm {
+                periapsis,
+                eccentricity,
+                distance,
+                retrograde,
+            } => {
+                let periapsis = uniform(rng, periapsis);
+                let eccentricity = uniform(rng, eccentricity);
+                let distance = uniform(rng, distance);
+                // Before the periapsis, where the hyperbola is `distance` away
+                let semi_latus_rectum = periapsis * (1. + eccentricity);
+                let anomaly = -((semi_latus_rectum / distance - 1.) / eccentricity).acos();
+                orbit_state(
+                    mu,
+                    periapsis / (eccentricity - 1.),
+                    eccentricity,
+                    uniform(rng, [0., TAU]),
+                    anomaly,
+                    rng.gen::<f32>() < retrograde,
+                )
+            }
+            OrbitDistribution::Trojans {
+                planet,
+                spread,
+                width,
+                retrograde,
+            } => {
+                let planet = &bodies[planet].ball;
+                let offset = planet.position - body.position;
+                let retrograde = retrograde
+                    .unwrap_or_else(|| offset.perp_dot(planet.velocity - body.velocity) < 0.);
+                let sense = if retrograde { -1. } else { 1. };
+                let lagrange_point = if rng.gen::<bool>() { 60. } else { -60. };
+
+                let angle = offset.y.atan2(offset.x)
+                    + sense
+                        * uniform(rng, [lagrange_point - spread, lagrange_point + spread])
+                            .to_radians();
+                let distance = offset.length() + uniform(rng, [-width / 2., width / 2.]);
+                let speed = (gravity * (body.mass + planet.mass) / distance).sqrt();
+                let direction = Vec2::from_angle(angle);
+                (direction * distance, direction.perp() * sense * speed)
+            }
+        };
+        (body.position + position, body.velocity + velocity)
+    }
+}
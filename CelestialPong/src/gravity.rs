@@ -0,0 +1,156 @@
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::ball::Ball;
+use crate::barnes_hut::BarnesHutTree;
+
+/// A point mass contributing to the gravity field.
+#[derive(Clone, Copy, Debug)]
+pub struct Attractor {
+    pub position: Vec2,
+    pub mass: f32,
+}
+
+impl Attractor {
+    pub fn new(ball: &Ball) -> Attractor {
+        Attractor {
+            position: ball.position,
+            mass: ball.mass,
+        }
+    }
+}
+
+/// Acceleration felt at `position` because of `attractor`.
+/// `softening` is a length keeping the force finite when both get very close.
+pub fn get_gravity_acceleration(
+    position: Vec2,
+    attractor: &Attractor,
+    gravity: f32,
+    softening: f32,
+) -> Vec2 {
+    let delta = attractor.position - position;
+    let distance_squared = delta.length_squared() + softening * softening;
+    if distance_squared <= f32::EPSILON {
+        return Vec2::ZERO;
+    }
+
+    delta * (gravity * attractor.mass / (distance_squared * distance_squared.sqrt()))
+}
+
+pub fn get_gravity_force(ball: &Ball, body: &Ball, gravity: f32, softening: f32) -> Vec2 {
+    get_gravity_acceleration(ball.position, &Attractor::new(body), gravity, softening) * ball.mass
+}
+
+/// Sums the acceleration of every attractor except the one at index `skip`.
+pub fn get_field_acceleration(
+    position: Vec2,
+    attractors: &[Attractor],
+    skip: Option<usize>,
+    gravity: f32,
+    softening: f32,
+) -> Vec2 {
+    let mut acceleration = Vec2::ZERO;
+    for (index, attractor) in attractors.iter().enumerate() {
+        if skip != Some(index) {
+            acceleration += get_gravity_acceleration(position, attractor, gravity, softening);
+        }
+    }
+
+    acceleration
+}
+
+pub fn get_orbital_velocity(b1: &Ball, b2: &Ball, gravity: f32) -> Vec2 {
+    let delta = b2.position - b1.position;
+    let orbit_radius = delta.length();
+    let speed = (gravity * b2.mass / orbit_radius).sqrt();
+    Vec2::from((delta.y, -delta.x)).normalize() * speed + b2.velocity
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GravitySolver {
+    /// Exact sum over every attractor, O(n²)
+    #[default]
+    Direct,
+    /// Barnes-Hut approximation, O(n log n)
+    BarnesHut,
+}
+
+/// Gravity of a set of attractors, evaluated with the chosen solver.
+pub struct GravityField<'a> {
+    pub attractors: &'a [Attractor],
+    // Only used by the Barnes-Hut solver, must be built from `attractors`
+    pub tree: &'a BarnesHutTree,
+    pub solver: GravitySolver,
+    pub theta: f32,
+    pub gravity: f32,
+    pub softening: f32,
+}
+
+impl GravityField<'_> {
+    pub fn acceleration(&self, position: Vec2, skip: Option<usize>) -> Vec2 {
+        match self.solver {
+            GravitySolver::Direct => get_field_acceleration(
+                position,
+                self.attractors,
+                skip,
+                self.gravity,
+                self.softening,
+            ),
+            GravitySolver::BarnesHut => self.tree.acceleration(
+                position,
+                self.attractors,
+                skip,
+                self.theta,
+                self.gravity,
+                self.softening,
+            ),
+        }
+    }
+
+    /// Relative error of the Barnes-Hut approximation against the exact sum,
+    /// measured at every sample position, skipping the attractor given with it.
+    pub fn force_error(
+        &self,
+        samples: impl IntoIterator<Item = (Vec2, Option<usize>)>,
+    ) -> ForceError {
+        let mut error = ForceError::default();
+        let mut count = 0;
+        for (position, skip) in samples {
+            let exact = get_field_acceleration(
+                position,
+                self.attractors,
+                skip,
+                self.gravity,
+                self.softening,
+            );
+            if exact.length_squared() <= f32::EPSILON {
+                continue;
+            }
+
+            let approximation = self.tree.acceleration(
+                position,
+                self.attractors,
+                skip,
+                self.theta,
+                self.gravity,
+                self.softening,
+            );
+            let relative = (approximation - exact).length() / exact.length();
+            error.mean += relative;
+            error.max = error.max.max(relative);
+            count += 1;
+        }
+
+        if count > 0 {
+            error.mean /= count as f32;
+        }
+        error
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ForceError {
+    pub mean: f32,
+    pub max: f32,
+}
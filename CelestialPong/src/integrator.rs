@@ -0,0 +1,193 @@
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::ball::Ball;
+
+/// Advances a ball by one time step in an acceleration field.
+///
+/// Every integrator leaves `position`, `prev_position` and `velocity` consistent with each
+/// other (`prev_position == position - velocity * dt`), so the integrator can be switched
+/// between two steps.
+pub trait Integrator {
+    fn name(&self) -> &'static str;
+
+    /// `acceleration` gives the acceleration felt by the ball at a given position.
+    fn integrate(&self, ball: &mut Ball, dt: f32, acceleration: &dyn Fn(Vec2) -> Vec2);
+}
+
+fn finish(ball: &mut Ball, position: Vec2, velocity: Vec2, dt: f32) {
+    ball.position = position;
+    ball.set_velocity(velocity, dt);
+}
+
+pub struct ExplicitEuler;
+
+impl Integrator for ExplicitEuler {
+    fn name(&self) -> &'static str {
+        "Explicit Euler"
+    }
+
+    fn integrate(&self, ball: &mut Ball, dt: f32, acceleration: &dyn Fn(Vec2) -> Vec2) {
+        let position = ball.position + ball.velocity * dt;
+        let velocity = ball.velocity + acceleration(ball.position) * dt;
+        finish(ball, position, velocity, dt);
+    }
+}
+
+pub struct SemiImplicitEuler;
+
+impl Integrator for SemiImplicitEuler {
+    fn name(&self) -> &'static str {
+        "Semi-implicit Euler"
+    }
+
+    fn integrate(&self, ball: &mut Ball, dt: f32, acceleration: &dyn Fn(Vec2) -> Vec2) {
+        ball.update(dt, acceleration(ball.position));
+    }
+}
+
+pub struct PositionVerlet;
+
+impl Integrator for PositionVerlet {
+    fn name(&self) -> &'static str {
+        "Position Verlet"
+    }
+
+    fn integrate(&self, ball: &mut Ball, dt: f32, acceleration: &dyn Fn(Vec2) -> Vec2) {
+        ball.update_verlet(dt, acceleration(ball.position));
+    }
+}
+
+pub struct VelocityVerlet;
+
+impl Integrator for VelocityVerlet {
+    fn name(&self) -> &'static str {
+        "Velocity Verlet"
+    }
+
+    fn integrate(&self, ball: &mut Ball, dt: f32, acceleration: &dyn Fn(Vec2) -> Vec2) {
+        let half_velocity = ball.velocity + acceleration(ball.position) * dt * 0.5;
+        let position = ball.position + half_velocity * dt;
+        let velocity = half_velocity + acceleration(position) * dt * 0.5;
+        finish(ball, position, velocity, dt);
+    }
+}
+
+/// Drift-kick-drift leapfrog.
+pub struct Leapfrog;
+
+impl Integrator for Leapfrog {
+    fn name(&self) -> &'static str {
+        "Leapfrog"
+    }
+
+    fn integrate(&self, ball: &mut Ball, dt: f32, acceleration: &dyn Fn(Vec2) -> Vec2) {
+        let half_position = ball.position + ball.velocity * dt * 0.5;
+        let velocity = ball.velocity + acceleration(half_position) * dt;
+        let position = half_position + velocity * dt * 0.5;
+        finish(ball, position, velocity, dt);
+    }
+}
+
+/// Classic 4th order Runge-Kutta.
+pub struct RungeKutta4;
+
+impl Integrator for RungeKutta4 {
+    fn name(&self) -> &'static str {
+        "Runge-Kutta 4"
+    }
+
+    fn integrate(&self, ball: &mut Ball, dt: f32, acceleration: &dyn Fn(Vec2) -> Vec2) {
+        let x = ball.position;
+        let v = ball.velocity;
+
+        let k1x = v;
+        let k1v = acceleration(x);
+        let k2x = v + k1v * dt * 0.5;
+        let k2v = acceleration(x + k1x * dt * 0.5);
+        let k3x = v + k2v * dt * 0.5;
+        let k3v = acceleration(x + k2x * dt * 0.5);
+        let k4x = v + k3v * dt;
+        let k4v = acceleration(x + k3x * dt);
+
+        let position = x + (k1x + 2. * k2x + 2. * k3x + k4x) * dt / 6.;
+        let velocity = v + (k1v + 2. * k2v + 2. * k3v + k4v) * dt / 6.;
+        finish(ball, position, velocity, dt);
+    }
+}
+
+/// 4th order symplectic integrator, Yoshida's composition of three leapfrog steps
+/// (identical to Forest-Ruth).
+pub struct Yoshida4;
+
+impl Integrator for Yoshida4 {
+    fn name(&self) -> &'static str {
+        "Yoshida 4"
+    }
+
+    fn integrate(&self, ball: &mut Ball, dt: f32, acceleration: &dyn Fn(Vec2) -> Vec2) {
+        let cbrt2 = 2f32.cbrt();
+        let w0 = -cbrt2 / (2. - cbrt2);
+        let w1 = 1. / (2. - cbrt2);
+        let c = [w1 / 2., (w0 + w1) / 2., (w0 + w1) / 2., w1 / 2.];
+        let d = [w1, w0, w1];
+
+        let mut x = ball.position;
+        let mut v = ball.velocity;
+        for i in 0..3 {
+            x += v * c[i] * dt;
+            v += acceleration(x) * d[i] * dt;
+        }
+        x += v * c[3] * dt;
+
+        finish(ball, x, v, dt);
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegratorKind {
+    ExplicitEuler,
+    SemiImplicitEuler,
+    #[default]
+    PositionVerlet,
+    VelocityVerlet,
+    Leapfrog,
+    Rk4,
+    Yoshida4,
+}
+
+impl IntegratorKind {
+    pub const ALL: [IntegratorKind; 7] = [
+        IntegratorKind::ExplicitEuler,
+        IntegratorKind::SemiImplicitEuler,
+        IntegratorKind::PositionVerlet,
+        IntegratorKind::VelocityVerlet,
+        IntegratorKind::Leapfrog,
+        IntegratorKind::Rk4,
+        IntegratorKind::Yoshida4,
+    ];
+
+    pub fn integrator(self) -> &'static dyn Integrator {
+        match self {
+            IntegratorKind::ExplicitEuler => &ExplicitEuler,
+            IntegratorKind::SemiImplicitEuler => &SemiImplicitEuler,
+            IntegratorKind::PositionVerlet => &PositionVerlet,
+            IntegratorKind::VelocityVerlet => &VelocityVerlet,
+            IntegratorKind::Leapfrog => &Leapfrog,
+            IntegratorKind::Rk4 => &RungeKutta4,
+            IntegratorKind::Yoshida4 => &Yoshida4,
+        }
+    }
+
+    pub fn index(self) -> usize {
+        IntegratorKind::ALL
+            .iter()
+            .position(|kind| *kind == self)
+            .unwrap()
+    }
+
+    pub fn next(self) -> IntegratorKind {
+        IntegratorKind::ALL[(self.index() + 1) % IntegratorKind::ALL.len()]
+    }
+}
@@ -0,0 +1,23 @@
+pub mod ai;
+pub mod arena;
+pub mod ball;
+pub mod barnes_hut;
+pub mod boundary;
+pub mod broad_phase;
+pub mod capsule;
+pub mod diagnostics;
+pub mod game;
+pub mod generator;
+pub mod gravity;
+pub mod integrator;
+pub mod material;
+pub mod orbit;
+pub mod paddle;
+pub mod prediction;
+pub mod quad_tree;
+pub mod scenario;
+pub mod snapshot;
+pub mod sort_and_sweep;
+pub mod spatial_hash;
+pub mod static_body;
+pub mod world;
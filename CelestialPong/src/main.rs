@@ -1,70 +1,160 @@
 // based on https://github.com/Markek1/Collision-Simulator
 // other usefull link https://arrowinmyknee.com/2021/03/15/some-math-about-capsule-collision/
 
-mod ball;
-mod capsule;
-mod quad_tree;
+use std::path::{Path, PathBuf};
 
-use macroquad::{
-    color::{self, colors},
-    prelude::*,
-    window,
-};
-use rand::SeedableRng;
+use macroquad::{color::colors, prelude::*};
 
-use rand::Rng;
-use rand_chacha::ChaCha20Rng;
-extern crate rand;
-
-use crate::ball::*;
-use crate::quad_tree::*;
-
-const NB_BALLS: usize = 2;
-const RADII: f32 = 10.;
-const BALL_MASS: f32 = 2.;
-
-const GRAVITY: f32 = 15000.;
-const BODY_BOUNCYNESS: f32 = 0.9;
-
-const MIN_START_ORBIT: f32 = 100.;
-const MAX_START_ORBIT: f32 = 400.;
+use celestial_pong::ai::{AiPaddle, Difficulty};
+use celestial_pong::diagnostics::DiagnosticsLog;
+use celestial_pong::game::{Game, GamePhase};
+use celestial_pong::gravity::GravitySolver;
+use celestial_pong::orbit::{OrbitKind, OrbitalElements};
+use celestial_pong::prediction::{predict_path, PathEnd, Predictor};
+use celestial_pong::scenario::Scenario;
+use celestial_pong::snapshot::Snapshot;
+use celestial_pong::world::*;
 
 const FPS_FRAMES: usize = 100;
-const TRACE_SIZE: usize = 1000;
-
-const SIMULATION_DT: f32 = 1. / 120.;
+const PICK_RADIUS: f32 = 10.;
+// Steps of the trajectory shown for the hovered or selected ball
+const PREDICTION_STEPS: usize = 600;
+const ORBIT_SEGMENTS: usize = 100;
+const SNAPSHOT_PATH: &str = "snapshot.cpsn";
+const DIAGNOSTICS_PATH: &str = "diagnostics.csv";
+// Keys steering the paddles: the first one turns it counterclockwise, the second clockwise
+const PADDLE_KEYS: [(KeyCode, KeyCode); 2] =
+    [(KeyCode::Left, KeyCode::Right), (KeyCode::Z, KeyCode::X)];
+
+// Tooltip describing the orbit of the ball under the mouse
+fn draw_orbit_tooltip(elements: &OrbitalElements, position: Vec2) {
+    let kind = match elements.kind {
+        OrbitKind::Bound => "bound",
+        OrbitKind::Escape => "escape",
+        OrbitKind::Impact => "impact",
+    };
+    let optional =
+        |value: Option<f32>| value.map_or("-".to_owned(), |value| format!("{:.1}", value));
+    let lines = [
+        format!("Orbit : {}", kind),
+        format!("Semi-major axis : {:.1}", elements.semi_major_axis),
+        format!("Eccentricity : {:.3}", elements.eccentricity),
+        format!(
+            "Argument of periapsis : {:.1} deg",
+            elements.argument_of_periapsis.to_degrees()
+        ),
+        format!("Periapsis : {:.1}", elements.periapsis),
+        format!("Apoapsis : {}", optional(elements.apoapsis)),
+        format!("Period : {} s", optional(elements.period)),
+        format!("Specific energy : {:.1}", elements.specific_energy),
+    ];
+    let origin = position + vec2(16., 16.);
+    draw_rectangle(
+        origin.x - 4.,
+        origin.y - 14.,
+        220.,
+        lines.len() as f32 * 16. + 6.,
+        Color::new(0., 0., 0., 0.7),
+    );
+    for (index, line) in lines.iter().enumerate() {
+        draw_text_ex(
+            line,
+            origin.x,
+            origin.y + index as f32 * 16.,
+            TextParams {
+                font_size: 15,
+                ..Default::default()
+            },
+        );
+    }
+}
 
 fn damping(pos: Vec2, target: Vec2, dt: f32, elasticity: f32) -> Vec2 {
-    return (target - pos) / elasticity * dt;
+    (target - pos) / elasticity * dt
 }
 
-fn get_gravity_force(ball: &Ball, body: &Ball) -> Vec2 {
-    let delta = body.position - ball.position;
-    return delta.normalize() * (body.mass * ball.mass) / delta.length().powf(2.) * GRAVITY;
-}
+const WINDOW_SIZE: [f32; 2] = [900., 900.];
 
-fn get_orbital_velocity(b1: &Ball, b2: &Ball) -> Vec2 {
-    let delta = b2.position - b1.position;
-    let orbit_radius = delta.length();
-    let speed = (GRAVITY * (b2.mass * b1.mass) / orbit_radius).sqrt();
-    return Vec2::from((delta.y, -delta.x)).normalize() * speed;
+fn draw_centered(text: &str, y: f32, font_size: u16) {
+    let size = measure_text(text, None, font_size, 1.);
+    draw_text_ex(
+        text,
+        (WINDOW_SIZE[0] - size.width) / 2.,
+        y,
+        TextParams {
+            font_size,
+            ..Default::default()
+        },
+    );
 }
 
-fn random_orbital_pos(
-    center: Vec2,
-    min_radius: f32,
-    max_radius: f32,
-    rng: &mut ChaCha20Rng,
-) -> Vec2 {
-    let angle = rng.gen::<f32>() * std::f32::consts::PI * 2.;
-    let result = Vec2::from((angle.cos(), angle.sin()));
-    let rad = rng.gen::<f32>() * (max_radius - min_radius) + min_radius;
-    let result = center + result * rad;
-    return result;
+// Scores at the bottom of the screen, and the title, point and results screens
+fn draw_game(game: &Game) {
+    let player_line = |player: usize| {
+        let mut line = format!("Player {} : {} points", player + 1, game.score(player));
+        if let Some(lives) = game.lives_left(player) {
+            line += &format!(", {} lives", lives);
+        }
+        line
+    };
+    let scores: Vec<String> = (0..game.players()).map(player_line).collect();
+    draw_centered(&scores.join("    "), WINDOW_SIZE[1] - 32., 20);
+
+    let center = WINDOW_SIZE[1] / 2.;
+    match game.phase {
+        GamePhase::Title => {
+            draw_centered("Celestial pong", center - 40., 60);
+            draw_centered(
+                "Press Enter to start a match, Space to run",
+                center + 10.,
+                24,
+            );
+        }
+        GamePhase::Serve { remaining } => {
+            draw_centered(&format!("{}", remaining.ceil()), center - 40., 40);
+        }
+        GamePhase::Rally => {}
+        GamePhase::PointScored { against, .. } => {
+            let text = match game.players() {
+                1 => "Ball lost".to_owned(),
+                _ => format!("Point against player {}", against + 1),
+            };
+            draw_centered(&text, center - 40., 32);
+        }
+        GamePhase::MatchOver { winner } => {
+            let title = match winner {
+                Some(player) => format!("Player {} wins", player + 1),
+                None if game.players() == 1 => "Game over".to_owned(),
+                None => "Draw".to_owned(),
+            };
+            draw_centered(&title, center - 80., 48);
+            for player in 0..game.players() {
+                draw_centered(
+                    &format!(
+                        "Player {} : {} points, {} conceded, {} hits",
+                        player + 1,
+                        game.score(player),
+                        game.conceded[player],
+                        game.hits[player]
+                    ),
+                    center - 30. + player as f32 * 26.,
+                    22,
+                );
+            }
+            draw_centered(
+                &format!("Match played in {:.0} seconds", game.duration),
+                center - 30. + game.players() as f32 * 26. + 10.,
+                22,
+            );
+            draw_centered(
+                "Press Enter to play again",
+                center + 80. + game.players() as f32 * 26.,
+                24,
+            );
+        }
+    }
 }
 
-const WINDOW_SIZE: [f32; 2] = [900., 900.];
-
 fn window_config() -> Conf {
     Conf {
         window_title: "Celestial pong".to_owned(),
@@ -74,87 +164,40 @@ fn window_config() -> Conf {
     }
 }
 
-fn reset_balls(
-    balls: &mut Vec<Ball>,
-    tree_area: quad_tree::Rect,
-    static_bodies: &Vec<Ball>,
-    mut rng: &mut ChaCha20Rng,
-) {
-    balls.clear();
-
-    for _ in 0..NB_BALLS {
-        let position = random_orbital_pos(
-            static_bodies[0].position,
-            MIN_START_ORBIT,
-            MAX_START_ORBIT,
-            &mut rng,
-        );
-
-        let mut ball = Ball::new(
-            position,
-            Vec2::ZERO,
-            RADII,
-            BALL_MASS,
-            Color {
-                r: rng.gen::<f32>() + 0.25,
-                g: rng.gen::<f32>() + 0.25,
-                b: rng.gen::<f32>() + 0.25,
-                a: 1.,
-            },
-            tree_area,
-        );
-
-        // let ball_speed = Vec2::from((rng.gen::<f32>() * 20. - 10., rng.gen::<f32>() * 20. - 10.));
-        let ball_speed = get_orbital_velocity(&ball, &static_bodies[0]);
-
-        ball.set_velocity(ball_speed, SIMULATION_DT);
-        // println!(
-        //     "{:?} | (x:{},y:{})",
-        //     ball.position - ball.prev_position,
-        //     ball.velocity.x,
-        //     ball.velocity.y
-        // );
-        balls.push(ball);
-    }
-}
-
 #[macroquad::main(window_config)]
 async fn main() {
-    let play_area_size = Vec2::new(window::screen_width(), window::screen_height());
+    // Usage: macroquad [scenario.toml]
+    let scenario_path = std::env::args().nth(1).map(PathBuf::from);
+    let scenario = match &scenario_path {
+        Some(path) => match Scenario::load(path) {
+            Ok(scenario) => scenario,
+            Err(err) => {
+                eprintln!("Invalid scenario: {}", err);
+                return;
+            }
+        },
+        None => Scenario::default(),
+    };
 
-    let mut rng = rand_chacha::ChaChaRng::seed_from_u64(1);
     let mut paused = true;
     let mut drawing_enabled = true;
-
-    let mut balls = Vec::with_capacity(NB_BALLS);
-    let mut static_bodies = Vec::new();
+    let mut show_diagnostics = false;
 
     let mut fps: [f32; FPS_FRAMES] = [0.; FPS_FRAMES];
     let mut fps_index: usize = 0;
 
-    let mut selected_ball: Option<usize> = None;
-
-    let tree_area = quad_tree::Rect::new(0., 0., play_area_size.x * 4., play_area_size.x * 4.);
-
-    let mut quad_tree;
-
-    let mut frame_per_frame: usize = 1;
-
-    let mut collided_balls = Vec::with_capacity(NB_BALLS);
-
-    static_bodies.push(Ball::new(
-        Vec2::new(0., 0.),
-        Vec2::ZERO,
-        30.,
-        1000.,
-        color::WHITE,
-        tree_area,
-    ));
-
-    let mut traces = [Vec2::ZERO; TRACE_SIZE];
-    let mut trace_index = 0;
-
-    reset_balls(&mut balls, tree_area, &static_bodies, &mut rng);
+    let mut world = World::new(scenario);
+    let mut game = Game::for_world(&world);
+    let mut opponents = AiPaddle::for_world(&world);
+    let mut status_message: Option<String> = None;
+    // Absorbed, bounced, landed, passed through and escaped balls since the last reset
+    let mut event_counts = [0usize; 5];
+    let mut near_entries = Vec::new();
+    // Predicted trajectories include the collisions with the other balls and the capsules
+    let mut predict_collisions = false;
+    let mut predictor = Predictor::new();
+    let mut predicted_path = Vec::new();
+    let mut conic = Vec::new();
 
     loop {
         if is_key_pressed(KeyCode::Escape) {
@@ -169,160 +212,199 @@ async fn main() {
         }
 
         if is_key_down(KeyCode::S) {
-            for ball in &mut balls {
-                ball.set_velocity(ball.velocity * 0.5, SIMULATION_DT);
+            world.scale_velocities(0.5);
+        }
+
+        if is_key_pressed(KeyCode::Enter) {
+            match game.phase {
+                GamePhase::Title => game.start(),
+                GamePhase::MatchOver { .. } => {
+                    game.restart(&mut world);
+                    game.start();
+                }
+                _ => {}
             }
         }
 
-        if is_key_down(KeyCode::R) {
-            selected_ball = None;
+        if is_key_pressed(KeyCode::R) {
             paused = true;
-            rng = rand_chacha::ChaChaRng::seed_from_u64(1);
-            reset_balls(&mut balls, tree_area, &static_bodies, &mut rng);
+            status_message = None;
+            event_counts = [0; 5];
+            match &scenario_path {
+                Some(path) => match Scenario::load(path) {
+                    Ok(scenario) => world.load_scenario(scenario),
+                    Err(err) => {
+                        eprintln!("Invalid scenario: {}", err);
+                        status_message = Some(format!("Scenario error : {}", err));
+                        world.reset();
+                    }
+                },
+                None => world.reset(),
+            }
+            game = Game::for_world(&world);
+            opponents = AiPaddle::for_world(&world);
+        }
+
+        // The computer takes over the last paddle, then plays harder, then lets it go
+        if is_key_pressed(KeyCode::C) && !world.paddles.is_empty() {
+            let paddle = world.paddles.len() - 1;
+            let current = opponents.iter().position(|ai| ai.paddle == paddle);
+            let next = match current.map(|index| opponents.remove(index).difficulty) {
+                None => Some(Difficulty::Easy),
+                Some(Difficulty::Easy) => Some(Difficulty::Medium),
+                Some(Difficulty::Medium) => Some(Difficulty::Hard),
+                Some(Difficulty::Hard) => None,
+            };
+            world.paddles[paddle].input = 0.;
+            status_message = Some(match next {
+                Some(difficulty) => {
+                    opponents.push(AiPaddle::new(paddle, difficulty));
+                    format!(
+                        "Player {} is played by the computer ({})",
+                        paddle + 1,
+                        difficulty.name()
+                    )
+                }
+                None => format!("Player {} is played from the keyboard", paddle + 1),
+            });
+        }
+
+        if is_key_pressed(KeyCode::F5) {
+            status_message = Some(match world.snapshot().save(Path::new(SNAPSHOT_PATH)) {
+                Ok(()) => format!("Snapshot saved to {}", SNAPSHOT_PATH),
+                Err(err) => format!("Cannot save snapshot : {}", err),
+            });
+        }
+
+        if is_key_pressed(KeyCode::F9) {
+            status_message = Some(match Snapshot::load(Path::new(SNAPSHOT_PATH)) {
+                Ok(snapshot) => {
+                    world.restore(&snapshot);
+                    format!("Snapshot loaded from {}", SNAPSHOT_PATH)
+                }
+                Err(err) => format!("Cannot load snapshot : {}", err),
+            });
         }
 
         if is_key_down(KeyCode::O) {
-            for ball in &mut balls {
-                ball.set_velocity(get_orbital_velocity(ball, &static_bodies[0]), SIMULATION_DT);
-            }
+            world.circularize_orbits();
         }
 
-        if is_key_pressed(KeyCode::Up) {
-            frame_per_frame = frame_per_frame + 1;
+        if is_key_pressed(KeyCode::I) {
+            world.physics.integrator = world.physics.integrator.next();
         }
 
-        if is_key_pressed(KeyCode::Down) {
-            frame_per_frame = (frame_per_frame - 1).max(1);
+        if is_key_pressed(KeyCode::D) {
+            show_diagnostics = !show_diagnostics;
         }
 
-        let dt = get_frame_time();
-        fps[fps_index] = dt;
-        fps_index = (fps_index + 1) % FPS_FRAMES;
+        if is_key_pressed(KeyCode::L) {
+            status_message = Some(match world.diagnostics_log.take() {
+                Some(log) => match log.save(Path::new(DIAGNOSTICS_PATH)) {
+                    Ok(()) => format!(
+                        "{} diagnostics samples saved to {}",
+                        log.samples.len(),
+                        DIAGNOSTICS_PATH
+                    ),
+                    Err(err) => format!("Cannot save diagnostics : {}", err),
+                },
+                None => {
+                    world.diagnostics_log = Some(DiagnosticsLog::new());
+                    "Recording diagnostics, press L again to save them".to_owned()
+                }
+            });
+        }
 
-        let dt = SIMULATION_DT;
+        if is_key_pressed(KeyCode::T) {
+            predict_collisions = !predict_collisions;
+            status_message = Some(match predict_collisions {
+                true => "Predicted trajectories include collisions".to_owned(),
+                false => "Predicted trajectories only follow gravity".to_owned(),
+            });
+        }
 
-        quad_tree = QuadTree::new(tree_area);
-        if !paused {
-            for _ in 0..frame_per_frame {
-                // Updating ball position
-                collided_balls.clear();
-                for index in 0..balls.len() {
-                    let ball = balls.get_mut(index).unwrap();
-                    quad_tree.add(QuadTreeEntry::new(ball.position, index));
-
-                    let mut local_force = Vec2::ZERO;
-                    if selected_ball == None || selected_ball.unwrap() != index {
-                        for body in &static_bodies {
-                            local_force = local_force + get_gravity_force(ball, body)
-                        }
-                    }
+        if is_key_pressed(KeyCode::B) {
+            world.physics.solver = match world.physics.solver {
+                GravitySolver::Direct => GravitySolver::BarnesHut,
+                GravitySolver::BarnesHut => GravitySolver::Direct,
+            };
+        }
 
-                    // ball.update(dt, local_force);
-                    ball.update_verlet(dt, local_force);
+        if is_key_pressed(KeyCode::Up) {
+            world.frame_per_frame += 1;
+        }
 
-                    // Recode previous positions
-                    traces[trace_index] = ball.position;
-                    trace_index = (trace_index + 1) % traces.len();
-                }
+        if is_key_pressed(KeyCode::Down) {
+            world.frame_per_frame = (world.frame_per_frame - 1).max(1);
+        }
 
-                // Colliding balls
-                for index in 0..balls.len() {
-                    // Has ball already collided this frame
-                    if collided_balls.iter().any(|c| c == &index) {
-                        continue;
-                    }
+        for (index, (paddle, (back, forward))) in
+            world.paddles.iter_mut().zip(PADDLE_KEYS).enumerate()
+        {
+            if opponents.iter().any(|ai| ai.paddle == index) {
+                continue;
+            }
+            paddle.input = is_key_down(forward) as i32 as f32 - is_key_down(back) as i32 as f32;
+        }
 
-                    let zone_check = balls[index].get_collision_area();
-                    let mut near_balls = Vec::new();
-                    quad_tree.query_entries(&zone_check, &mut near_balls);
-                    for entry in near_balls {
-                        if entry.payload == index
-                            || collided_balls.iter().any(|c| c == &entry.payload)
-                        {
-                            continue;
-                        }
-
-                        let other_ball_index = entry.payload;
-
-                        if balls[index].check_collision(&balls[other_ball_index]) {
-                            if index > other_ball_index {
-                                let (left, right) = balls.split_at_mut(index);
-                                right[0].collide(&mut left[other_ball_index], dt);
-                            } else {
-                                let (left, right) = balls.split_at_mut(other_ball_index);
-                                right[0].collide(&mut left[index], dt);
-                            }
-
-                            collided_balls.push(index);
-                            collided_balls.push(other_ball_index);
-                        }
-                    }
-                }
+        let frame_time = get_frame_time();
+        fps[fps_index] = frame_time;
+        fps_index = (fps_index + 1) % FPS_FRAMES;
 
-                // Bounce of static bodies
-                for body in static_bodies.iter_mut() {
-                    let query = body.get_collision_area();
-                    let mut near_objects = Vec::new();
-                    quad_tree.query_entries(&query, &mut near_objects);
-                    for near in near_objects {
-                        let ball = balls.get_mut(near.payload).unwrap();
-                        if body.check_collision(&ball) {
-                            // BOUNCE
-                            // let delta = ball.position - body.position;
-                            // if delta.dot(ball.velocity) < 0.
-                            //     && ball.velocity.length_squared() > 0.001
-                            // {
-                            //     let delta = delta.normalize();
-                            //     ball.position = body.position + delta * (body.radius + ball.radius);
-                            //     ball.set_velocity(
-                            //         (ball.velocity - 2. * delta.dot(ball.velocity) * delta)
-                            //             * BODY_BOUNCYNESS,
-                            //         dt,
-                            //     );
-                            // }
-
-                            // DELETE
-                            balls.remove(near.payload);
-                        }
-                    }
+        let dt = world.physics.dt;
+
+        if !paused {
+            let steps = world.frame_per_frame;
+            game.frame(&mut world, frame_time, steps, dt, |world| {
+                for ai in opponents.iter_mut() {
+                    ai.update(world, dt);
                 }
-            }
+            });
+        }
+
+        for event in world.drain_events() {
+            let counter = match event {
+                WorldEvent::Absorbed { .. } => 0,
+                WorldEvent::Bounced { .. } | WorldEvent::BouncedOffCapsule { .. } => 1,
+                WorldEvent::Landed { .. } => 2,
+                WorldEvent::PassedThrough { .. } => 3,
+                WorldEvent::Escaped { .. } => 4,
+            };
+            event_counts[counter] += 1;
         }
 
         let (spx, spy) = mouse_position();
         let mouse_pos = Vec2::new(spx, spy);
-        let mut near_balls = Vec::new();
-        quad_tree.query_entries(
-            &quad_tree::Rect::new(spx, spy, RADII * 2., RADII * 2.),
-            &mut near_balls,
-        );
-
-        let dist_check = RADII * RADII;
-        let under = near_balls
-            .into_iter()
-            .find(|b| (balls[b.payload].position - mouse_pos).length_squared() < dist_check);
+        let dist_check = PICK_RADIUS * PICK_RADIUS;
+        // The broad phase holds bounding boxes, picking is done on the ball centers
+        near_entries.clear();
+        world
+            .broad_phase
+            .query_radius_entries(mouse_pos, PICK_RADIUS, &mut near_entries);
+        let under = near_entries
+            .iter()
+            .filter_map(|entry| entry.payload.ball())
+            .find(|&id| {
+                world
+                    .balls
+                    .get(id)
+                    .is_some_and(|ball| (ball.position - mouse_pos).length_squared() < dist_check)
+            });
 
         if is_mouse_button_pressed(MouseButton::Left) {
-            match under {
-                Some(entry) => {
-                    selected_ball = Some(entry.payload);
-                }
-                _ => {}
+            if let Some(id) = under {
+                world.selected_ball = Some(id);
             }
         }
 
         if is_mouse_button_released(MouseButton::Left) {
-            selected_ball = None;
+            world.selected_ball = None;
         }
 
-        match selected_ball {
-            Some(ball_index) => {
-                let ball = balls.get_mut(ball_index).unwrap();
-                let force = damping(ball.position, mouse_pos, dt, 0.001);
+        if let Some(ball) = world.selected_ball.and_then(|id| world.balls.get_mut(id)) {
+            let force = damping(ball.position, mouse_pos, dt, 0.001);
 
-                ball.set_velocity(force, dt);
-            }
-            _ => {}
+            ball.set_velocity(force, dt);
         }
 
         if drawing_enabled {
@@ -331,39 +413,84 @@ async fn main() {
                 ..Default::default()
             });
 
-            for ball in &balls {
+            for (id, ball) in world.balls.iter() {
                 ball.draw();
 
+                // Draw the conic the ball would follow around the body pulling hardest on it
+                if let Some((body, elements)) = world.orbital_elements(id) {
+                    let center = world.static_bodies[body].ball.position;
+                    let mut c = ball.color;
+                    c.r -= 10.;
+                    conic.clear();
+                    elements.conic_points(center, ORBIT_SEGMENTS, WINDOW_SIZE[0], &mut conic);
+                    for segment in conic.windows(2) {
+                        draw_line(
+                            segment[0].x,
+                            segment[0].y,
+                            segment[1].x,
+                            segment[1].y,
+                            1.,
+                            c,
+                        );
+                    }
+                }
+
                 // ball.get_collision_area().debug_draw(1., ball.color);
+            }
 
-                // Draw ideal orbit
-                let mut c = ball.color;
-                c.r = c.r - 10.;
-                draw_poly_lines(
-                    static_bodies[0].position.x,
-                    static_bodies[0].position.y,
-                    100,
-                    (static_bodies[0].position - ball.position).length(),
-                    0.,
-                    1.,
-                    c,
-                );
+            // Predicted trajectory of the selected ball, or of the one under the mouse
+            if let Some(id) = world.selected_ball.or(under) {
+                predicted_path.clear();
+                let end = match predict_collisions {
+                    true => predictor.predict(&world, id, PREDICTION_STEPS, &mut predicted_path),
+                    false => predict_path(
+                        &world,
+                        &world.balls[id],
+                        PREDICTION_STEPS,
+                        &mut predicted_path,
+                    ),
+                };
+                let mut color = world.balls[id].color;
+                color.a = 0.6;
+                for segment in predicted_path.windows(2) {
+                    draw_line(
+                        segment[0].x,
+                        segment[0].y,
+                        segment[1].x,
+                        segment[1].y,
+                        1.,
+                        color,
+                    );
+                }
+                match end {
+                    PathEnd::Body { position, .. } => {
+                        draw_circle_lines(position.x, position.y, 8., 2., colors::RED)
+                    }
+                    PathEnd::Exit { position } => {
+                        draw_circle_lines(position.x, position.y, 8., 2., colors::YELLOW)
+                    }
+                    PathEnd::Horizon | PathEnd::Lost => {}
+                }
             }
 
-            for body in &static_bodies {
-                body.draw();
+            for body in &world.static_bodies {
+                body.ball.draw();
             }
 
-            // quad_tree.debug_draw();
+            for capsule in &world.capsules {
+                capsule.draw();
+            }
+
+            // world.broad_phase.debug_draw();
 
             // Draw trace objects
-            for trace in traces {
+            for trace in &world.traces {
                 draw_circle(trace.x, trace.y, 1., colors::BLUE);
             }
 
             // match under {
-            //     Some(entry) => {
-            //         let b = balls[entry.payload];
+            //     Some(id) => {
+            //         let b = world.balls[id];
             //         draw_circle_lines(b.position.x, b.position.y, b.radius, 2., colors::GOLD);
             //     }
             //     _ => {}
@@ -384,7 +511,7 @@ async fn main() {
             );
 
             draw_text_ex(
-                &format!("Simulation speed : {}", frame_per_frame),
+                &format!("Simulation speed : {}", world.frame_per_frame),
                 32.,
                 50.,
                 TextParams {
@@ -392,6 +519,95 @@ async fn main() {
                     ..Default::default()
                 },
             );
+
+            draw_text_ex(
+                &format!(
+                    "Integrator : {}",
+                    world.physics.integrator.integrator().name()
+                ),
+                32.,
+                68.,
+                TextParams {
+                    font_size: 15,
+                    ..Default::default()
+                },
+            );
+
+            let mut gravity_text = match world.physics.solver {
+                GravitySolver::Direct => "Gravity : direct".to_owned(),
+                GravitySolver::BarnesHut => {
+                    format!("Gravity : Barnes-Hut, theta {}", world.physics.theta)
+                }
+            };
+            if let Some(error) = world.force_error {
+                gravity_text += &format!(
+                    ", force error mean {:.2e} max {:.2e}",
+                    error.mean, error.max
+                );
+            }
+            draw_text_ex(
+                &gravity_text,
+                32.,
+                86.,
+                TextParams {
+                    font_size: 15,
+                    ..Default::default()
+                },
+            );
+
+            draw_game(&game);
+
+            if let Some((_, elements)) = under.and_then(|id| world.orbital_elements(id)) {
+                draw_orbit_tooltip(&elements, mouse_pos);
+            }
+
+            if let Some(message) = &status_message {
+                draw_text_ex(
+                    message,
+                    32.,
+                    104.,
+                    TextParams {
+                        font_size: 15,
+                        ..Default::default()
+                    },
+                );
+            }
+
+            if show_diagnostics {
+                let diagnostics = world.diagnostics();
+                let lines = [
+                    format!(
+                        "Energy : {:.6e} (kinetic {:.6e}, potential {:.6e})",
+                        diagnostics.total_energy(),
+                        diagnostics.kinetic_energy,
+                        diagnostics.potential_energy
+                    ),
+                    format!(
+                        "Momentum : ({:.4e}, {:.4e})",
+                        diagnostics.momentum.x, diagnostics.momentum.y
+                    ),
+                    format!("Angular momentum : {:.6e}", diagnostics.angular_momentum),
+                    format!(
+                        "Balls : {} absorbed, {} bounced, {} landed, {} passed through, {} escaped",
+                        event_counts[0],
+                        event_counts[1],
+                        event_counts[2],
+                        event_counts[3],
+                        event_counts[4]
+                    ),
+                ];
+                for (index, line) in lines.iter().enumerate() {
+                    draw_text_ex(
+                        line,
+                        32.,
+                        122. + index as f32 * 18.,
+                        TextParams {
+                            font_size: 15,
+                            ..Default::default()
+                        },
+                    );
+                }
+            }
         }
 
         next_frame().await
@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+
+/// Surface and bulk properties of a ball or body.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Material {
+    // Ratio of the normal separating speed after a contact to the approaching speed,
+    // 1 is perfectly elastic, 0 perfectly plastic
+    #[serde(default = "default_restitution")]
+    pub restitution: f32,
+    // Coulomb friction coefficient, bounds the tangential impulse by the normal one
+    #[serde(default)]
+    pub friction: f32,
+    // Mass per unit of area, lets scenarios give a radius and no mass
+    #[serde(default)]
+    pub density: Option<f32>,
+}
+
+fn default_restitution() -> f32 {
+    1.
+}
+
+impl Default for Material {
+    fn default() -> Material {
+        Material {
+            restitution: default_restitution(),
+            friction: 0.,
+            density: None,
+        }
+    }
+}
+
+impl Material {
+    /// Mass of a disc of this material, when a density is defined.
+    pub fn mass(&self, radius: f32) -> Option<f32> {
+        self.density
+            .map(|density| density * std::f32::consts::PI * radius * radius)
+    }
+
+    /// Properties used for a contact between the two materials. Like Box2D, the bounciest
+    /// restitution wins and the friction is the geometric mean, so a frictionless surface
+    /// stays frictionless whatever touches it.
+    pub fn combine(&self, other: &Material) -> Contact {
+        Contact {
+            restitution: self.restitution.max(other.restitution),
+            friction: (self.friction * other.friction).sqrt(),
+        }
+    }
+}
+
+/// Combined material of two touching objects.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Contact {
+    pub restitution: f32,
+    pub friction: f32,
+}
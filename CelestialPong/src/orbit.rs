@@ -0,0 +1,145 @@
+use std::f32::consts::TAU;
+
+use macroquad::prelude::*;
+
+use crate::ball::Ball;
+use crate::paddle::wrap_angle;
+use crate::static_body::StaticBody;
+
+/// Where an orbit leads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrbitKind {
+    /// An ellipse, the ball keeps coming back
+    Bound,
+    /// A parabola or hyperbola, the ball leaves and never comes back
+    Escape,
+    /// The ball hits the body before its periapsis
+    Impact,
+}
+
+/// Keplerian elements of a ball around a body, from their relative position and velocity.
+/// Only the body attracts the ball, the softening is ignored. Angles are in radians and
+/// times in seconds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OrbitalElements {
+    // Negative for hyperbolic orbits, infinite for parabolic ones
+    pub semi_major_axis: f32,
+    pub eccentricity: f32,
+    // Direction of the periapsis from the body, 0 for circular orbits
+    pub argument_of_periapsis: f32,
+    // Closest and farthest distances between the centers, no apoapsis when unbound
+    pub periapsis: f32,
+    pub apoapsis: Option<f32>,
+    pub period: Option<f32>,
+    // Kinetic plus potential energy per unit of mass
+    pub specific_energy: f32,
+    // Positive when the ball turns the same way as the angles increase
+    pub specific_angular_momentum: f32,
+    pub kind: OrbitKind,
+}
+
+/// Static body pulling hardest on a ball at `position`.
+pub fn dominant_body(bodies: &[StaticBody], position: Vec2) -> Option<usize> {
+    let pull = |body: &StaticBody| body.ball.mass / body.ball.position.distance_squared(position);
+    (0..bodies.len()).max_by(|&a, &b| pull(&bodies[a]).total_cmp(&pull(&bodies[b])))
+}
+
+/// Position and velocity relative to a body of gravitational parameter `mu` (gravity
+/// times its mass) of a ball on the given orbit, the reverse of `OrbitalElements::new`.
+/// Open orbits take the magnitude of their semi-major axis, and true anomalies past their
+/// asymptotes are pulled back within them. Retrograde balls turn the other way around.
+pub fn orbit_state(
+    mu: f32,
+    semi_major_axis: f32,
+    eccentricity: f32,
+    argument_of_periapsis: f32,
+    true_anomaly: f32,
+    retrograde: bool,
+) -> (Vec2, Vec2) {
+    let semi_latus_rectum = semi_major_axis.abs() * (1. - eccentricity * eccentricity).abs();
+    let mut anomaly = true_anomaly;
+    if eccentricity >= 1. {
+        let asymptote = (-1. / eccentricity).acos();
+        anomaly = wrap_angle(anomaly).clamp(-0.95 * asymptote, 0.95 * asymptote);
+    }
+
+    let radius = semi_latus_rectum / (1. + eccentricity * anomaly.cos());
+    let speed = (mu / semi_latus_rectum).sqrt();
+    let sense = if retrograde { -1. } else { 1. };
+    let position = vec2(anomaly.cos(), sense * anomaly.sin()) * radius;
+    let velocity = vec2(-anomaly.sin(), sense * (eccentricity + anomaly.cos())) * speed;
+    let rotation = Vec2::from_angle(argument_of_periapsis);
+    (rotation.rotate(position), rotation.rotate(velocity))
+}
+
+impl OrbitalElements {
+    pub fn new(ball: &Ball, body: &Ball, gravity: f32) -> OrbitalElements {
+        let mu = gravity * body.mass;
+        let r = ball.position - body.position;
+        let v = ball.velocity - body.velocity;
+        let distance = r.length();
+
+        let specific_energy = v.length_squared() / 2. - mu / distance;
+        let specific_angular_momentum = r.perp_dot(v);
+        let eccentricity_vector = ((v.length_squared() - mu / distance) * r - r.dot(v) * v) / mu;
+        let eccentricity = eccentricity_vector.length();
+        let semi_major_axis = -mu / (2. * specific_energy);
+
+        // From the semi-latus rectum, valid for every conic
+        let periapsis = specific_angular_momentum.powi(2) / (mu * (1. + eccentricity));
+        let bound = specific_energy < 0.;
+        let apoapsis = bound.then_some(semi_major_axis * (1. + eccentricity));
+        let period = bound.then(|| TAU * (semi_major_axis.powi(3) / mu).sqrt());
+        let argument_of_periapsis = match eccentricity > 1e-6 {
+            true => eccentricity_vector.y.atan2(eccentricity_vector.x),
+            false => 0.,
+        };
+
+        // Unbound balls moving away already passed their periapsis
+        let approaching = bound || r.dot(v) < 0.;
+        let kind = if periapsis <= body.radius + ball.radius && approaching {
+            OrbitKind::Impact
+        } else if bound {
+            OrbitKind::Bound
+        } else {
+            OrbitKind::Escape
+        };
+
+        OrbitalElements {
+            semi_major_axis,
+            eccentricity,
+            argument_of_periapsis,
+            periapsis,
+            apoapsis,
+            period,
+            specific_energy,
+            specific_angular_momentum,
+            kind,
+        }
+    }
+
+    /// Distance from the body at `angle` around it, None where an open orbit never goes.
+    pub fn radius_at(&self, angle: f32) -> Option<f32> {
+        let semi_latus_rectum = self.periapsis * (1. + self.eccentricity);
+        let denominator = 1. + self.eccentricity * (angle - self.argument_of_periapsis).cos();
+        (denominator > 1e-6).then(|| semi_latus_rectum / denominator)
+    }
+
+    /// Pushes `segments + 1` points of the conic around a body at `center`, from apoapsis
+    /// to apoapsis, leaving out those farther than `max_radius`. Closed orbits end where
+    /// they started, open ones are a single run of points around their periapsis.
+    pub fn conic_points(
+        &self,
+        center: Vec2,
+        segments: usize,
+        max_radius: f32,
+        out: &mut Vec<Vec2>,
+    ) {
+        for index in 0..=segments {
+            let angle = self.argument_of_periapsis + TAU * (index as f32 / segments as f32 - 0.5);
+            if let Some(radius) = self.radius_at(angle).filter(|r| *r <= max_radius) {
+                out.push(center + Vec2::from_angle(angle) * radius);
+            }
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use std::f32::consts::{PI, TAU};
+
+use macroquad::prelude::*;
+
+use crate::capsule::Capsule;
+
+/// `angle` plus or minus full turns, between -PI and PI.
+pub fn wrap_angle(angle: f32) -> f32 {
+    (angle + PI).rem_euclid(TAU) - PI
+}
+
+/// A player controlled paddle sliding along a circle around a static body. It drives one
+/// of the world capsules, kept on the chord of the circle centered on `angle`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Paddle {
+    // Index in `World::capsules`
+    pub capsule: usize,
+    // Index in `World::static_bodies`
+    pub around: usize,
+    // Distance from the body center to the middle of the paddle
+    pub orbit: f32,
+    // Where the paddle is around the body, in radians
+    pub angle: f32,
+    pub length: f32,
+    // Speed along the circle when fully steered
+    pub speed: f32,
+    // Smallest and largest angle the paddle can reach, None to go all the way around
+    pub arc: Option<(f32, f32)>,
+    // Steering between -1 and 1, positive increases the angle. Set by the player every frame
+    pub input: f32,
+}
+
+impl Paddle {
+    /// Ends of the paddle when the body is at `center`.
+    pub fn endpoints(&self, center: Vec2) -> (Vec2, Vec2) {
+        let direction = Vec2::from_angle(self.angle);
+        let middle = center + direction * self.orbit;
+        let half = direction.perp() * self.length / 2.;
+        (middle - half, middle + half)
+    }
+
+    /// Middle of the part of the circle the paddle guards: its arc, or where it is when it
+    /// can go all the way around.
+    pub fn sector_center(&self) -> f32 {
+        match self.arc {
+            Some((min, max)) => (min + max) / 2.,
+            None => self.angle,
+        }
+    }
+
+    /// `angle` plus or minus full turns, to be as close as possible to the paddle sector.
+    pub fn nearest_angle(&self, angle: f32) -> f32 {
+        let center = self.sector_center();
+        center + wrap_angle(angle - center)
+    }
+
+    /// Puts the capsule where the paddle is, at rest.
+    pub fn place(&self, capsule: &mut Capsule, center: Vec2) {
+        (capsule.p1, capsule.p2) = self.endpoints(center);
+        capsule.velocity = Vec2::ZERO;
+    }
+
+    /// Moves the paddle along its circle by its input for a step of `dt`. The capsule is
+    /// turned to its new orientation and given the velocity taking it to its new place,
+    /// moving it is left to the world, like for every other capsule.
+    pub fn steer(&mut self, capsule: &mut Capsule, center: Vec2, dt: f32) {
+        let angular_speed = self.input.clamp(-1., 1.) * self.speed / self.orbit;
+        self.angle += angular_speed * dt;
+        self.angle = match self.arc {
+            Some((min, max)) => self.angle.clamp(min, max),
+            None => wrap_angle(self.angle),
+        };
+
+        let (p1, p2) = self.endpoints(center);
+        let displacement = (p1 + p2 - capsule.p1 - capsule.p2) / 2.;
+        capsule.p1 = p1 - displacement;
+        capsule.p2 = p2 - displacement;
+        capsule.velocity = displacement / dt;
+    }
+}
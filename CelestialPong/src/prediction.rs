@@ -0,0 +1,131 @@
+use macroquad::prelude::*;
+
+use crate::ball::{Ball, BallId};
+use crate::gravity::{get_field_acceleration, Attractor};
+use crate::world::{World, WorldEvent};
+
+/// Why a predicted path stops.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PathEnd {
+    /// Every step was predicted
+    Horizon,
+    /// The ball reaches the static body `body` at `position`
+    Body { body: usize, position: Vec2 },
+    /// The ball leaves the playing field at `position`
+    Exit { position: Vec2 },
+    /// The ball is removed by something else than a body or the field edge
+    Lost,
+}
+
+/// Path `ball` will follow in the field of the static bodies, stepped with the world
+/// integrator and time step. `path` gets the start position then one position per step.
+/// Bodies are frozen where they are, other balls and capsules are ignored. Stops after
+/// `steps` steps, or once the ball touches a static body or leaves the field.
+pub fn predict_path(world: &World, ball: &Ball, steps: usize, path: &mut Vec<Vec2>) -> PathEnd {
+    let integrator = world.physics.integrator.integrator();
+    let dt = world.physics.dt;
+    let attractors: Vec<Attractor> = world
+        .static_bodies
+        .iter()
+        .map(|body| Attractor::new(&body.ball))
+        .collect();
+    let acceleration = |position: Vec2| {
+        get_field_acceleration(
+            position,
+            &attractors,
+            None,
+            world.physics.gravity,
+            world.physics.softening,
+        )
+    };
+
+    let mut ball = *ball;
+    path.push(ball.position);
+    for _ in 0..steps {
+        integrator.integrate(&mut ball, dt, &acceleration);
+        path.push(ball.position);
+        let touched = world
+            .static_bodies
+            .iter()
+            .position(|body| body.ball.check_collision(&ball));
+        if let Some(body) = touched {
+            return PathEnd::Body {
+                body,
+                position: ball.position,
+            };
+        }
+        if !world.tree_area.contains(ball.position) {
+            return PathEnd::Exit {
+                position: ball.position,
+            };
+        }
+    }
+    PathEnd::Horizon
+}
+
+/// Predicts paths by stepping a copy of the world, so collisions with the other balls
+/// and the capsules are included. Much slower than `predict_path`, the copy is kept
+/// between predictions to reuse its memory.
+#[derive(Default)]
+pub struct Predictor {
+    copy: Option<World>,
+}
+
+impl Predictor {
+    pub fn new() -> Predictor {
+        Predictor::default()
+    }
+
+    /// Same as `predict_path` for the ball `id`, with every collision. Boundaries apply as
+    /// in the world, so the path only ends on the field edge when the ball escapes.
+    pub fn predict(
+        &mut self,
+        world: &World,
+        id: BallId,
+        steps: usize,
+        path: &mut Vec<Vec2>,
+    ) -> PathEnd {
+        let snapshot = world.snapshot();
+        let copy = self
+            .copy
+            .get_or_insert_with(|| World::new(world.scenario.clone()));
+        copy.restore(&snapshot);
+        // The ball held by the player is predicted as if it was let go
+        copy.selected_ball = None;
+
+        let Some(ball) = copy.balls.get(id) else {
+            return PathEnd::Lost;
+        };
+        path.push(ball.position);
+        for _ in 0..steps {
+            copy.step(copy.physics.dt);
+            if let Some(ball) = copy.balls.get(id) {
+                path.push(ball.position);
+            }
+
+            for event in copy.drain_events() {
+                match event {
+                    WorldEvent::Absorbed {
+                        ball,
+                        body,
+                        position,
+                    } if ball == id => return PathEnd::Body { body, position },
+                    WorldEvent::Bounced { ball, body, .. } | WorldEvent::Landed { ball, body }
+                        if ball == id =>
+                    {
+                        let position = *path.last().unwrap();
+                        return PathEnd::Body { body, position };
+                    }
+                    WorldEvent::Escaped { ball, position } if ball == id => {
+                        return PathEnd::Exit { position };
+                    }
+                    _ => {}
+                }
+            }
+            if !copy.balls.contains(id) {
+                return PathEnd::Lost;
+            }
+        }
+        PathEnd::Horizon
+    }
+}
@@ -3,6 +3,13 @@ use macroquad::{
     prelude::*,
 };
 
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ball::BallId;
+use crate::capsule::segments_distance_squared;
+
 #[derive(Clone, Copy, Debug)]
 pub struct Rect {
     pub x: f32,
@@ -18,7 +25,7 @@ pub struct Rect {
 
 impl Rect {
     pub fn new(x: f32, y: f32, width: f32, height: f32) -> Rect {
-        return Rect {
+        Rect {
             x,
             y,
             half_width: width / 2.,
@@ -28,20 +35,112 @@ impl Rect {
             right: x + width / 2.,
             up: y - height / 2.,
             down: y + height / 2.,
-        };
+        }
+    }
+
+    pub fn from_edges(left: f32, right: f32, up: f32, down: f32) -> Rect {
+        Rect {
+            x: (left + right) / 2.,
+            y: (up + down) / 2.,
+            half_width: (right - left) / 2.,
+            half_height: (down - up) / 2.,
+
+            left,
+            right,
+            up,
+            down,
+        }
+    }
+
+    /// Box of half size `half_size` around `center`.
+    pub fn around(center: Vec2, half_size: Vec2) -> Rect {
+        Rect::new(center.x, center.y, half_size.x * 2., half_size.y * 2.)
     }
+
+    pub fn center(&self) -> Vec2 {
+        Vec2::new(self.x, self.y)
+    }
+
+    pub fn half_size(&self) -> Vec2 {
+        Vec2::new(self.half_width, self.half_height)
+    }
+
+    /// Smallest rect containing both.
+    pub fn union(&self, other: &Rect) -> Rect {
+        Rect::from_edges(
+            self.left.min(other.left),
+            self.right.max(other.right),
+            self.up.min(other.up),
+            self.down.max(other.down),
+        )
+    }
+
     pub fn contains(&self, pos: Vec2) -> bool {
-        return pos.x >= self.x - self.half_width
-            && pos.x < self.x + self.half_width
-            && pos.y >= self.y - self.half_height
-            && pos.y < self.y + self.half_height;
+        pos.x >= self.left && pos.x < self.right && pos.y >= self.up && pos.y < self.down
+    }
+
+    pub fn contains_rect(&self, other: &Rect) -> bool {
+        other.left >= self.left
+            && other.right <= self.right
+            && other.up >= self.up
+            && other.down <= self.down
     }
 
     pub fn overlap(&self, other: &Rect) -> bool {
-        return !(self.right < other.left
+        !(self.right < other.left
             || self.left > other.right
             || self.up > other.down
-            || self.down < other.up);
+            || self.down < other.up)
+    }
+
+    /// Squared distance from `point` to the closest point of the rect, 0 inside.
+    pub fn distance_squared(&self, point: Vec2) -> f32 {
+        ((point - self.center()).abs() - self.half_size())
+            .max(Vec2::ZERO)
+            .length_squared()
+    }
+
+    /// Squared distance from the segment `a`-`b` to the rect, 0 if they cross.
+    pub fn segment_distance_squared(&self, a: Vec2, b: Vec2) -> f32 {
+        if self.ray_distance(a, b - a, 1.).is_some() {
+            return 0.;
+        }
+
+        // Otherwise the closest points are an end of the segment or a corner of the rect,
+        // both are covered by the distance to the edges
+        let corners = [
+            Vec2::new(self.left, self.up),
+            Vec2::new(self.right, self.up),
+            Vec2::new(self.right, self.down),
+            Vec2::new(self.left, self.down),
+        ];
+        (0..4)
+            .map(|i| segments_distance_squared(a, b, corners[i], corners[(i + 1) % 4]))
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    /// Where the ray from `origin` along `direction` enters the rect, in multiples of
+    /// `direction`, 0 if it starts inside. None if it misses or enters past `max_distance`.
+    pub fn ray_distance(&self, origin: Vec2, direction: Vec2, max_distance: f32) -> Option<f32> {
+        let min = Vec2::new(self.left, self.up);
+        let max = Vec2::new(self.right, self.down);
+        let mut enter = 0f32;
+        let mut exit = max_distance;
+        for axis in 0..2 {
+            if direction[axis] == 0. {
+                // Parallel to the slab, either always inside it or never
+                if origin[axis] < min[axis] || origin[axis] > max[axis] {
+                    return None;
+                }
+                continue;
+            }
+
+            let a = (min[axis] - origin[axis]) / direction[axis];
+            let b = (max[axis] - origin[axis]) / direction[axis];
+            enter = enter.max(a.min(b));
+            exit = exit.min(a.max(b));
+        }
+        (enter <= exit).then_some(enter)
     }
 
     pub fn debug_draw(&self, thickness: f32, color: Color) {
@@ -56,124 +155,600 @@ impl Rect {
     }
 }
 
+/// What a quad tree entry stands for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Collider {
+    Ball(BallId),
+    // Index in `World::static_bodies`
+    Body(usize),
+    // Index in `World::capsules`
+    Capsule(usize),
+}
+
+impl Collider {
+    pub fn ball(self) -> Option<BallId> {
+        match self {
+            Collider::Ball(id) => Some(id),
+            _ => None,
+        }
+    }
+
+    pub fn body(self) -> Option<usize> {
+        match self {
+            Collider::Body(index) => Some(index),
+            _ => None,
+        }
+    }
+
+    pub fn capsule(self) -> Option<usize> {
+        match self {
+            Collider::Capsule(index) => Some(index),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct QuadTreeEntry {
-    pub position: Vec2,
-    pub payload: usize,
+    pub bounds: Rect,
+    pub payload: Collider,
 }
 
 impl QuadTreeEntry {
-    pub fn new(position: Vec2, payload: usize) -> QuadTreeEntry {
-        return QuadTreeEntry { position, payload };
+    pub fn new(bounds: Rect, payload: Collider) -> QuadTreeEntry {
+        QuadTreeEntry { bounds, payload }
     }
 }
 
-const QUADTREE_SIZE: usize = 2;
+/// Deepest `QuadTreeConfig::max_depth` allowed.
+pub const MAX_DEPTH: usize = 32;
+
+/// Shape of a quad tree, can be tuned per scenario.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct QuadTreeConfig {
+    // Entries a node holds before it is split
+    #[serde(default = "default_node_capacity")]
+    pub node_capacity: usize,
+    // Nodes at this depth are never split, their bucket grows instead.
+    // This is what stops entries at the same position from splitting forever.
+    // At most `MAX_DEPTH`.
+    #[serde(default = "default_max_depth")]
+    pub max_depth: usize,
+}
+
+fn default_node_capacity() -> usize {
+    4
+}
+
+fn default_max_depth() -> usize {
+    12
+}
+
+impl Default for QuadTreeConfig {
+    fn default() -> QuadTreeConfig {
+        QuadTreeConfig {
+            node_capacity: default_node_capacity(),
+            max_depth: default_max_depth(),
+        }
+    }
+}
+
+// Child of a node covering `area` that contains `position`, the children areas are
+// half open like `Rect::contains`
+fn quadrant(area: &Rect, position: Vec2) -> u32 {
+    (position.x >= area.x) as u32 + 2 * (position.y >= area.y) as u32
+}
+
+// Whether `bounds` fits in the loose area of the children of a node covering `area`
+fn fits_children(area: &Rect, bounds: &Rect) -> bool {
+    let half_size = area.half_size() / 2.;
+    bounds.half_width <= half_size.x && bounds.half_height <= half_size.y
+}
+
+const NO_NODE: u32 = u32::MAX;
+
+// Deepest a depth first walk of the tree can stack nodes: 3 siblings left per level
+const STACK_SIZE: usize = MAX_DEPTH * 3 + 4;
 
 #[derive(Clone, Debug)]
-pub struct QuadTree {
-    entries: [QuadTreeEntry; QUADTREE_SIZE],
-    number_of_entries: usize,
+struct Node {
     area: Rect,
-    sub_trees: Option<Box<[QuadTree; 4]>>,
+    // Area grown by half its size on every side. Entries centered in `area` and no larger
+    // than half of it stay inside, this is what lets entries of any size live in a node.
+    loose: Rect,
+    depth: usize,
+    parent: u32,
+    // Index of the first of the 4 consecutive children, NO_NODE for leaves
+    children: u32,
+    // Entries too large for the children, and every entry of leaves
+    entries: Vec<QuadTreeEntry>,
+}
+
+impl Node {
+    fn new(area: Rect, depth: usize, parent: u32) -> Node {
+        Node {
+            area,
+            loose: Rect::around(area.center(), area.half_size() * 2.),
+            depth,
+            parent,
+            children: NO_NODE,
+            entries: Vec::new(),
+        }
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.children == NO_NODE
+    }
+
+    // Whether `bounds` belongs to this node rather than to its parent or children
+    fn holds(&self, bounds: &Rect) -> bool {
+        self.area.contains(bounds.center())
+            && (self.depth == 0 || self.loose.contains_rect(bounds))
+            && (self.is_leaf() || !fits_children(&self.area, bounds))
+    }
+}
+
+/// Loose quad tree of bounding boxes, the root node is the first one.
+/// Each entry is stored once, in the deepest node whose area contains its center and
+/// whose loose area contains all of it. The root takes entries of any size.
+/// Nodes and their entry buckets are kept when they are merged back or cleared and reused
+/// by later splits, so once it has grown keeping the tree up to date does not allocate.
+#[derive(Clone, Debug)]
+pub struct QuadTree {
+    config: QuadTreeConfig,
+    nodes: Vec<Node>,
+    // First index of unused blocks of 4 children
+    free_blocks: Vec<u32>,
+    // Node holding each entry
+    holders: HashMap<Collider, u32>,
 }
 
 impl QuadTree {
     pub fn new(area: Rect) -> QuadTree {
-        return QuadTree {
-            area,
-            entries: [QuadTreeEntry::new(Vec2::ZERO, 0); QUADTREE_SIZE],
-            number_of_entries: 0,
-            sub_trees: Option::None,
+        QuadTree::with_config(area, QuadTreeConfig::default())
+    }
+
+    pub fn with_config(area: Rect, mut config: QuadTreeConfig) -> QuadTree {
+        config.max_depth = config.max_depth.min(MAX_DEPTH);
+        QuadTree {
+            config,
+            nodes: vec![Node::new(area, 0, NO_NODE)],
+            free_blocks: Vec::new(),
+            holders: HashMap::new(),
+        }
+    }
+
+    pub fn area(&self) -> &Rect {
+        &self.nodes[0].area
+    }
+
+    pub fn config(&self) -> QuadTreeConfig {
+        self.config
+    }
+
+    /// Removes every entry, keeping the nodes for later use.
+    pub fn clear(&mut self) {
+        for node in self.nodes.iter_mut() {
+            node.entries.clear();
+        }
+        self.nodes[0].children = NO_NODE;
+        self.free_blocks.clear();
+        self.free_blocks
+            .extend((1..self.nodes.len() as u32).step_by(4).rev());
+        self.holders.clear();
+    }
+
+    fn split(&mut self, index: u32) {
+        let area = self.nodes[index as usize].area;
+        let depth = self.nodes[index as usize].depth + 1;
+        // Children share their edges exactly with the parent center, so `quadrant`
+        // always agrees with `Rect::contains`
+        let areas = [
+            Rect::from_edges(area.left, area.x, area.up, area.y),
+            Rect::from_edges(area.x, area.right, area.up, area.y),
+            Rect::from_edges(area.left, area.x, area.y, area.down),
+            Rect::from_edges(area.x, area.right, area.y, area.down),
+        ];
+
+        let first = match self.free_blocks.pop() {
+            Some(first) => first,
+            None => {
+                let first = self.nodes.len() as u32;
+                self.nodes
+                    .extend(areas.iter().map(|area| Node::new(*area, depth, index)));
+                first
+            }
         };
+        for (offset, area) in areas.iter().enumerate() {
+            self.nodes[first as usize + offset] = Node {
+                entries: std::mem::take(&mut self.nodes[first as usize + offset].entries),
+                ..Node::new(*area, depth, index)
+            };
+        }
+        self.nodes[index as usize].children = first;
+
+        // Entries small enough move down, the bucket keeps its capacity
+        let mut entries = std::mem::take(&mut self.nodes[index as usize].entries);
+        let mut kept = 0;
+        for position in 0..entries.len() {
+            let entry = entries[position];
+            if fits_children(&area, &entry.bounds) {
+                let child = first + quadrant(&area, entry.bounds.center());
+                self.nodes[child as usize].entries.push(entry);
+                self.holders.insert(entry.payload, child);
+            } else {
+                entries[kept] = entry;
+                kept += 1;
+            }
+        }
+        entries.truncate(kept);
+        self.nodes[index as usize].entries = entries;
     }
 
-    fn is_full(&self) -> bool {
-        return self.number_of_entries >= QUADTREE_SIZE;
+    // Puts the children of `index` back into it if they hold few enough entries,
+    // and keeps going up the tree
+    fn merge(&mut self, mut index: u32) {
+        while index != NO_NODE {
+            let node = &self.nodes[index as usize];
+            let first = node.children as usize;
+            if node.is_leaf() {
+                index = node.parent;
+                continue;
+            }
+
+            let children = &self.nodes[first..first + 4];
+            let count: usize = children.iter().map(|child| child.entries.len()).sum();
+            if children.iter().any(|child| !child.is_leaf())
+                || count + node.entries.len() > self.config.node_capacity
+            {
+                return;
+            }
+
+            let mut entries = std::mem::take(&mut self.nodes[index as usize].entries);
+            for child in first..first + 4 {
+                for entry in self.nodes[child].entries.drain(..) {
+                    self.holders.insert(entry.payload, index);
+                    entries.push(entry);
+                }
+            }
+            self.nodes[index as usize].entries = entries;
+            self.nodes[index as usize].children = NO_NODE;
+            self.free_blocks.push(first as u32);
+            index = self.nodes[index as usize].parent;
+        }
     }
 
-    pub fn add(&mut self, entry: QuadTreeEntry) {
-        if !self.area.contains(entry.position) {
-            return;
+    /// Adds an entry, replacing any entry with the same payload.
+    /// Returns false if the center of the entry is outside of the tree area.
+    pub fn add(&mut self, entry: QuadTreeEntry) -> bool {
+        self.remove(entry.payload);
+        let center = entry.bounds.center();
+        if !self.area().contains(center) {
+            return false;
         }
 
-        match self.is_full() {
-            false => {
-                self.entries[self.number_of_entries] = entry;
-                self.number_of_entries = self.number_of_entries + 1;
-                if self.is_full() {
-                    self.sub_trees = Some(Box::new([
-                        QuadTree::new(Rect::new(
-                            self.area.x - self.area.half_width / 2.,
-                            self.area.y - self.area.half_height / 2.,
-                            self.area.half_width,
-                            self.area.half_height,
-                        )),
-                        QuadTree::new(Rect::new(
-                            self.area.x + self.area.half_width / 2.,
-                            self.area.y - self.area.half_height / 2.,
-                            self.area.half_width,
-                            self.area.half_height,
-                        )),
-                        QuadTree::new(Rect::new(
-                            self.area.x - self.area.half_width / 2.,
-                            self.area.y + self.area.half_height / 2.,
-                            self.area.half_width,
-                            self.area.half_height,
-                        )),
-                        QuadTree::new(Rect::new(
-                            self.area.x + self.area.half_width / 2.,
-                            self.area.y + self.area.half_height / 2.,
-                            self.area.half_width,
-                            self.area.half_height,
-                        )),
-                    ]));
+        let mut index = 0;
+        loop {
+            let node = &self.nodes[index as usize];
+            let small = fits_children(&node.area, &entry.bounds);
+            if node.is_leaf() {
+                let full = node.entries.len() >= self.config.node_capacity.max(1);
+                if !full || !small || node.depth >= self.config.max_depth {
+                    self.push(index, entry);
+                    return true;
                 }
+                self.split(index);
+            } else if !small {
+                self.push(index, entry);
+                return true;
             }
-            true => match self.sub_trees {
-                Some(ref mut sub_nodes) => {
-                    for node in sub_nodes.iter_mut() {
-                        node.add(entry);
-                    }
+
+            let node = &self.nodes[index as usize];
+            index = node.children + quadrant(&node.area, center);
+        }
+    }
+
+    fn push(&mut self, index: u32, entry: QuadTreeEntry) {
+        self.nodes[index as usize].entries.push(entry);
+        self.holders.insert(entry.payload, index);
+    }
+
+    /// Returns false if there was no entry for `payload`.
+    pub fn remove(&mut self, payload: Collider) -> bool {
+        let Some(leaf) = self.holders.remove(&payload) else {
+            return false;
+        };
+
+        let entries = &mut self.nodes[leaf as usize].entries;
+        if let Some(position) = entries.iter().position(|entry| entry.payload == payload) {
+            entries.swap_remove(position);
+        }
+        let node = &self.nodes[leaf as usize];
+        match node.is_leaf() {
+            true => self.merge(node.parent),
+            false => self.merge(leaf),
+        }
+        true
+    }
+
+    /// Moves the entry of `payload` to `bounds`, adding it if it is missing.
+    /// Returns false, and removes the entry, if the new center is outside of the tree area.
+    pub fn update(&mut self, payload: Collider, bounds: Rect) -> bool {
+        if let Some(&holder) = self.holders.get(&payload) {
+            let node = &mut self.nodes[holder as usize];
+            if node.holds(&bounds) {
+                if let Some(entry) = node.entries.iter_mut().find(|e| e.payload == payload) {
+                    entry.bounds = bounds;
+                    return true;
                 }
-                None => panic!("missing subnodes!"),
-            },
+            }
         }
+
+        self.add(QuadTreeEntry::new(bounds, payload))
+    }
+
+    pub fn contains(&self, payload: Collider) -> bool {
+        self.holders.contains_key(&payload)
     }
 
+    // Entries for which `overlaps` is true, visiting only nodes whose loose area it
+    // accepts. `overlaps` must accept every rect containing one it accepts.
+    fn walk<F: Fn(&Rect) -> bool>(&self, overlaps: F) -> QueryIter<'_, F> {
+        QueryIter {
+            tree: self,
+            overlaps,
+            stack: [0; STACK_SIZE],
+            stack_size: 1,
+            node: NO_NODE,
+            entry: 0,
+        }
+    }
+
+    /// Entries whose bounds overlap `query`.
+    pub fn query<'a>(&'a self, query: &'a Rect) -> impl Iterator<Item = QuadTreeEntry> + 'a {
+        self.walk(move |bounds| bounds.overlap(query))
+    }
+
+    /// Pushes every entry whose bounds overlap `query`.
     pub fn query_entries(&self, query: &Rect, result: &mut Vec<QuadTreeEntry>) {
-        if !self.area.overlap(query) {
+        result.extend(self.query(query));
+    }
+
+    /// Entries whose bounds are at most `radius` away from `center`.
+    pub fn query_radius(
+        &self,
+        center: Vec2,
+        radius: f32,
+    ) -> impl Iterator<Item = QuadTreeEntry> + '_ {
+        self.walk(move |bounds| bounds.distance_squared(center) <= radius * radius)
+    }
+
+    pub fn query_radius_entries(&self, center: Vec2, radius: f32, result: &mut Vec<QuadTreeEntry>) {
+        result.extend(self.query_radius(center, radius));
+    }
+
+    /// Entries whose bounds are at most `radius` away from the segment `a`-`b`.
+    pub fn query_capsule(
+        &self,
+        a: Vec2,
+        b: Vec2,
+        radius: f32,
+    ) -> impl Iterator<Item = QuadTreeEntry> + '_ {
+        self.walk(move |bounds| bounds.segment_distance_squared(a, b) <= radius * radius)
+    }
+
+    pub fn query_capsule_entries(
+        &self,
+        a: Vec2,
+        b: Vec2,
+        radius: f32,
+        result: &mut Vec<QuadTreeEntry>,
+    ) {
+        result.extend(self.query_capsule(a, b, radius));
+    }
+
+    /// Entries whose bounds are hit by the ray from `origin` along `direction` before
+    /// `max_distance`, with the distance at which the ray enters them, in no particular order.
+    pub fn ray_hits(
+        &self,
+        origin: Vec2,
+        direction: Vec2,
+        max_distance: f32,
+    ) -> impl Iterator<Item = (f32, QuadTreeEntry)> + '_ {
+        let direction = direction.normalize_or_zero();
+        self.walk(move |bounds| {
+            bounds
+                .ray_distance(origin, direction, max_distance)
+                .is_some()
+        })
+        .filter_map(move |entry| {
+            let distance = entry.bounds.ray_distance(origin, direction, max_distance)?;
+            Some((distance, entry))
+        })
+    }
+
+    /// First entry hit by the ray, see `ray_hits`.
+    pub fn ray_cast(
+        &self,
+        origin: Vec2,
+        direction: Vec2,
+        max_distance: f32,
+    ) -> Option<(f32, QuadTreeEntry)> {
+        self.ray_hits(origin, direction, max_distance)
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+    }
+
+    // Depth first search visiting the closest children first. `visit` is given the entries
+    // closer than the bound it returned last, with their squared distance to `point`, and
+    // returns the new bound.
+    fn search_nearest(&self, point: Vec2, mut visit: impl FnMut(f32, &QuadTreeEntry) -> f32) {
+        let mut bound = f32::INFINITY;
+        let mut stack = [0u32; STACK_SIZE];
+        let mut stack_size = 1;
+        while stack_size > 0 {
+            stack_size -= 1;
+            let index = stack[stack_size];
+            let node = &self.nodes[index as usize];
+            if index != 0 && node.loose.distance_squared(point) > bound {
+                continue;
+            }
+
+            for entry in &node.entries {
+                let distance = entry.bounds.distance_squared(point);
+                if distance < bound {
+                    bound = visit(distance, entry);
+                }
+            }
+            if node.is_leaf() {
+                continue;
+            }
+
+            // The closest child goes last so it is visited first
+            let mut children = [0, 1, 2, 3].map(|child| node.children + child);
+            children.sort_by(|a, b| {
+                let a = self.nodes[*a as usize].loose.distance_squared(point);
+                let b = self.nodes[*b as usize].loose.distance_squared(point);
+                b.total_cmp(&a)
+            });
+            stack[stack_size..stack_size + 4].copy_from_slice(&children);
+            stack_size += 4;
+        }
+    }
+
+    /// Replaces the content of `result` by the `count` entries whose bounds are the
+    /// closest to `point`, with their distance, closest first.
+    pub fn nearest_entries(
+        &self,
+        point: Vec2,
+        count: usize,
+        result: &mut Vec<(f32, QuadTreeEntry)>,
+    ) {
+        result.clear();
+        if count == 0 {
             return;
         }
 
-        for entry in self.entries {
-            if query.contains(entry.position) {
-                result.push(entry);
+        self.search_nearest(point, |distance, entry| {
+            if result.len() == count {
+                result.pop();
+            }
+            let position = result.partition_point(|(other, _)| *other <= distance);
+            result.insert(position, (distance, *entry));
+            match result.len() == count {
+                true => result[count - 1].0,
+                false => f32::INFINITY,
             }
+        });
+        for (distance, _) in result.iter_mut() {
+            *distance = distance.sqrt();
         }
+    }
+
+    /// Entry whose bounds are the closest to `point`, with its distance.
+    pub fn nearest(&self, point: Vec2) -> Option<(f32, QuadTreeEntry)> {
+        let mut best = None;
+        self.search_nearest(point, |distance, entry| {
+            best = Some((distance, *entry));
+            distance
+        });
+        best.map(|(distance, entry)| (distance.sqrt(), entry))
+    }
+
+    /// Every entry, in no particular order.
+    pub fn entries(&self) -> impl Iterator<Item = QuadTreeEntry> + '_ {
+        self.nodes
+            .iter()
+            .flat_map(|node| node.entries.iter().copied())
+    }
 
-        match self.sub_trees {
-            Some(ref sub_nodes) => {
-                for node in sub_nodes.iter() {
-                    node.query_entries(query, result);
+    /// Nodes allocated so far, used or kept for later splits.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn len(&self) -> usize {
+        self.holders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.holders.is_empty()
+    }
+
+    /// Depth of the deepest leaf, the root is at depth 0.
+    pub fn depth(&self) -> usize {
+        self.leaf_nodes().map(|node| node.depth).max().unwrap_or(0)
+    }
+
+    fn leaf_nodes(&self) -> impl Iterator<Item = &Node> + '_ {
+        let mut stack = vec![0u32];
+        std::iter::from_fn(move || {
+            while let Some(index) = stack.pop() {
+                let node = &self.nodes[index as usize];
+                if node.is_leaf() {
+                    return Some(node);
                 }
+                stack.extend(node.children..node.children + 4);
             }
-            None => {}
-        }
+            None
+        })
     }
 
     pub fn debug_draw(&self) {
         let color = color::RED;
+        for node in self.leaf_nodes() {
+            node.area.debug_draw(2., color);
+        }
+    }
+}
+
+/// Entries found by a query on a `QuadTree`, in no particular order.
+/// Walks the tree with a fixed size stack, so it does not allocate.
+pub struct QueryIter<'a, F> {
+    tree: &'a QuadTree,
+    overlaps: F,
+    stack: [u32; STACK_SIZE],
+    stack_size: usize,
+    // Node whose entries are being listed
+    node: u32,
+    entry: usize,
+}
+
+impl<'a, F: Fn(&Rect) -> bool> Iterator for QueryIter<'a, F> {
+    type Item = QuadTreeEntry;
+
+    fn next(&mut self) -> Option<QuadTreeEntry> {
+        loop {
+            if self.node != NO_NODE {
+                let entries = &self.tree.nodes[self.node as usize].entries;
+                while let Some(entry) = entries.get(self.entry) {
+                    self.entry += 1;
+                    if (self.overlaps)(&entry.bounds) {
+                        return Some(*entry);
+                    }
+                }
+                self.node = NO_NODE;
+            }
 
-        self.area.debug_draw(2., color);
+            if self.stack_size == 0 {
+                return None;
+            }
+            self.stack_size -= 1;
+            let index = self.stack[self.stack_size];
+            let node = &self.tree.nodes[index as usize];
+            // Entries of the root may stick out of its loose area
+            if index != 0 && !(self.overlaps)(&node.loose) {
+                continue;
+            }
 
-        match &self.sub_trees {
-            Some(sub_nodes) => {
-                for node in sub_nodes.iter() {
-                    node.debug_draw();
+            if !node.is_leaf() {
+                for child in 0..4 {
+                    self.stack[self.stack_size] = node.children + child;
+                    self.stack_size += 1;
                 }
             }
-            None => {}
+            self.node = index;
+            self.entry = 0;
         }
     }
 }
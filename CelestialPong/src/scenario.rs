@@ -0,0 +1,719 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ai::Difficulty;
+use crate::boundary::BoundaryPolicy;
+use crate::broad_phase::BroadPhaseKind;
+use crate::game::GameRules;
+use crate::generator::OrbitDistribution;
+use crate::gravity::GravitySolver;
+use crate::integrator::IntegratorKind;
+use crate::material::Material;
+use crate::quad_tree::{QuadTreeConfig, MAX_DEPTH};
+use crate::static_body::ContactPolicy;
+
+/// Describes the initial state of a world: bodies, balls, physics constants and seed.
+/// Loaded from a TOML file, see `scenarios/default.toml`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    #[serde(default = "default_seed")]
+    pub seed: u64,
+    #[serde(default)]
+    pub physics: Physics,
+    // Scoring and match length
+    #[serde(default)]
+    pub game: GameRules,
+    // Size of the playing field, centered on the origin
+    #[serde(default = "default_field_size")]
+    pub field_size: [f32; 2],
+    // Named materials, referred to by bodies and balls
+    #[serde(default)]
+    pub materials: BTreeMap<String, Material>,
+    #[serde(default)]
+    pub static_bodies: Vec<BodyDesc>,
+    #[serde(default)]
+    pub capsules: Vec<CapsuleDesc>,
+    #[serde(default)]
+    pub paddles: Vec<PaddleDesc>,
+    #[serde(default)]
+    pub balls: Vec<BallPopulation>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Physics {
+    // Gravitational constant, a body of mass M pulls every ball with an acceleration of
+    // gravity * M / r² whatever the ball mass. Scenarios written when the pull also grew
+    // with the ball mass need their gravity multiplied by that mass: the old default of
+    // 15000 with balls of mass 2 is the current 30000.
+    #[serde(default = "default_gravity")]
+    pub gravity: f32,
+    #[serde(default = "default_dt")]
+    pub dt: f32,
+    #[serde(default)]
+    pub integrator: IntegratorKind,
+    // Balls attract each other, not only the static bodies
+    #[serde(default)]
+    pub n_body: bool,
+    // Static bodies are moved by gravity too
+    #[serde(default)]
+    pub dynamic_bodies: bool,
+    // Length added to every distance when computing gravity, avoids infinite forces
+    #[serde(default)]
+    pub softening: f32,
+    #[serde(default)]
+    pub solver: GravitySolver,
+    // Barnes-Hut opening angle, lower is more precise
+    #[serde(default = "default_theta")]
+    pub theta: f32,
+    // Measure the Barnes-Hut error against the exact sum every step
+    #[serde(default)]
+    pub report_force_error: bool,
+    // Sweep balls along their motion so fast ones cannot tunnel through each other
+    #[serde(default = "default_true")]
+    pub continuous_collisions: bool,
+    // What happens to balls reaching the edge of the field
+    #[serde(default)]
+    pub boundary: BoundaryPolicy,
+    // Spatial index used to find colliding balls
+    #[serde(default)]
+    pub broad_phase: BroadPhaseKind,
+    // Shape of the quad tree when it is the broad phase
+    #[serde(default)]
+    pub quad_tree: QuadTreeConfig,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BodyDesc {
+    #[serde(default)]
+    pub position: [f32; 2],
+    // Only used when `physics.dynamic_bodies` is set
+    #[serde(default)]
+    pub velocity: [f32; 2],
+    pub radius: f32,
+    // Derived from the material density when missing
+    pub mass: Option<f32>,
+    #[serde(default = "default_body_color")]
+    pub color: [f32; 4],
+    pub material: Option<String>,
+    // What happens to balls touching the body, absorbed by default
+    #[serde(default)]
+    pub contact: ContactPolicy,
+}
+
+/// A wall or a paddle: every point within `radius` of the segment from `p1` to `p2`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CapsuleDesc {
+    pub p1: [f32; 2],
+    pub p2: [f32; 2],
+    pub radius: f32,
+    // Kinematic capsules keep moving at this velocity, whatever they hit
+    #[serde(default)]
+    pub velocity: [f32; 2],
+    #[serde(default = "default_body_color")]
+    pub color: [f32; 4],
+    pub material: Option<String>,
+    // Fraction of the velocity along the segment given to balls bouncing off it
+    #[serde(default)]
+    pub transfer: f32,
+}
+
+/// A player controlled paddle: a capsule of `length` sliding along the circle of radius
+/// `orbit` around the static body `around`. Angles are in degrees.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PaddleDesc {
+    #[serde(default)]
+    pub around: usize,
+    pub orbit: f32,
+    #[serde(default)]
+    pub angle: f32,
+    pub length: f32,
+    pub radius: f32,
+    // Speed along the circle when fully steered
+    pub speed: f32,
+    // Smallest and largest angle the paddle can reach, all the way around when missing
+    pub arc: Option<[f32; 2]>,
+    // Fraction of the paddle velocity along its length given to the balls it hits
+    #[serde(default = "default_transfer")]
+    pub transfer: f32,
+    #[serde(default = "default_body_color")]
+    pub color: [f32; 4],
+    pub material: Option<String>,
+    // Played by the computer at this difficulty instead of a player
+    pub ai: Option<Difficulty>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum BallPopulation {
+    /// A single ball. Without a velocity it is put on a circular orbit around `around`.
+    /// Without a mass it is derived from the material density, as for `Orbital`.
+    Explicit {
+        position: [f32; 2],
+        velocity: Option<[f32; 2]>,
+        radius: f32,
+        mass: Option<f32>,
+        color: Option<[f32; 4]>,
+        material: Option<String>,
+        #[serde(default)]
+        around: usize,
+    },
+    /// `count` balls at a random distance from `around`, on circular orbits.
+    Orbital {
+        count: usize,
+        #[serde(default)]
+        around: usize,
+        min_orbit: f32,
+        max_orbit: f32,
+        radius: f32,
+        mass: Option<f32>,
+        color: Option<[f32; 4]>,
+        material: Option<String>,
+    },
+    /// `count` balls on orbits around `around` drawn from `orbits`.
+    Elements {
+        count: usize,
+        #[serde(default)]
+        around: usize,
+        orbits: OrbitDistribution,
+        radius: f32,
+        mass: Option<f32>,
+        color: Option<[f32; 4]>,
+        material: Option<String>,
+    },
+}
+
+fn default_seed() -> u64 {
+    1
+}
+
+fn default_field_size() -> [f32; 2] {
+    [3600., 3600.]
+}
+
+fn default_gravity() -> f32 {
+    30000.
+}
+
+fn default_dt() -> f32 {
+    1. / 120.
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_theta() -> f32 {
+    0.5
+}
+
+fn default_body_color() -> [f32; 4] {
+    [1., 1., 1., 1.]
+}
+
+fn default_transfer() -> f32 {
+    1.
+}
+
+impl Default for Physics {
+    fn default() -> Physics {
+        Physics {
+            gravity: default_gravity(),
+            dt: default_dt(),
+            integrator: IntegratorKind::default(),
+            n_body: false,
+            dynamic_bodies: false,
+            softening: 0.,
+            solver: GravitySolver::default(),
+            theta: default_theta(),
+            report_force_error: false,
+            continuous_collisions: true,
+            boundary: BoundaryPolicy::default(),
+            broad_phase: BroadPhaseKind::default(),
+            quad_tree: QuadTreeConfig::default(),
+        }
+    }
+}
+
+impl Default for Scenario {
+    fn default() -> Scenario {
+        Scenario {
+            seed: default_seed(),
+            // Balls escaping the field score, as in scenarios/default.toml
+            physics: Physics {
+                boundary: BoundaryPolicy::Despawn,
+                ..Physics::default()
+            },
+            game: GameRules::default(),
+            field_size: default_field_size(),
+            materials: BTreeMap::new(),
+            static_bodies: vec![BodyDesc {
+                position: [0., 0.],
+                velocity: [0., 0.],
+                radius: 30.,
+                mass: Some(1000.),
+                color: default_body_color(),
+                material: None,
+                contact: ContactPolicy::default(),
+            }],
+            capsules: Vec::new(),
+            paddles: Vec::new(),
+            balls: vec![BallPopulation::Orbital {
+                count: 2,
+                around: 0,
+                min_orbit: 100.,
+                max_orbit: 400.,
+                radius: 10.,
+                mass: Some(2.),
+                color: None,
+                material: None,
+            }],
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(PathBuf, std::io::Error),
+    // Field the error was found in, empty for syntax errors
+    Parse(String, toml::de::Error),
+    Invalid { field: String, reason: String },
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Io(path, err) => write!(f, "cannot read {}: {}", path.display(), err),
+            ScenarioError::Parse(field, err) if field.is_empty() => write!(f, "{}", err),
+            ScenarioError::Parse(field, err) => write!(f, "`{}`: {}", field, err),
+            ScenarioError::Invalid { field, reason } => write!(f, "`{}` {}", field, reason),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+fn invalid(field: String, reason: &str) -> ScenarioError {
+    ScenarioError::Invalid {
+        field,
+        reason: reason.to_owned(),
+    }
+}
+
+fn check_positive(field: String, value: f32) -> Result<(), ScenarioError> {
+    if value.is_finite() && value > 0. {
+        Ok(())
+    } else {
+        Err(invalid(field, "must be a positive number"))
+    }
+}
+
+fn check_range(field: String, range: [f32; 2]) -> Result<(), ScenarioError> {
+    check_finite(field.clone(), &range)?;
+    if range[1] < range[0] {
+        return Err(invalid(field, "must not end before it starts"));
+    }
+    Ok(())
+}
+
+fn check_fraction(field: String, value: f32) -> Result<(), ScenarioError> {
+    if (0. ..=1.).contains(&value) {
+        Ok(())
+    } else {
+        Err(invalid(field, "must be between 0 and 1"))
+    }
+}
+
+fn check_finite(field: String, values: &[f32]) -> Result<(), ScenarioError> {
+    if values.iter().all(|v| v.is_finite()) {
+        Ok(())
+    } else {
+        Err(invalid(field, "must only contain finite numbers"))
+    }
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Scenario, ScenarioError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|err| ScenarioError::Io(path.to_path_buf(), err))?;
+        Scenario::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Scenario, ScenarioError> {
+        let deserializer = toml::Deserializer::new(text);
+        let scenario: Scenario = serde_path_to_error::deserialize(deserializer).map_err(|err| {
+            let field = err.path().to_string();
+            ScenarioError::Parse(field.trim_start_matches('.').to_owned(), err.into_inner())
+        })?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    /// Checks the values serde cannot: ranges, and references between bodies.
+    pub fn validate(&self) -> Result<(), ScenarioError> {
+        check_positive("physics.gravity".to_owned(), self.physics.gravity)?;
+        check_positive("physics.dt".to_owned(), self.physics.dt)?;
+        if !(self.physics.softening.is_finite() && self.physics.softening >= 0.) {
+            return Err(invalid(
+                "physics.softening".to_owned(),
+                "must be zero or a positive number",
+            ));
+        }
+        check_positive("field_size[0]".to_owned(), self.field_size[0])?;
+        check_positive("field_size[1]".to_owned(), self.field_size[1])?;
+
+        if !(self.physics.theta.is_finite() && self.physics.theta >= 0.) {
+            return Err(invalid(
+                "physics.theta".to_owned(),
+                "must be zero or a positive number",
+            ));
+        }
+
+        if let BroadPhaseKind::Grid { cell_size } = self.physics.broad_phase {
+            check_positive("physics.broad_phase.cell_size".to_owned(), cell_size)?;
+        }
+        if self.physics.quad_tree.node_capacity == 0 {
+            return Err(invalid(
+                "physics.quad_tree.node_capacity".to_owned(),
+                "must be at least 1",
+            ));
+        }
+        if self.physics.quad_tree.max_depth > MAX_DEPTH {
+            return Err(invalid(
+                "physics.quad_tree.max_depth".to_owned(),
+                &format!("must be at most {}", MAX_DEPTH),
+            ));
+        }
+
+        for (field, delay) in [
+            ("game.serve_delay", self.game.serve_delay),
+            ("game.point_delay", self.game.point_delay),
+        ] {
+            if !(delay.is_finite() && delay >= 0.) {
+                return Err(invalid(
+                    field.to_owned(),
+                    "must be zero or a positive number",
+                ));
+            }
+        }
+        for (field, count) in [
+            ("game.lives", self.game.lives),
+            ("game.match_points", self.game.match_points),
+        ] {
+            if count == Some(0) {
+                return Err(invalid(field.to_owned(), "must be at least 1"));
+            }
+        }
+
+        for (name, material) in &self.materials {
+            let field = |field: &str| format!("materials.{}.{}", name, field);
+            if !(0. ..=1.).contains(&material.restitution) {
+                return Err(invalid(field("restitution"), "must be between 0 and 1"));
+            }
+            if !(material.friction.is_finite() && material.friction >= 0.) {
+                return Err(invalid(
+                    field("friction"),
+                    "must be zero or a positive number",
+                ));
+            }
+            if let Some(density) = material.density {
+                check_positive(field("density"), density)?;
+            }
+        }
+
+        for (index, body) in self.static_bodies.iter().enumerate() {
+            let field = |name: &str| format!("static_bodies[{}].{}", index, name);
+            check_finite(field("position"), &body.position)?;
+            check_finite(field("velocity"), &body.velocity)?;
+            check_positive(field("radius"), body.radius)?;
+            self.check_mass(&field, body.mass, &body.material, body.radius)?;
+            check_finite(field("color"), &body.color)?;
+            if let ContactPolicy::Bounce { correction } = body.contact {
+                if !(0. ..=1.).contains(&correction) {
+                    return Err(invalid(
+                        field("contact.correction"),
+                        "must be between 0 and 1",
+                    ));
+                }
+            }
+        }
+
+        for (index, capsule) in self.capsules.iter().enumerate() {
+            let field = |name: &str| format!("capsules[{}].{}", index, name);
+            check_finite(field("p1"), &capsule.p1)?;
+            check_finite(field("p2"), &capsule.p2)?;
+            check_positive(field("radius"), capsule.radius)?;
+            check_finite(field("velocity"), &capsule.velocity)?;
+            check_finite(field("color"), &capsule.color)?;
+            check_fraction(field("transfer"), capsule.transfer)?;
+            self.check_material(&field, &capsule.material)?;
+        }
+
+        for (index, paddle) in self.paddles.iter().enumerate() {
+            let field = |name: &str| format!("paddles[{}].{}", index, name);
+            if paddle.around >= self.static_bodies.len() {
+                return Err(invalid(
+                    field("around"),
+                    &format!(
+                        "refers to static body {} but only {} are defined",
+                        paddle.around,
+                        self.static_bodies.len()
+                    ),
+                ));
+            }
+            check_positive(field("orbit"), paddle.orbit)?;
+            check_finite(field("angle"), &[paddle.angle])?;
+            check_positive(field("length"), paddle.length)?;
+            check_positive(field("radius"), paddle.radius)?;
+            check_positive(field("speed"), paddle.speed)?;
+            if let Some(arc) = paddle.arc {
+                check_finite(field("arc"), &arc)?;
+                if arc[1] < arc[0] {
+                    return Err(invalid(field("arc"), "must not end before it starts"));
+                }
+                if !(arc[0]..=arc[1]).contains(&paddle.angle) {
+                    return Err(invalid(field("angle"), "must be within the arc"));
+                }
+            }
+            check_fraction(field("transfer"), paddle.transfer)?;
+            check_finite(field("color"), &paddle.color)?;
+            self.check_material(&field, &paddle.material)?;
+        }
+
+        for (index, population) in self.balls.iter().enumerate() {
+            let field = |name: &str| format!("balls[{}].{}", index, name);
+            let (around, radius, mass, color, material) = match population {
+                BallPopulation::Explicit {
+                    position,
+                    velocity,
+                    radius,
+                    mass,
+                    color,
+                    material,
+                    around,
+                } => {
+                    check_finite(field("position"), position)?;
+                    if let Some(velocity) = velocity {
+                        check_finite(field("velocity"), velocity)?;
+                    }
+                    (*around, *radius, *mass, color, material)
+                }
+                BallPopulation::Orbital {
+                    around,
+                    min_orbit,
+                    max_orbit,
+                    radius,
+                    mass,
+                    color,
+                    material,
+                    ..
+                } => {
+                    check_positive(field("min_orbit"), *min_orbit)?;
+                    check_positive(field("max_orbit"), *max_orbit)?;
+                    if max_orbit < min_orbit {
+                        return Err(invalid(field("max_orbit"), "must not be below min_orbit"));
+                    }
+                    (*around, *radius, *mass, color, material)
+                }
+                BallPopulation::Elements {
+                    around,
+                    orbits,
+                    radius,
+                    mass,
+                    color,
+                    material,
+                    ..
+                } => {
+                    self.check_orbits(&field, orbits, *around)?;
+                    (*around, *radius, *mass, color, material)
+                }
+            };
+
+            check_positive(field("radius"), radius)?;
+            self.check_mass(&field, mass, material, radius)?;
+            if let Some(color) = color {
+                check_finite(field("color"), color)?;
+            }
+
+            let needs_body = match population {
+                BallPopulation::Explicit { velocity, .. } => velocity.is_none(),
+                BallPopulation::Orbital { .. } | BallPopulation::Elements { .. } => true,
+            };
+            if needs_body && around >= self.static_bodies.len() {
+                return Err(invalid(
+                    field("around"),
+                    &format!(
+                        "refers to static body {} but only {} are defined",
+                        around,
+                        self.static_bodies.len()
+                    ),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Material called `name`, the default one when no name is given.
+    /// The name must exist, which `validate` checks.
+    pub fn material(&self, name: &Option<String>) -> Material {
+        match name {
+            Some(name) => self.materials[name],
+            None => Material::default(),
+        }
+    }
+
+    /// Mass given explicitly, or derived from the material density.
+    pub fn mass(&self, mass: Option<f32>, material: &Option<String>, radius: f32) -> f32 {
+        mass.or_else(|| self.material(material).mass(radius))
+            .unwrap_or(0.)
+    }
+
+    fn check_orbits(
+        &self,
+        field: &dyn Fn(&str) -> String,
+        orbits: &OrbitDistribution,
+        around: usize,
+    ) -> Result<(), ScenarioError> {
+        let field = |name: &str| field(&format!("orbits.{}", name));
+        match *orbits {
+            OrbitDistribution::Ranges {
+                semi_major_axis,
+                eccentricity,
+                argument_of_periapsis,
+                true_anomaly,
+                retrograde,
+            } => {
+                check_range(field("semi_major_axis"), semi_major_axis)?;
+                check_positive(field("semi_major_axis"), semi_major_axis[0])?;
+                check_range(field("eccentricity"), eccentricity)?;
+                if eccentricity[0] < 0. {
+                    return Err(invalid(field("eccentricity"), "must not be negative"));
+                }
+                if eccentricity[0] <= 1. && eccentricity[1] >= 1. {
+                    return Err(invalid(
+                        field("eccentricity"),
+                        "must be either below or above 1, parabolas are not supported",
+                    ));
+                }
+                check_range(field("argument_of_periapsis"), argument_of_periapsis)?;
+                check_range(field("true_anomaly"), true_anomaly)?;
+                check_fraction(field("retrograde"), retrograde)
+            }
+            OrbitDistribution::AsteroidBelt {
+                inner,
+                outer,
+                max_eccentricity,
+            } => {
+                check_positive(field("inner"), inner)?;
+                check_range(field("outer"), [inner, outer])?;
+                if !(0. ..1.).contains(&max_eccentricity) {
+                    return Err(invalid(
+                        field("max_eccentricity"),
+                        "must be at least 0 and below 1",
+                    ));
+                }
+                Ok(())
+            }
+            OrbitDistribution::Ring { inner, outer } => {
+                check_positive(field("inner"), inner)?;
+                check_range(field("outer"), [inner, outer])
+            }
+            OrbitDistribution::CometSwarm {
+                periapsis,
+                eccentricity,
+                distance,
+                retrograde,
+            } => {
+                check_range(field("periapsis"), periapsis)?;
+                check_positive(field("periapsis"), periapsis[0])?;
+                check_range(field("eccentricity"), eccentricity)?;
+                if eccentricity[0] <= 1. {
+                    return Err(invalid(field("eccentricity"), "must be above 1"));
+                }
+                check_range(field("distance"), distance)?;
+                if distance[0] < periapsis[1] {
+                    return Err(invalid(
+                        field("distance"),
+                        "must not be below the periapsis",
+                    ));
+                }
+                check_fraction(field("retrograde"), retrograde)
+            }
+            OrbitDistribution::Trojans {
+                planet,
+                spread,
+                width,
+                retrograde,
+            } => {
+                if planet >= self.static_bodies.len() || planet == around {
+                    return Err(invalid(
+                        field("planet"),
+                        &format!(
+                            "must be one of the {} static bodies other than `around`",
+                            self.static_bodies.len()
+                        ),
+                    ));
+                }
+                if !(spread.is_finite() && spread >= 0.) {
+                    return Err(invalid(
+                        field("spread"),
+                        "must be zero or a positive number",
+                    ));
+                }
+                if !(width.is_finite() && width >= 0.) {
+                    return Err(invalid(field("width"), "must be zero or a positive number"));
+                }
+                if retrograde.is_none() && !self.physics.dynamic_bodies {
+                    return Err(invalid(
+                        field("retrograde"),
+                        "must be given unless physics.dynamic_bodies is set, the planet \
+                         does not move otherwise",
+                    ));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn check_material(
+        &self,
+        field: &dyn Fn(&str) -> String,
+        material: &Option<String>,
+    ) -> Result<(), ScenarioError> {
+        match material {
+            Some(name) if !self.materials.contains_key(name) => Err(invalid(
+                field("material"),
+                &format!("refers to material `{}` which is not defined", name),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn check_mass(
+        &self,
+        field: &dyn Fn(&str) -> String,
+        mass: Option<f32>,
+        material: &Option<String>,
+        radius: f32,
+    ) -> Result<(), ScenarioError> {
+        self.check_material(field, material)?;
+        match mass {
+            Some(mass) => check_positive(field("mass"), mass),
+            None if self.material(material).density.is_some() => {
+                check_positive(field("mass"), self.mass(None, material, radius))
+            }
+            None => Err(invalid(
+                field("mass"),
+                "is required when the material has no density",
+            )),
+        }
+    }
+}
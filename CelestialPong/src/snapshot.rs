@@ -0,0 +1,452 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use ::rand::SeedableRng;
+use macroquad::prelude::*;
+use rand_chacha::ChaCha20Rng;
+
+use crate::arena::{Arena, Slot};
+use crate::ball::Ball;
+use crate::capsule::Capsule;
+use crate::material::Material;
+use crate::paddle::Paddle;
+use crate::quad_tree::Rect;
+use crate::scenario::Physics;
+use crate::static_body::{ContactPolicy, Landing, StaticBody};
+
+const MAGIC: &[u8; 4] = b"CPSN";
+pub const SNAPSHOT_VERSION: u32 = 2;
+
+/// Complete copy of the simulation state. Restoring it and stepping gives
+/// bit-identical results to the original run.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub physics: Physics,
+    pub tree_area: Rect,
+    // Stored slot by slot, so ball ids stay valid after a restore
+    pub balls: Arena<Ball>,
+    pub static_bodies: Vec<StaticBody>,
+    pub capsules: Vec<Capsule>,
+    pub paddles: Vec<Paddle>,
+    pub traces: Vec<Vec2>,
+    pub trace_index: usize,
+    pub frame_per_frame: usize,
+    pub step_count: u64,
+    pub rng: ChaCha20Rng,
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    NotASnapshot,
+    UnsupportedVersion(u32),
+    Corrupted(&'static str),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(err) => write!(f, "{}", err),
+            SnapshotError::NotASnapshot => write!(f, "not a snapshot file"),
+            SnapshotError::UnsupportedVersion(version) => write!(
+                f,
+                "snapshot version {} is not supported (expected {})",
+                version, SNAPSHOT_VERSION
+            ),
+            SnapshotError::Corrupted(what) => write!(f, "corrupted snapshot: invalid {}", what),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(err: io::Error) -> SnapshotError {
+        SnapshotError::Io(err)
+    }
+}
+
+// Everything is stored little endian, floats by their bit pattern so nothing is rounded.
+struct Writer<W: Write>(W);
+
+impl<W: Write> Writer<W> {
+    fn u32(&mut self, value: u32) -> io::Result<()> {
+        self.0.write_all(&value.to_le_bytes())
+    }
+
+    fn u64(&mut self, value: u64) -> io::Result<()> {
+        self.0.write_all(&value.to_le_bytes())
+    }
+
+    fn f32(&mut self, value: f32) -> io::Result<()> {
+        self.u32(value.to_bits())
+    }
+
+    fn vec2(&mut self, value: Vec2) -> io::Result<()> {
+        self.f32(value.x)?;
+        self.f32(value.y)
+    }
+
+    fn ball(&mut self, ball: &Ball) -> io::Result<()> {
+        self.vec2(ball.position)?;
+        self.vec2(ball.prev_position)?;
+        self.vec2(ball.velocity)?;
+        self.f32(ball.radius)?;
+        self.f32(ball.mass)?;
+        self.f32(ball.color.r)?;
+        self.f32(ball.color.g)?;
+        self.f32(ball.color.b)?;
+        self.f32(ball.color.a)?;
+        self.material(&ball.material)?;
+        match ball.landed_on {
+            Some(landing) => {
+                self.u32(1)?;
+                self.u64(landing.body as u64)?;
+                self.vec2(landing.offset)
+            }
+            None => self.u32(0),
+        }
+    }
+
+    fn capsule(&mut self, capsule: &Capsule) -> io::Result<()> {
+        self.vec2(capsule.p1)?;
+        self.vec2(capsule.p2)?;
+        self.f32(capsule.radius)?;
+        self.f32(capsule.color.r)?;
+        self.f32(capsule.color.g)?;
+        self.f32(capsule.color.b)?;
+        self.f32(capsule.color.a)?;
+        self.vec2(capsule.velocity)?;
+        self.material(&capsule.material)?;
+        self.f32(capsule.transfer)
+    }
+
+    fn paddle(&mut self, paddle: &Paddle) -> io::Result<()> {
+        self.u64(paddle.capsule as u64)?;
+        self.u64(paddle.around as u64)?;
+        self.f32(paddle.orbit)?;
+        self.f32(paddle.angle)?;
+        self.f32(paddle.length)?;
+        self.f32(paddle.speed)?;
+        match paddle.arc {
+            Some((min, max)) => {
+                self.u32(1)?;
+                self.f32(min)?;
+                self.f32(max)?;
+            }
+            None => self.u32(0)?,
+        }
+        self.f32(paddle.input)
+    }
+
+    fn contact(&mut self, contact: &ContactPolicy) -> io::Result<()> {
+        match *contact {
+            ContactPolicy::Absorb { accrete } => {
+                self.u32(0)?;
+                self.u32(accrete as u32)
+            }
+            ContactPolicy::Bounce { correction } => {
+                self.u32(1)?;
+                self.f32(correction)
+            }
+            ContactPolicy::Land => self.u32(2),
+            ContactPolicy::PassThrough => self.u32(3),
+        }
+    }
+
+    fn material(&mut self, material: &Material) -> io::Result<()> {
+        self.f32(material.restitution)?;
+        self.f32(material.friction)?;
+        self.u32(material.density.is_some() as u32)?;
+        self.f32(material.density.unwrap_or(0.))
+    }
+}
+
+struct Reader<R: Read>(R);
+
+impl<R: Read> Reader<R> {
+    fn bytes<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        let mut buffer = [0; N];
+        self.0.read_exact(&mut buffer)?;
+        Ok(buffer)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes()?))
+    }
+
+    fn len(&mut self, what: &'static str) -> Result<usize, SnapshotError> {
+        usize::try_from(self.u64()?).map_err(|_| SnapshotError::Corrupted(what))
+    }
+
+    fn f32(&mut self) -> io::Result<f32> {
+        Ok(f32::from_bits(self.u32()?))
+    }
+
+    fn vec2(&mut self) -> io::Result<Vec2> {
+        Ok(Vec2::new(self.f32()?, self.f32()?))
+    }
+
+    fn ball(&mut self) -> Result<Ball, SnapshotError> {
+        let position = self.vec2()?;
+        let prev_position = self.vec2()?;
+        let velocity = self.vec2()?;
+        let radius = self.f32()?;
+        let mass = self.f32()?;
+        let color = Color::new(self.f32()?, self.f32()?, self.f32()?, self.f32()?);
+
+        let mut ball = Ball::new(position, velocity, radius, mass, color);
+        ball.prev_position = prev_position;
+        ball.material = self.material()?;
+        ball.landed_on = match self.u32()? {
+            0 => None,
+            1 => Some(Landing {
+                body: self.len("landing")?,
+                offset: self.vec2()?,
+            }),
+            _ => return Err(SnapshotError::Corrupted("landing")),
+        };
+        Ok(ball)
+    }
+
+    fn capsule(&mut self) -> Result<Capsule, SnapshotError> {
+        let p1 = self.vec2()?;
+        let p2 = self.vec2()?;
+        let radius = self.f32()?;
+        let color = Color::new(self.f32()?, self.f32()?, self.f32()?, self.f32()?);
+
+        let mut capsule = Capsule::new(p1, p2, radius, color);
+        capsule.velocity = self.vec2()?;
+        capsule.material = self.material()?;
+        capsule.transfer = self.f32()?;
+        Ok(capsule)
+    }
+
+    fn paddle(&mut self) -> Result<Paddle, SnapshotError> {
+        Ok(Paddle {
+            capsule: self.len("paddle capsule")?,
+            around: self.len("paddle body")?,
+            orbit: self.f32()?,
+            angle: self.f32()?,
+            length: self.f32()?,
+            speed: self.f32()?,
+            arc: match self.u32()? {
+                0 => None,
+                1 => Some((self.f32()?, self.f32()?)),
+                _ => return Err(SnapshotError::Corrupted("paddle arc")),
+            },
+            input: self.f32()?,
+        })
+    }
+
+    fn contact(&mut self) -> Result<ContactPolicy, SnapshotError> {
+        Ok(match self.u32()? {
+            0 => ContactPolicy::Absorb {
+                accrete: self.u32()? != 0,
+            },
+            1 => ContactPolicy::Bounce {
+                correction: self.f32()?,
+            },
+            2 => ContactPolicy::Land,
+            3 => ContactPolicy::PassThrough,
+            _ => return Err(SnapshotError::Corrupted("contact policy")),
+        })
+    }
+
+    fn material(&mut self) -> io::Result<Material> {
+        let restitution = self.f32()?;
+        let friction = self.f32()?;
+        let has_density = self.u32()? != 0;
+        let density = self.f32()?;
+        Ok(Material {
+            restitution,
+            friction,
+            density: has_density.then_some(density),
+        })
+    }
+}
+
+impl Snapshot {
+    pub fn write_to<W: Write>(&self, out: W) -> io::Result<()> {
+        let mut out = Writer(out);
+        out.0.write_all(MAGIC)?;
+        out.u32(SNAPSHOT_VERSION)?;
+
+        // Physics settings are stored as toml, f32 survive the text round trip exactly
+        let physics = toml::to_string(&self.physics)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        out.u64(physics.len() as u64)?;
+        out.0.write_all(physics.as_bytes())?;
+        out.f32(self.tree_area.x)?;
+        out.f32(self.tree_area.y)?;
+        out.f32(self.tree_area.half_width)?;
+        out.f32(self.tree_area.half_height)?;
+        out.u64(self.frame_per_frame as u64)?;
+        out.u64(self.step_count)?;
+
+        out.0.write_all(&self.rng.get_seed())?;
+        out.u64(self.rng.get_stream())?;
+        out.0.write_all(&self.rng.get_word_pos().to_le_bytes())?;
+
+        out.u64(self.static_bodies.len() as u64)?;
+        for body in &self.static_bodies {
+            out.ball(&body.ball)?;
+            out.contact(&body.contact)?;
+        }
+
+        out.u64(self.capsules.len() as u64)?;
+        for capsule in &self.capsules {
+            out.capsule(capsule)?;
+        }
+        out.u64(self.paddles.len() as u64)?;
+        for paddle in &self.paddles {
+            out.paddle(paddle)?;
+        }
+
+        out.u64(self.balls.slot_count() as u64)?;
+        for slot in self.balls.slots() {
+            out.u32(slot.generation)?;
+            match &slot.value {
+                Some(ball) => {
+                    out.u32(1)?;
+                    out.ball(ball)?;
+                }
+                None => out.u32(0)?,
+            }
+        }
+        out.u64(self.balls.free_slots().len() as u64)?;
+        for &index in self.balls.free_slots() {
+            out.u32(index)?;
+        }
+
+        out.u64(self.traces.len() as u64)?;
+        out.u64(self.trace_index as u64)?;
+        for trace in &self.traces {
+            out.vec2(*trace)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read_from<R: Read>(input: R) -> Result<Snapshot, SnapshotError> {
+        let mut input = Reader(input);
+        if &input.bytes::<4>()? != MAGIC {
+            return Err(SnapshotError::NotASnapshot);
+        }
+
+        let version = input.u32()?;
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+
+        let length = input.len("physics")?;
+        if length > 1 << 16 {
+            return Err(SnapshotError::Corrupted("physics"));
+        }
+        let mut physics = vec![0; length];
+        input.0.read_exact(&mut physics)?;
+        let physics: Physics = std::str::from_utf8(&physics)
+            .ok()
+            .and_then(|text| toml::from_str(text).ok())
+            .ok_or(SnapshotError::Corrupted("physics"))?;
+
+        let x = input.f32()?;
+        let y = input.f32()?;
+        let half_width = input.f32()?;
+        let half_height = input.f32()?;
+        let tree_area = Rect::new(x, y, half_width * 2., half_height * 2.);
+        let frame_per_frame = input.len("simulation speed")?;
+        let step_count = input.u64()?;
+
+        let mut rng = ChaCha20Rng::from_seed(input.bytes()?);
+        rng.set_stream(input.u64()?);
+        rng.set_word_pos(u128::from_le_bytes(input.bytes()?));
+
+        let count = input.len("static body count")?;
+        let mut static_bodies = Vec::with_capacity(count.min(1024));
+        for _ in 0..count {
+            let ball = input.ball()?;
+            static_bodies.push(StaticBody::new(ball, input.contact()?));
+        }
+
+        let count = input.len("capsule count")?;
+        let mut capsules = Vec::with_capacity(count.min(1024));
+        for _ in 0..count {
+            capsules.push(input.capsule()?);
+        }
+        let count = input.len("paddle count")?;
+        let mut paddles = Vec::with_capacity(count.min(1024));
+        for _ in 0..count {
+            let paddle = input.paddle()?;
+            if paddle.capsule >= capsules.len() || paddle.around >= static_bodies.len() {
+                return Err(SnapshotError::Corrupted("paddle"));
+            }
+            paddles.push(paddle);
+        }
+
+        let count = input.len("ball count")?;
+        let mut slots = Vec::with_capacity(count.min(1 << 16));
+        for _ in 0..count {
+            let generation = input.u32()?;
+            let value = match input.u32()? {
+                0 => None,
+                1 => Some(input.ball()?),
+                _ => return Err(SnapshotError::Corrupted("ball slot")),
+            };
+            slots.push(Slot { generation, value });
+        }
+        let count = input.len("free slot count")?;
+        let mut free = Vec::with_capacity(count.min(1 << 16));
+        for _ in 0..count {
+            free.push(input.u32()?);
+        }
+        let balls = Arena::from_slots(slots, free).ok_or(SnapshotError::Corrupted("free slots"))?;
+        let landed_on_missing_body = balls
+            .values()
+            .filter_map(|ball| ball.landed_on)
+            .any(|landing| landing.body >= static_bodies.len());
+        if landed_on_missing_body {
+            return Err(SnapshotError::Corrupted("landing"));
+        }
+
+        let count = input.len("trace count")?;
+        let trace_index = input.len("trace index")?;
+        if count > 0 && trace_index >= count {
+            return Err(SnapshotError::Corrupted("trace index"));
+        }
+        let mut traces = Vec::with_capacity(count.min(1 << 16));
+        for _ in 0..count {
+            traces.push(input.vec2()?);
+        }
+
+        Ok(Snapshot {
+            physics,
+            tree_area,
+            balls,
+            static_bodies,
+            capsules,
+            paddles,
+            traces,
+            trace_index,
+            frame_per_frame,
+            step_count,
+            rng,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut file = io::BufWriter::new(std::fs::File::create(path)?);
+        self.write_to(&mut file)?;
+        file.flush()
+    }
+
+    pub fn load(path: &Path) -> Result<Snapshot, SnapshotError> {
+        let file = std::fs::File::open(path)?;
+        Snapshot::read_from(io::BufReader::new(file))
+    }
+}
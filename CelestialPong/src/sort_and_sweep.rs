@@ -0,0 +1,129 @@
+use std::collections::HashMap;
+
+use crate::broad_phase::{ordered, BroadPhase};
+use crate::quad_tree::{Collider, QuadTreeEntry, Rect};
+
+/// Entries sorted by their left edge. Queries and pairs only look at the entries whose
+/// left edge is close enough along x, then check y.
+/// Entries barely move between steps, so the insertion sort done by `refresh` is
+/// almost linear and does not allocate.
+#[derive(Clone, Debug, Default)]
+pub struct SortAndSweep {
+    entries: Vec<QuadTreeEntry>,
+    // Position of each entry in `entries`
+    indices: HashMap<Collider, usize>,
+    // Widest entry, every entry overlapping a query starts less than this before it
+    max_width: f32,
+    dirty: bool,
+}
+
+impl SortAndSweep {
+    pub fn new() -> SortAndSweep {
+        SortAndSweep::default()
+    }
+
+    // Entries whose left edge is between `left` and `right`
+    fn starting_between(&self, left: f32, right: f32) -> &[QuadTreeEntry] {
+        debug_assert!(!self.dirty, "refresh was not called after changes");
+        let first = self
+            .entries
+            .partition_point(|entry| entry.bounds.left < left);
+        let last = self
+            .entries
+            .partition_point(|entry| entry.bounds.left <= right);
+        &self.entries[first..last.max(first)]
+    }
+}
+
+impl BroadPhase for SortAndSweep {
+    fn name(&self) -> &'static str {
+        "Sort and sweep"
+    }
+
+    fn area(&self) -> Option<&Rect> {
+        None
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.indices.clear();
+        self.max_width = 0.;
+        self.dirty = false;
+    }
+
+    fn update(&mut self, payload: Collider, bounds: Rect) -> bool {
+        match self.indices.get(&payload) {
+            Some(&index) => self.entries[index].bounds = bounds,
+            None => {
+                self.indices.insert(payload, self.entries.len());
+                self.entries.push(QuadTreeEntry::new(bounds, payload));
+            }
+        }
+        self.dirty = true;
+        true
+    }
+
+    fn remove(&mut self, payload: Collider) -> bool {
+        let Some(index) = self.indices.remove(&payload) else {
+            return false;
+        };
+        self.entries.swap_remove(index);
+        if let Some(moved) = self.entries.get(index) {
+            self.indices.insert(moved.payload, index);
+        }
+        self.dirty = true;
+        true
+    }
+
+    fn refresh(&mut self) {
+        if !self.dirty {
+            return;
+        }
+
+        // NaN edges go last so they never break the order of the others
+        let key = |entry: &QuadTreeEntry| match entry.bounds.left.is_nan() {
+            true => f32::INFINITY,
+            false => entry.bounds.left,
+        };
+        for index in 1..self.entries.len() {
+            let mut position = index;
+            while position > 0 && key(&self.entries[position - 1]) > key(&self.entries[position]) {
+                self.entries.swap(position - 1, position);
+                position -= 1;
+            }
+        }
+
+        self.max_width = 0.;
+        for (index, entry) in self.entries.iter().enumerate() {
+            self.indices.insert(entry.payload, index);
+            self.max_width = self.max_width.max(entry.bounds.right - entry.bounds.left);
+        }
+        self.dirty = false;
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn query_entries(&self, query: &Rect, result: &mut Vec<QuadTreeEntry>) {
+        for entry in self.starting_between(query.left - self.max_width, query.right) {
+            if entry.bounds.overlap(query) {
+                result.push(*entry);
+            }
+        }
+    }
+
+    fn overlapping_pairs(&self, result: &mut Vec<(Collider, Collider)>) {
+        debug_assert!(!self.dirty, "refresh was not called after changes");
+        for (index, a) in self.entries.iter().enumerate() {
+            for b in &self.entries[index + 1..] {
+                if b.bounds.left > a.bounds.right {
+                    break;
+                }
+                if a.bounds.overlap(&b.bounds) {
+                    result.push(ordered(a.payload, b.payload));
+                }
+            }
+        }
+    }
+}
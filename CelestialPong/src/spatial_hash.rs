@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+
+use crate::broad_phase::{ordered, BroadPhase};
+use crate::quad_tree::{Collider, QuadTreeEntry, Rect};
+
+// Entries covering more cells are kept aside and tested against every query
+const MAX_CELLS_PER_ENTRY: i64 = 64;
+
+type Cell = (i32, i32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct CellRange {
+    min: Cell,
+    max: Cell,
+}
+
+impl CellRange {
+    fn count(&self) -> i64 {
+        (self.max.0 as i64 - self.min.0 as i64 + 1) * (self.max.1 as i64 - self.min.1 as i64 + 1)
+    }
+
+    fn cells(self) -> impl Iterator<Item = Cell> {
+        (self.min.1..=self.max.1).flat_map(move |y| (self.min.0..=self.max.0).map(move |x| (x, y)))
+    }
+}
+
+/// Uniform grid of square cells, only the cells holding entries are stored.
+/// Each entry is listed in every cell it overlaps. Cells are kept once emptied, so once
+/// the grid has covered the playing field keeping it up to date does not allocate.
+#[derive(Clone, Debug)]
+pub struct SpatialHashGrid {
+    cell_size: f32,
+    cells: HashMap<Cell, Vec<QuadTreeEntry>>,
+    // Entries covering too many cells
+    large: Vec<QuadTreeEntry>,
+    bounds: HashMap<Collider, Rect>,
+}
+
+impl SpatialHashGrid {
+    pub fn new(cell_size: f32) -> SpatialHashGrid {
+        SpatialHashGrid {
+            cell_size,
+            cells: HashMap::new(),
+            large: Vec::new(),
+            bounds: HashMap::new(),
+        }
+    }
+
+    pub fn cell_size(&self) -> f32 {
+        self.cell_size
+    }
+
+    // Float to int casts saturate, NaN goes to cell 0
+    fn cell(&self, x: f32, y: f32) -> Cell {
+        (
+            (x / self.cell_size).floor() as i32,
+            (y / self.cell_size).floor() as i32,
+        )
+    }
+
+    fn range(&self, bounds: &Rect) -> CellRange {
+        CellRange {
+            min: self.cell(bounds.left, bounds.up),
+            max: self.cell(bounds.right, bounds.down),
+        }
+    }
+
+    fn insert(&mut self, entry: QuadTreeEntry) {
+        let range = self.range(&entry.bounds);
+        if range.count() > MAX_CELLS_PER_ENTRY {
+            self.large.push(entry);
+            return;
+        }
+        for cell in range.cells() {
+            self.cells.entry(cell).or_default().push(entry);
+        }
+    }
+
+    fn take_out(&mut self, payload: Collider, bounds: &Rect) {
+        let range = self.range(bounds);
+        if range.count() > MAX_CELLS_PER_ENTRY {
+            remove_from(&mut self.large, payload);
+            return;
+        }
+        for cell in range.cells() {
+            if let Some(list) = self.cells.get_mut(&cell) {
+                remove_from(list, payload);
+            }
+        }
+    }
+}
+
+fn remove_from(list: &mut Vec<QuadTreeEntry>, payload: Collider) {
+    if let Some(position) = list.iter().position(|entry| entry.payload == payload) {
+        list.swap_remove(position);
+    }
+}
+
+// Entries overlapping several cells are seen in each of them, a pair or a query result
+// is only kept in the first cell shared by both sides
+fn first_shared_cell(a: Cell, b: Cell) -> Cell {
+    (a.0.max(b.0), a.1.max(b.1))
+}
+
+impl BroadPhase for SpatialHashGrid {
+    fn name(&self) -> &'static str {
+        "Spatial hash grid"
+    }
+
+    fn area(&self) -> Option<&Rect> {
+        None
+    }
+
+    fn clear(&mut self) {
+        for list in self.cells.values_mut() {
+            list.clear();
+        }
+        self.large.clear();
+        self.bounds.clear();
+    }
+
+    fn update(&mut self, payload: Collider, bounds: Rect) -> bool {
+        match self.bounds.insert(payload, bounds) {
+            Some(old) if self.range(&old) == self.range(&bounds) => {
+                // Still in the same cells, only the bounds change
+                let range = self.range(&bounds);
+                if range.count() > MAX_CELLS_PER_ENTRY {
+                    update_in(&mut self.large, payload, bounds);
+                } else {
+                    for cell in range.cells() {
+                        if let Some(list) = self.cells.get_mut(&cell) {
+                            update_in(list, payload, bounds);
+                        }
+                    }
+                }
+            }
+            Some(old) => {
+                self.take_out(payload, &old);
+                self.insert(QuadTreeEntry::new(bounds, payload));
+            }
+            None => self.insert(QuadTreeEntry::new(bounds, payload)),
+        }
+        true
+    }
+
+    fn remove(&mut self, payload: Collider) -> bool {
+        let Some(bounds) = self.bounds.remove(&payload) else {
+            return false;
+        };
+        self.take_out(payload, &bounds);
+        true
+    }
+
+    fn len(&self) -> usize {
+        self.bounds.len()
+    }
+
+    fn query_entries(&self, query: &Rect, result: &mut Vec<QuadTreeEntry>) {
+        for entry in &self.large {
+            if entry.bounds.overlap(query) {
+                result.push(*entry);
+            }
+        }
+
+        let query_range = self.range(query);
+        let mut visit = |cell: Cell, list: &Vec<QuadTreeEntry>| {
+            for entry in list {
+                let range = self.range(&entry.bounds);
+                if entry.bounds.overlap(query)
+                    && first_shared_cell(range.min, query_range.min) == cell
+                {
+                    result.push(*entry);
+                }
+            }
+        };
+
+        // Large queries go through the stored cells rather than the covered ones
+        if query_range.count() > self.cells.len() as i64 {
+            for (cell, list) in &self.cells {
+                let inside = (query_range.min.0..=query_range.max.0).contains(&cell.0)
+                    && (query_range.min.1..=query_range.max.1).contains(&cell.1);
+                if inside {
+                    visit(*cell, list);
+                }
+            }
+        } else {
+            for cell in query_range.cells() {
+                if let Some(list) = self.cells.get(&cell) {
+                    visit(cell, list);
+                }
+            }
+        }
+    }
+
+    fn overlapping_pairs(&self, result: &mut Vec<(Collider, Collider)>) {
+        for (cell, list) in &self.cells {
+            for (index, a) in list.iter().enumerate() {
+                for b in &list[index + 1..] {
+                    let shared =
+                        first_shared_cell(self.range(&a.bounds).min, self.range(&b.bounds).min);
+                    if shared == *cell && a.bounds.overlap(&b.bounds) {
+                        result.push(ordered(a.payload, b.payload));
+                    }
+                }
+            }
+        }
+
+        for (index, a) in self.large.iter().enumerate() {
+            for b in &self.large[index + 1..] {
+                if a.bounds.overlap(&b.bounds) {
+                    result.push(ordered(a.payload, b.payload));
+                }
+            }
+            for (&payload, bounds) in &self.bounds {
+                let small = self.range(bounds).count() <= MAX_CELLS_PER_ENTRY;
+                if small && a.bounds.overlap(bounds) {
+                    result.push(ordered(a.payload, payload));
+                }
+            }
+        }
+    }
+}
+
+fn update_in(list: &mut [QuadTreeEntry], payload: Collider, bounds: Rect) {
+    if let Some(entry) = list.iter_mut().find(|entry| entry.payload == payload) {
+        entry.bounds = bounds;
+    }
+}
@@ -0,0 +1,69 @@
+use macroquad::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::ball::Ball;
+
+/// What happens to a ball touching a static body.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ContactPolicy {
+    /// The ball is removed. With `accrete` its mass is added to the body, and its momentum
+    /// too when bodies are dynamic.
+    Absorb {
+        #[serde(default)]
+        accrete: bool,
+    },
+    /// The ball bounces with the restitution and friction of both materials, dynamic
+    /// bodies are pushed back by the ball. `correction`
+    /// is the fraction of the overlap removed by pushing the ball out, 1 puts it back on
+    /// the surface.
+    Bounce {
+        #[serde(default = "default_correction")]
+        correction: f32,
+    },
+    /// The ball stops on the surface and moves with the body from then on.
+    Land,
+    /// The ball goes through, only an event is emitted.
+    PassThrough,
+}
+
+fn default_correction() -> f32 {
+    1.
+}
+
+impl Default for ContactPolicy {
+    fn default() -> ContactPolicy {
+        ContactPolicy::Absorb { accrete: false }
+    }
+}
+
+/// Where a landed ball sits on a static body.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Landing {
+    pub body: usize,
+    // From the body center to the ball center
+    pub offset: Vec2,
+}
+
+/// A massive body balls orbit around. It only moves when `physics.dynamic_bodies` is set.
+#[derive(Clone, Copy, Debug)]
+pub struct StaticBody {
+    pub ball: Ball,
+    pub contact: ContactPolicy,
+}
+
+impl StaticBody {
+    pub fn new(ball: Ball, contact: ContactPolicy) -> StaticBody {
+        StaticBody { ball, contact }
+    }
+
+    /// Adds the mass of `ball` to the body, and its momentum when `moving`.
+    pub fn accrete(&mut self, ball: &Ball, moving: bool, dt: f32) {
+        let mass = self.ball.mass + ball.mass;
+        if moving {
+            let momentum = self.ball.velocity * self.ball.mass + ball.velocity * ball.mass;
+            self.ball.set_velocity(momentum / mass, dt);
+        }
+        self.ball.mass = mass;
+    }
+}
@@ -0,0 +1,919 @@
+// based on https://github.com/Markek1/Collision-Simulator
+
+use ::rand::{Rng, SeedableRng};
+use macroquad::prelude::*;
+use rand_chacha::ChaCha20Rng;
+
+use crate::arena::Arena;
+use crate::ball::{Ball, BallId};
+use crate::barnes_hut::BarnesHutTree;
+use crate::boundary::{grown_area, BoundaryPolicy};
+use crate::broad_phase::BroadPhase;
+use crate::capsule::Capsule;
+use crate::diagnostics::{Diagnostics, DiagnosticsLog};
+use crate::gravity::{get_orbital_velocity, Attractor, ForceError, GravityField, GravitySolver};
+use crate::orbit::{dominant_body, OrbitalElements};
+use crate::paddle::Paddle;
+use crate::quad_tree::{Collider, QuadTreeEntry, Rect};
+use crate::scenario::{BallPopulation, Physics, Scenario};
+use crate::snapshot::Snapshot;
+use crate::static_body::{ContactPolicy, Landing, StaticBody};
+
+pub const TRACE_SIZE: usize = 1000;
+
+pub fn random_orbital_pos(
+    center: Vec2,
+    min_radius: f32,
+    max_radius: f32,
+    rng: &mut ChaCha20Rng,
+) -> Vec2 {
+    let angle = rng.gen::<f32>() * std::f32::consts::PI * 2.;
+    let result = Vec2::from((angle.cos(), angle.sin()));
+    let rad = rng.gen::<f32>() * (max_radius - min_radius) + min_radius;
+    center + result * rad
+}
+
+fn random_color(rng: &mut ChaCha20Rng) -> Color {
+    Color {
+        r: rng.gen::<f32>() + 0.25,
+        g: rng.gen::<f32>() + 0.25,
+        b: rng.gen::<f32>() + 0.25,
+        a: 1.,
+    }
+}
+
+/// Something that happened during a step, for the game and the display to react to.
+/// An absorbed ball is already removed when its event is drained.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WorldEvent {
+    /// The ball was removed at the end of the step, `position` is where it touched the body
+    Absorbed {
+        ball: BallId,
+        body: usize,
+        position: Vec2,
+    },
+    /// `speed` is the speed at which the ball was approaching the surface
+    Bounced {
+        ball: BallId,
+        body: usize,
+        speed: f32,
+    },
+    Landed {
+        ball: BallId,
+        body: usize,
+    },
+    /// `speed` is the speed at which the ball was approaching the capsule
+    BouncedOffCapsule {
+        ball: BallId,
+        capsule: usize,
+        speed: f32,
+    },
+    /// The ball started overlapping a body it goes through
+    PassedThrough {
+        ball: BallId,
+        body: usize,
+    },
+    /// The ball left the playing field at `position`, it is removed at the end of the step
+    /// unless the world is unbounded
+    Escaped {
+        ball: BallId,
+        position: Vec2,
+    },
+}
+
+/// Headless simulation state: everything needed to step the game without a window.
+pub struct World {
+    pub scenario: Scenario,
+    pub physics: Physics,
+    pub balls: Arena<Ball>,
+    pub static_bodies: Vec<StaticBody>,
+    // Walls and paddles, moved by their velocity only
+    pub capsules: Vec<Capsule>,
+    // Player paddles, driving the capsules after the ones of the scenario
+    pub paddles: Vec<Paddle>,
+    // Balls, static bodies and capsules, kept up to date at the end of every step
+    pub broad_phase: Box<dyn BroadPhase>,
+    // Playing field, the quad tree may cover more when the world is unbounded
+    pub tree_area: Rect,
+    pub rng: ChaCha20Rng,
+
+    // Ball held by the player, it ignores gravity while held
+    pub selected_ball: Option<BallId>,
+
+    // Number of steps since the last reset
+    pub step_count: u64,
+    // When set, diagnostics are recorded at the end of every step
+    pub diagnostics_log: Option<DiagnosticsLog>,
+
+    // Barnes-Hut error measured on the last step, when `physics.report_force_error` is set
+    pub force_error: Option<ForceError>,
+
+    pub traces: Vec<Vec2>,
+    pub trace_index: usize,
+
+    // Number of sub-steps run for each rendered frame
+    pub frame_per_frame: usize,
+
+    // Events since the last `drain_events`
+    events: Vec<WorldEvent>,
+
+    collided_balls: Vec<BallId>,
+    // Balls to remove at the end of the step, so ids stay valid during it
+    despawned_balls: Vec<BallId>,
+    // Positions at the start of the step, for continuous collision detection,
+    // balls are indexed by their id index
+    ball_starts: Vec<Vec2>,
+    body_starts: Vec<Vec2>,
+    // Ball collisions found this step: fraction of the step, and both balls
+    impacts: Vec<(f32, BallId, BallId)>,
+    near_entries: Vec<QuadTreeEntry>,
+    // Static bodies first, then balls when n-body gravity is enabled
+    attractors: Vec<Attractor>,
+    barnes_hut: BarnesHutTree,
+}
+
+impl World {
+    pub fn new(scenario: Scenario) -> World {
+        let tree_area = Rect::new(0., 0., scenario.field_size[0], scenario.field_size[1]);
+        let mut world = World {
+            physics: scenario.physics,
+            balls: Arena::new(),
+            static_bodies: Vec::new(),
+            capsules: Vec::new(),
+            paddles: Vec::new(),
+            broad_phase: scenario
+                .physics
+                .broad_phase
+                .build(tree_area, scenario.physics.quad_tree),
+            tree_area,
+            rng: ChaCha20Rng::seed_from_u64(scenario.seed),
+            selected_ball: None,
+            traces: vec![Vec2::ZERO; TRACE_SIZE],
+            trace_index: 0,
+            frame_per_frame: 1,
+            events: Vec::new(),
+            collided_balls: Vec::new(),
+            despawned_balls: Vec::new(),
+            ball_starts: Vec::new(),
+            body_starts: Vec::new(),
+            impacts: Vec::new(),
+            near_entries: Vec::new(),
+            attractors: Vec::new(),
+            barnes_hut: BarnesHutTree::new(),
+            force_error: None,
+            step_count: 0,
+            diagnostics_log: None,
+            scenario,
+        };
+
+        world.reset();
+        world
+    }
+
+    /// Rebuilds bodies and balls from the scenario, reseeding the rng.
+    pub fn reset(&mut self) {
+        self.selected_ball = None;
+        self.physics = self.scenario.physics;
+        self.rng = ChaCha20Rng::seed_from_u64(self.scenario.seed);
+        self.traces = vec![Vec2::ZERO; TRACE_SIZE];
+        self.trace_index = 0;
+        self.force_error = None;
+        self.step_count = 0;
+        self.events.clear();
+        self.despawned_balls.clear();
+
+        self.static_bodies.clear();
+        for desc in &self.scenario.static_bodies {
+            let mut body = Ball::new(
+                Vec2::from(desc.position),
+                Vec2::ZERO,
+                desc.radius,
+                self.scenario.mass(desc.mass, &desc.material, desc.radius),
+                Color::from(desc.color),
+            );
+            body.material = self.scenario.material(&desc.material);
+            if self.physics.dynamic_bodies {
+                body.set_velocity(Vec2::from(desc.velocity), self.physics.dt);
+            }
+            self.static_bodies.push(StaticBody::new(body, desc.contact));
+        }
+
+        self.capsules.clear();
+        for desc in &self.scenario.capsules {
+            let mut capsule = Capsule::new(
+                Vec2::from(desc.p1),
+                Vec2::from(desc.p2),
+                desc.radius,
+                Color::from(desc.color),
+            );
+            capsule.velocity = Vec2::from(desc.velocity);
+            capsule.material = self.scenario.material(&desc.material);
+            capsule.transfer = desc.transfer;
+            self.capsules.push(capsule);
+        }
+
+        self.paddles.clear();
+        for desc in &self.scenario.paddles {
+            let paddle = Paddle {
+                capsule: self.capsules.len(),
+                around: desc.around,
+                orbit: desc.orbit,
+                angle: desc.angle.to_radians(),
+                length: desc.length,
+                speed: desc.speed,
+                arc: desc
+                    .arc
+                    .map(|[min, max]| (min.to_radians(), max.to_radians())),
+                input: 0.,
+            };
+            let mut capsule =
+                Capsule::new(Vec2::ZERO, Vec2::ZERO, desc.radius, Color::from(desc.color));
+            capsule.material = self.scenario.material(&desc.material);
+            capsule.transfer = desc.transfer;
+            paddle.place(&mut capsule, self.static_bodies[desc.around].ball.position);
+            self.capsules.push(capsule);
+            self.paddles.push(paddle);
+        }
+
+        self.respawn_balls();
+    }
+
+    /// Replaces the balls by new ones from the scenario populations, drawn with the rng
+    /// where it is. Bodies and capsules stay as they are.
+    pub fn respawn_balls(&mut self) {
+        self.selected_ball = None;
+        self.despawned_balls.clear();
+        // Cleared in place so ids of the previous balls never point to new ones
+        self.balls.clear();
+        for population in &self.scenario.balls {
+            match population {
+                BallPopulation::Explicit {
+                    position,
+                    velocity,
+                    radius,
+                    mass,
+                    color,
+                    material,
+                    around,
+                } => {
+                    let color = match color {
+                        Some(color) => Color::from(*color),
+                        None => random_color(&mut self.rng),
+                    };
+                    let mut ball = Ball::new(
+                        Vec2::from(*position),
+                        Vec2::ZERO,
+                        *radius,
+                        self.scenario.mass(*mass, material, *radius),
+                        color,
+                    );
+                    ball.material = self.scenario.material(material);
+
+                    let ball_speed = match velocity {
+                        Some(velocity) => Vec2::from(*velocity),
+                        None => get_orbital_velocity(
+                            &ball,
+                            &self.static_bodies[*around].ball,
+                            self.physics.gravity,
+                        ),
+                    };
+                    ball.set_velocity(ball_speed, self.physics.dt);
+                    self.balls.insert(ball);
+                }
+                BallPopulation::Orbital {
+                    count,
+                    around,
+                    min_orbit,
+                    max_orbit,
+                    radius,
+                    mass,
+                    color,
+                    material,
+                } => {
+                    let mass = self.scenario.mass(*mass, material, *radius);
+                    for _ in 0..*count {
+                        let position = random_orbital_pos(
+                            self.static_bodies[*around].ball.position,
+                            *min_orbit,
+                            *max_orbit,
+                            &mut self.rng,
+                        );
+
+                        let color = match color {
+                            Some(color) => Color::from(*color),
+                            None => random_color(&mut self.rng),
+                        };
+                        let mut ball = Ball::new(position, Vec2::ZERO, *radius, mass, color);
+                        ball.material = self.scenario.material(material);
+
+                        let ball_speed = get_orbital_velocity(
+                            &ball,
+                            &self.static_bodies[*around].ball,
+                            self.physics.gravity,
+                        );
+                        ball.set_velocity(ball_speed, self.physics.dt);
+                        self.balls.insert(ball);
+                    }
+                }
+                BallPopulation::Elements {
+                    count,
+                    around,
+                    orbits,
+                    radius,
+                    mass,
+                    color,
+                    material,
+                } => {
+                    let mass = self.scenario.mass(*mass, material, *radius);
+                    for _ in 0..*count {
+                        let (position, velocity) = orbits.sample(
+                            &self.static_bodies,
+                            *around,
+                            self.physics.gravity,
+                            &mut self.rng,
+                        );
+
+                        let color = match color {
+                            Some(color) => Color::from(*color),
+                            None => random_color(&mut self.rng),
+                        };
+                        let mut ball = Ball::new(position, Vec2::ZERO, *radius, mass, color);
+                        ball.material = self.scenario.material(material);
+                        ball.set_velocity(velocity, self.physics.dt);
+                        self.balls.insert(ball);
+                    }
+                }
+            }
+        }
+
+        self.rebuild_broad_phase(self.tree_area);
+        self.sync_broad_phase();
+    }
+
+    /// Replaces the scenario and resets the world from it.
+    pub fn load_scenario(&mut self, scenario: Scenario) {
+        self.tree_area = Rect::new(0., 0., scenario.field_size[0], scenario.field_size[1]);
+        self.scenario = scenario;
+        self.reset();
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            physics: self.physics,
+            tree_area: self.tree_area,
+            balls: self.balls.clone(),
+            static_bodies: self.static_bodies.clone(),
+            capsules: self.capsules.clone(),
+            paddles: self.paddles.clone(),
+            traces: self.traces.clone(),
+            trace_index: self.trace_index,
+            frame_per_frame: self.frame_per_frame,
+            step_count: self.step_count,
+            rng: self.rng.clone(),
+        }
+    }
+
+    /// Puts the world back in the state captured by `snapshot`, the scenario is kept for resets.
+    pub fn restore(&mut self, snapshot: &Snapshot) {
+        self.physics = snapshot.physics;
+        self.tree_area = snapshot.tree_area;
+        self.balls.clone_from(&snapshot.balls);
+        self.static_bodies.clone_from(&snapshot.static_bodies);
+        self.capsules.clone_from(&snapshot.capsules);
+        self.paddles.clone_from(&snapshot.paddles);
+        self.traces.clone_from(&snapshot.traces);
+        self.trace_index = snapshot.trace_index;
+        self.frame_per_frame = snapshot.frame_per_frame;
+        self.rng = snapshot.rng.clone();
+        self.selected_ball = None;
+        self.force_error = None;
+        self.step_count = snapshot.step_count;
+        self.events.clear();
+        self.despawned_balls.clear();
+        self.rebuild_broad_phase(self.tree_area);
+        self.sync_broad_phase();
+    }
+
+    fn rebuild_broad_phase(&mut self, area: Rect) {
+        self.broad_phase = self.physics.broad_phase.build(area, self.physics.quad_tree);
+    }
+
+    /// Brings the broad phase up to date with the balls and static bodies. Done at the end
+    /// of every step, only needed after moving them by hand.
+    pub fn sync_broad_phase(&mut self) {
+        if self.physics.boundary == BoundaryPolicy::Unbounded {
+            let escaped = self.broad_phase.area().is_some_and(|area| {
+                self.balls
+                    .values()
+                    .any(|ball| !area.contains(ball.position))
+            });
+            if escaped {
+                let positions = self.balls.values().map(|ball| ball.position);
+                self.rebuild_broad_phase(grown_area(&self.tree_area, positions));
+            }
+        }
+
+        for (index, body) in self.static_bodies.iter().enumerate() {
+            self.broad_phase
+                .update(Collider::Body(index), body.ball.get_collision_area());
+        }
+        for (index, capsule) in self.capsules.iter().enumerate() {
+            self.broad_phase
+                .update(Collider::Capsule(index), capsule.bounds());
+        }
+        for (id, ball) in self.balls.iter() {
+            self.broad_phase
+                .update(Collider::Ball(id), ball.get_collision_area());
+        }
+        self.broad_phase.refresh();
+    }
+
+    /// Advances the simulation by a single sub-step.
+    pub fn step(&mut self, dt: f32) {
+        // Every body is attracted by the positions at the start of the step, so pairwise
+        // forces stay opposite and momentum is conserved by the integrators evaluating
+        // the field once per step. Multi-stage integrators see the other bodies frozen.
+        self.attractors.clear();
+        self.attractors.extend(
+            self.static_bodies
+                .iter()
+                .map(|body| Attractor::new(&body.ball)),
+        );
+        if self.physics.n_body {
+            self.attractors
+                .extend(self.balls.values().map(Attractor::new));
+        }
+
+        if self.physics.solver == GravitySolver::BarnesHut || self.physics.report_force_error {
+            self.barnes_hut.build(&self.attractors);
+        }
+
+        let field = GravityField {
+            attractors: &self.attractors,
+            tree: &self.barnes_hut,
+            solver: self.physics.solver,
+            theta: self.physics.theta,
+            gravity: self.physics.gravity,
+            softening: self.physics.softening,
+        };
+        // Measured on the balls, whose accelerations are the ones approximated
+        let ball_offset = self.static_bodies.len();
+        let n_body = self.physics.n_body;
+        self.force_error =
+            self.physics.report_force_error.then(|| {
+                field.force_error(self.balls.values().enumerate().map(|(index, ball)| {
+                    (ball.position, Some(ball_offset + index).filter(|_| n_body))
+                }))
+            });
+
+        self.ball_starts.clear();
+        self.ball_starts.resize(self.balls.slot_count(), Vec2::ZERO);
+        for (id, ball) in self.balls.iter() {
+            self.ball_starts[id.index()] = ball.position;
+        }
+        self.body_starts.clear();
+        self.body_starts
+            .extend(self.static_bodies.iter().map(|body| body.ball.position));
+
+        let integrator = self.physics.integrator.integrator();
+        if self.physics.dynamic_bodies {
+            for (index, body) in self.static_bodies.iter_mut().enumerate() {
+                let acceleration = |position: Vec2| field.acceleration(position, Some(index));
+                integrator.integrate(&mut body.ball, dt, &acceleration);
+            }
+        }
+
+        for paddle in self.paddles.iter_mut() {
+            let center = self.static_bodies[paddle.around].ball.position;
+            paddle.steer(&mut self.capsules[paddle.capsule], center, dt);
+        }
+        for capsule in self.capsules.iter_mut() {
+            capsule.translate(capsule.velocity * dt);
+        }
+
+        // Updating ball position
+        self.collided_balls.clear();
+        for (index, (id, ball)) in self.balls.iter_mut().enumerate() {
+            let held = self.selected_ball == Some(id);
+            if held {
+                ball.landed_on = None;
+            }
+
+            let skip = Some(ball_offset + index).filter(|_| self.physics.n_body);
+            let acceleration = |position: Vec2| {
+                if held {
+                    return Vec2::ZERO;
+                }
+                field.acceleration(position, skip)
+            };
+
+            match ball.landed_on {
+                Some(landing) => {
+                    let body = &self.static_bodies[landing.body].ball;
+                    ball.position = body.position + landing.offset;
+                    ball.set_velocity(body.velocity, dt);
+                }
+                None => integrator.integrate(ball, dt, &acceleration),
+            }
+
+            // Recode previous positions
+            if !self.traces.is_empty() {
+                self.traces[self.trace_index] = ball.position;
+                self.trace_index = (self.trace_index + 1) % self.traces.len();
+            }
+        }
+
+        // The broad phase holds where things were at the end of the last step, anything a ball
+        // may hit this step is within its swept area grown by how far things moved since
+        let continuous = self.physics.continuous_collisions;
+        let margin = self
+            .balls
+            .iter()
+            .map(|(id, ball)| ball.position.distance(self.ball_starts[id.index()]))
+            .chain(
+                self.static_bodies
+                    .iter()
+                    .zip(&self.body_starts)
+                    .map(|(body, start)| body.ball.position.distance(*start)),
+            )
+            .chain(
+                self.capsules
+                    .iter()
+                    .map(|capsule| capsule.velocity.length() * dt),
+            )
+            .fold(0., f32::max);
+
+        // Colliding balls, resolved in the order they happen during the step
+        self.impacts.clear();
+        for (id, ball) in self.balls.iter() {
+            let zone_check = match continuous {
+                true => ball.get_swept_area(self.ball_starts[id.index()], margin),
+                false => ball.get_swept_area(ball.position, margin),
+            };
+            self.near_entries.clear();
+            self.broad_phase
+                .query_entries(&zone_check, &mut self.near_entries);
+            for entry in self.near_entries.iter() {
+                let Some(other_id) = entry.payload.ball() else {
+                    continue;
+                };
+                let Some(other) = self.balls.get(other_id).filter(|_| other_id != id) else {
+                    continue;
+                };
+
+                let impact = match continuous {
+                    true => ball.time_of_impact(
+                        self.ball_starts[id.index()],
+                        other,
+                        self.ball_starts[other_id.index()],
+                    ),
+                    false => ball.check_collision(other).then_some(1.),
+                };
+
+                if let Some(time) = impact {
+                    self.impacts.push((time, id, other_id));
+                }
+            }
+        }
+
+        // Ties are broken by id so the result does not depend on the broad phase
+        self.impacts
+            .sort_unstable_by(|a, b| a.0.total_cmp(&b.0).then((a.1, a.2).cmp(&(b.1, b.2))));
+        for &(time, id, other_id) in self.impacts.iter() {
+            // Has ball already collided this frame
+            if self.collided_balls.contains(&id) || self.collided_balls.contains(&other_id) {
+                continue;
+            }
+
+            let Some((ball, other)) = self.balls.get2_mut(id, other_id) else {
+                continue;
+            };
+            // Balls already overlapping at the start of the step bounce where they are,
+            // moving them back would throw away the path the integrator gave them
+            if continuous && time > 0. {
+                // Move both balls back to the moment they touch, bounce, and use their
+                // new velocity for the rest of the step
+                let ball_position = self.ball_starts[id.index()].lerp(ball.position, time);
+                let other_position = self.ball_starts[other_id.index()].lerp(other.position, time);
+                if (ball.velocity - other.velocity).dot(ball_position - other_position) >= 0. {
+                    continue;
+                }
+
+                ball.position = ball_position;
+                other.position = other_position;
+                ball.collide(other, dt);
+
+                let remaining = (1. - time) * dt;
+                for ball in [ball, other] {
+                    ball.position += ball.velocity * remaining;
+                    ball.set_velocity(ball.velocity, dt);
+                }
+            } else {
+                ball.collide(other, dt);
+            }
+
+            self.collided_balls.push(id);
+            self.collided_balls.push(other_id);
+        }
+
+        // Contacts with static bodies
+        for body_index in 0..self.static_bodies.len() {
+            let StaticBody {
+                ball: body,
+                contact,
+            } = self.static_bodies[body_index];
+            let body_start = self.body_starts[body_index];
+            let query = match continuous {
+                true => body.get_swept_area(body_start, margin),
+                false => body.get_swept_area(body.position, margin),
+            };
+            self.near_entries.clear();
+            self.broad_phase
+                .query_entries(&query, &mut self.near_entries);
+            // In id order, so accreted masses add up the same whatever the broad phase
+            self.near_entries
+                .sort_unstable_by_key(|entry| entry.payload);
+            for near in self.near_entries.iter() {
+                let Some(id) = near.payload.ball() else {
+                    continue;
+                };
+                if self.despawned_balls.contains(&id) {
+                    continue;
+                }
+                let Some(ball) = self.balls.get_mut(id) else {
+                    continue;
+                };
+                let ball_start = self.ball_starts[id.index()];
+                if ball.landed_on.is_some() {
+                    continue;
+                }
+                // Earlier balls may have changed its mass or velocity
+                let body = self.static_bodies[body_index].ball;
+
+                let impact = match continuous {
+                    true => ball.time_of_impact(ball_start, &body, body_start),
+                    false => body.check_collision(ball).then_some(1.),
+                };
+                let Some(time) = impact else {
+                    continue;
+                };
+
+                match contact {
+                    ContactPolicy::Absorb { accrete } => {
+                        if accrete {
+                            self.static_bodies[body_index].accrete(
+                                ball,
+                                self.physics.dynamic_bodies,
+                                dt,
+                            );
+                        }
+                        self.despawned_balls.push(id);
+                        self.events.push(WorldEvent::Absorbed {
+                            ball: id,
+                            body: body_index,
+                            position: match continuous {
+                                true => ball_start.lerp(ball.position, time),
+                                false => ball.position,
+                            },
+                        });
+                    }
+                    ContactPolicy::Bounce { correction } => {
+                        // Bounce where they touch, and use the new velocity for the rest
+                        // of the step. Overlaps from the start of the step are only pushed
+                        // out, so the ball keeps the path the integrator gave it.
+                        let rewind = continuous && time > 0.;
+                        let mut contact_body = body;
+                        if rewind {
+                            ball.position = ball_start.lerp(ball.position, time);
+                            contact_body.position = body_start.lerp(body.position, time);
+                        }
+
+                        // Dynamic bodies are pushed back, the others are too heavy to move
+                        let speed = match self.physics.dynamic_bodies {
+                            true => {
+                                let speed = ball.collide(&mut contact_body, dt);
+                                self.static_bodies[body_index]
+                                    .ball
+                                    .set_velocity(contact_body.velocity, dt);
+                                speed
+                            }
+                            false => ball.bounce_off(&contact_body, dt),
+                        };
+                        if rewind {
+                            ball.position += ball.velocity * (1. - time) * dt;
+                            ball.set_velocity(ball.velocity, dt);
+                        }
+
+                        let delta = ball.position - body.position;
+                        let penetration = body.radius + ball.radius - delta.length();
+                        if penetration > 0. {
+                            ball.position += delta.normalize_or_zero() * penetration * correction;
+                            ball.set_velocity(ball.velocity, dt);
+                        }
+
+                        if let Some(speed) = speed {
+                            self.events.push(WorldEvent::Bounced {
+                                ball: id,
+                                body: body_index,
+                                speed,
+                            });
+                        }
+                    }
+                    ContactPolicy::Land => {
+                        let direction = (ball.position - body.position).try_normalize();
+                        let offset = direction.unwrap_or(Vec2::X) * (body.radius + ball.radius);
+                        ball.landed_on = Some(Landing {
+                            body: body_index,
+                            offset,
+                        });
+                        ball.position = body.position + offset;
+                        ball.set_velocity(body.velocity, dt);
+                        self.events.push(WorldEvent::Landed {
+                            ball: id,
+                            body: body_index,
+                        });
+                    }
+                    ContactPolicy::PassThrough => {
+                        if ball_start.distance(body_start) > ball.radius + body.radius {
+                            self.events.push(WorldEvent::PassedThrough {
+                                ball: id,
+                                body: body_index,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+
+        // Contacts with capsules, balls bounce off them like off an infinitely heavy body
+        for capsule_index in 0..self.capsules.len() {
+            let capsule = self.capsules[capsule_index];
+            let displacement = capsule.velocity * dt;
+            let bounds = capsule.bounds();
+            let query = match continuous {
+                true => bounds.union(&Rect::around(
+                    bounds.center() - displacement,
+                    bounds.half_size(),
+                )),
+                false => bounds,
+            };
+            self.near_entries.clear();
+            self.broad_phase.query_entries(
+                &Rect::around(query.center(), query.half_size() + Vec2::splat(margin)),
+                &mut self.near_entries,
+            );
+            self.near_entries
+                .sort_unstable_by_key(|entry| entry.payload);
+            for near in self.near_entries.iter() {
+                let Some(id) = near.payload.ball() else {
+                    continue;
+                };
+                if self.despawned_balls.contains(&id) {
+                    continue;
+                }
+                let Some(ball) = self
+                    .balls
+                    .get_mut(id)
+                    .filter(|ball| ball.landed_on.is_none())
+                else {
+                    continue;
+                };
+                let ball_start = self.ball_starts[id.index()];
+
+                let impact = match continuous {
+                    true => capsule.time_of_impact(displacement, ball_start, ball),
+                    false => capsule.contact(ball).map(|_| 1.),
+                };
+                let Some(time) = impact else {
+                    continue;
+                };
+
+                // Bounce where they touch, and use the new velocity for the rest of the step.
+                // Overlaps from the start of the step are only pushed out.
+                let rewind = continuous && time > 0.;
+                let mut contact_capsule = capsule;
+                if rewind {
+                    ball.position = ball_start.lerp(ball.position, time);
+                    contact_capsule.translate(-displacement * (1. - time));
+                }
+                let speed = contact_capsule.bounce(ball, dt);
+                if rewind {
+                    ball.position += ball.velocity * (1. - time) * dt;
+                    ball.set_velocity(ball.velocity, dt);
+                }
+                capsule.push_out(ball, 1., dt);
+
+                if let Some(speed) = speed {
+                    self.events.push(WorldEvent::BouncedOffCapsule {
+                        ball: id,
+                        capsule: capsule_index,
+                        speed,
+                    });
+                }
+            }
+        }
+
+        // Done last so collisions are found along the path the balls really took
+        for (id, ball) in self.balls.iter_mut() {
+            let was_inside = self.tree_area.contains(self.ball_starts[id.index()]);
+            let outside = self.physics.boundary.apply(ball, &self.tree_area, dt);
+            if !outside {
+                continue;
+            }
+
+            match self.physics.boundary {
+                BoundaryPolicy::Despawn if !self.despawned_balls.contains(&id) => {
+                    self.despawned_balls.push(id);
+                    self.events.push(WorldEvent::Escaped {
+                        ball: id,
+                        position: ball.position,
+                    });
+                }
+                BoundaryPolicy::Unbounded if was_inside => {
+                    self.events.push(WorldEvent::Escaped {
+                        ball: id,
+                        position: ball.position,
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        for id in self.despawned_balls.drain(..) {
+            self.balls.remove(id);
+            self.broad_phase.remove(Collider::Ball(id));
+        }
+        if self
+            .selected_ball
+            .is_some_and(|id| !self.balls.contains(id))
+        {
+            self.selected_ball = None;
+        }
+        self.sync_broad_phase();
+
+        self.step_count += 1;
+        if self.diagnostics_log.is_some() {
+            let diagnostics = self.diagnostics();
+            if let Some(log) = &mut self.diagnostics_log {
+                log.record(diagnostics);
+            }
+        }
+    }
+
+    /// Removes the ball at the end of the next step, or of the current one when called
+    /// during a step.
+    pub fn despawn(&mut self, id: BallId) {
+        if self.balls.contains(id) && !self.despawned_balls.contains(&id) {
+            self.despawned_balls.push(id);
+        }
+    }
+
+    /// Number of balls not waiting to be removed.
+    pub fn balls_in_play(&self) -> usize {
+        self.balls.len() - self.despawned_balls.len()
+    }
+
+    /// Events emitted since the last `drain_events`, left in place.
+    pub fn events(&self) -> &[WorldEvent] {
+        &self.events
+    }
+
+    /// Takes the events emitted since the last call.
+    pub fn drain_events(&mut self) -> std::vec::Drain<'_, WorldEvent> {
+        self.events.drain(..)
+    }
+
+    pub fn diagnostics(&self) -> Diagnostics {
+        Diagnostics::measure(self)
+    }
+
+    /// Orbit of the ball `id` around the static body pulling hardest on it, with the index
+    /// of that body. None without bodies.
+    pub fn orbital_elements(&self, id: BallId) -> Option<(usize, OrbitalElements)> {
+        let ball = self.balls.get(id)?;
+        let body = dominant_body(&self.static_bodies, ball.position)?;
+        let elements =
+            OrbitalElements::new(ball, &self.static_bodies[body].ball, self.physics.gravity);
+        Some((body, elements))
+    }
+
+    /// Gives every ball the velocity of a circular orbit around the first static body.
+    pub fn circularize_orbits(&mut self) {
+        let Some(center) = self.static_bodies.first().map(|body| &body.ball) else {
+            return;
+        };
+
+        for ball in self.balls.values_mut() {
+            ball.set_velocity(
+                get_orbital_velocity(ball, center, self.physics.gravity),
+                self.physics.dt,
+            );
+        }
+    }
+
+    pub fn scale_velocities(&mut self, factor: f32) {
+        for ball in self.balls.values_mut() {
+            ball.set_velocity(ball.velocity * factor, self.physics.dt);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use celestial_pong::ai::{AiPaddle, Difficulty};
+use celestial_pong::game::{Game, GamePhase};
+use celestial_pong::scenario::Scenario;
+use celestial_pong::world::World;
+use macroquad::prelude::*;
+
+// A computer paddle guarding the right half of a star, starting at `angle`, and a
+// ball falling into the star at 60 degrees
+fn guarded_world(angle: f32) -> World {
+    let direction = Vec2::from_angle(60f32.to_radians());
+    let position = direction * 450.;
+    let velocity = -direction * 150.;
+    let scenario = Scenario::parse(&format!(
+        r#"
+        [game]
+        serve_delay = 0.0
+
+        [[static_bodies]]
+        radius = 30.0
+        mass = 1000.0
+
+        [[paddles]]
+        orbit = 200.0
+        angle = {angle:?}
+        arc = [-90.0, 90.0]
+        length = 50.0
+        radius = 5.0
+        speed = 300.0
+        ai = "hard"
+
+        [[balls]]
+        kind = "explicit"
+        position = [{}, {}]
+        velocity = [{}, {}]
+        radius = 5.0
+        mass = 1.0
+        "#,
+        position.x, position.y, velocity.x, velocity.y
+    ))
+    .unwrap();
+    World::new(scenario)
+}
+
+// Plays until the ball is hit back or lost
+fn play(world: &mut World, opponents: &mut [AiPaddle]) -> Game {
+    let mut game = Game::for_world(world);
+    game.start();
+    let dt = world.physics.dt;
+    for _ in 0..2000 {
+        for ai in opponents.iter_mut() {
+            ai.update(world, dt);
+        }
+        game.step(world, dt);
+        if game.hits[0] > 0 || matches!(game.phase, GamePhase::PointScored { .. }) {
+            break;
+        }
+    }
+    game
+}
+
+#[test]
+fn computer_paddles_intercept_falling_balls() {
+    let mut world = guarded_world(-30.);
+    let mut opponents = AiPaddle::for_world(&world);
+    assert_eq!(opponents, vec![AiPaddle::new(0, Difficulty::Hard)]);
+
+    let game = play(&mut world, &mut opponents);
+    assert_eq!(game.hits, vec![1]);
+    assert_eq!(game.conceded, vec![0]);
+    let target = opponents[0].target.unwrap();
+    assert!((target - 60f32.to_radians()).abs() < 0.05, "{}", target);
+
+    // Without anyone at the paddle, the ball falls in
+    let mut world = guarded_world(-30.);
+    let game = play(&mut world, &mut []);
+    assert_eq!(game.conceded, vec![1]);
+}
+
+#[test]
+fn easier_computers_are_slower() {
+    let mut distances = Vec::new();
+    for difficulty in Difficulty::ALL {
+        let mut world = guarded_world(-80.);
+        let mut ai = AiPaddle::new(0, difficulty);
+        let dt = world.physics.dt;
+        // A quarter of a second: every difficulty decided to move by then
+        for _ in 0..30 {
+            ai.update(&mut world, dt);
+            world.step(dt);
+        }
+        distances.push(world.paddles[0].angle - (-80f32).to_radians());
+    }
+    assert!(distances[0] > 0.);
+    assert!(distances[0] < distances[1] && distances[1] < distances[2]);
+
+    // Missing the ball when too slow to get there, or seeing it too late
+    for (angle, saved_from) in [(-20., Difficulty::Hard), (20., Difficulty::Medium)] {
+        for difficulty in Difficulty::ALL {
+            let mut world = guarded_world(angle);
+            let game = play(&mut world, &mut [AiPaddle::new(0, difficulty)]);
+            let saved = difficulty >= saved_from;
+            assert_eq!(game.hits, vec![saved as u32], "{:?}", difficulty);
+            assert_eq!(game.conceded, vec![!saved as u32]);
+        }
+    }
+}
+
+#[test]
+fn paddles_that_cannot_move_stay_still() {
+    let mut world = guarded_world(-80.);
+    let mut ai = AiPaddle::new(0, Difficulty::Hard);
+    let dt = world.physics.dt;
+    ai.update(&mut world, 0.);
+    assert_eq!(world.paddles[0].input, 0.);
+
+    world.paddles[0].speed = 0.;
+    ai.update(&mut world, dt);
+    assert_eq!(world.paddles[0].input, 0.);
+    world.step(dt);
+    assert_eq!(world.paddles[0].angle, (-80f32).to_radians());
+}
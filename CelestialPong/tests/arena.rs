@@ -0,0 +1,106 @@
+use celestial_pong::arena::{Arena, ArenaId, Slot};
+use celestial_pong::scenario::Scenario;
+use celestial_pong::world::World;
+
+#[test]
+fn reused_slots_get_new_generations() {
+    let mut arena = Arena::new();
+    let a = arena.insert("a");
+    let b = arena.insert("b");
+    assert_eq!((a.index(), a.generation()), (0, 0));
+    assert_eq!((b.index(), b.generation()), (1, 0));
+
+    assert_eq!(arena.remove(a), Some("a"));
+    assert_eq!(arena.remove(a), None);
+    let c = arena.insert("c");
+    assert_eq!((c.index(), c.generation()), (0, 1));
+    assert_eq!(arena.len(), 2);
+
+    arena.remove(c);
+    let d = arena.insert("d");
+    assert_eq!((d.index(), d.generation()), (0, 2));
+}
+
+#[test]
+fn stale_ids_find_nothing() {
+    let mut arena = Arena::new();
+    let a = arena.insert(1);
+    arena.remove(a);
+    let b = arena.insert(2);
+    assert_eq!(a.index(), b.index());
+
+    assert_eq!(arena.get(a), None);
+    assert_eq!(arena.get_mut(a), None);
+    assert!(!arena.contains(a));
+    assert_eq!(arena.get(b), Some(&2));
+    // Never given out yet
+    assert_eq!(arena.get(ArenaId::new(5, 0)), None);
+
+    // Clearing invalidates every id, slots are reused in order
+    arena.clear();
+    assert!(arena.is_empty() && !arena.contains(b));
+    let c = arena.insert(3);
+    assert_eq!((c.index(), c.generation()), (0, 2));
+}
+
+#[test]
+fn pairs_of_values() {
+    let mut arena = Arena::new();
+    let a = arena.insert(1);
+    let b = arena.insert(2);
+
+    let (first, second) = arena.get2_mut(b, a).unwrap();
+    assert_eq!((*first, *second), (2, 1));
+    *first = 20;
+    assert_eq!(arena[b], 20);
+
+    assert!(arena.get2_mut(a, a).is_none());
+    arena.remove(a);
+    assert!(arena.get2_mut(a, b).is_none());
+}
+
+#[test]
+fn free_lists_must_match_the_slots() {
+    let slots = || {
+        vec![
+            Slot {
+                generation: 0,
+                value: Some(1),
+            },
+            Slot {
+                generation: 3,
+                value: None,
+            },
+            Slot {
+                generation: 1,
+                value: Some(2),
+            },
+        ]
+    };
+    let arena = Arena::from_slots(slots(), vec![1]).unwrap();
+    assert_eq!(arena.len(), 2);
+    assert_eq!(arena.get(ArenaId::new(2, 1)), Some(&2));
+
+    // Missing, listed twice, occupied or out of range
+    assert!(Arena::from_slots(slots(), vec![]).is_none());
+    assert!(Arena::from_slots(slots(), vec![1, 1]).is_none());
+    assert!(Arena::from_slots(slots(), vec![0]).is_none());
+    assert!(Arena::from_slots(slots(), vec![1, 7]).is_none());
+}
+
+#[test]
+fn ids_do_not_survive_resets() {
+    let mut world = World::new(Scenario::default());
+    let before: Vec<ArenaId> = world.balls.ids().collect();
+    world.reset();
+    assert_eq!(world.balls.len(), before.len());
+    for id in before {
+        assert!(!world.balls.contains(id));
+    }
+
+    // Same balls in the same order as a new world
+    let fresh = World::new(Scenario::default());
+    let positions =
+        |world: &World| -> Vec<_> { world.balls.values().map(|ball| ball.position).collect() };
+    assert_eq!(positions(&world), positions(&fresh));
+}
@@ -0,0 +1,96 @@
+use celestial_pong::ball::Ball;
+use celestial_pong::material::Material;
+use celestial_pong::scenario::Scenario;
+use celestial_pong::world::World;
+use macroquad::prelude::*;
+
+#[test]
+fn balls_at_the_same_place_do_not_collide() {
+    let dt = 1. / 120.;
+    let mut a = Ball::new(vec2(10., 20.), vec2(30., 0.), 5., 1., WHITE);
+    let mut b = Ball::new(vec2(10., 20.), vec2(-30., 0.), 5., 2., WHITE);
+    a.collide(&mut b, dt);
+    assert_eq!(a.velocity, vec2(30., 0.));
+    assert_eq!(b.velocity, vec2(-30., 0.));
+
+    // Nor in a world, where they stay finite until they drift apart
+    let mut world = World::new(
+        Scenario::parse(
+            r#"
+            [[balls]]
+            kind = "explicit"
+            position = [0.0, 0.0]
+            velocity = [10.0, 0.0]
+            radius = 5.0
+            mass = 1.0
+
+            [[balls]]
+            kind = "explicit"
+            position = [0.0, 0.0]
+            velocity = [0.0, 0.0]
+            radius = 5.0
+            mass = 1.0
+            "#,
+        )
+        .unwrap(),
+    );
+    let dt = world.physics.dt;
+    for _ in 0..10 {
+        world.step(dt);
+    }
+    for ball in world.balls.values() {
+        assert!(ball.position.is_finite() && ball.velocity.is_finite());
+        assert!(ball.prev_position.is_finite());
+    }
+}
+
+// Two balls of mass 1 touching along x, the first one moving at (100, 100) into the
+// other one at rest
+fn oblique_collision(restitution: f32, friction: f32) -> (Ball, Ball) {
+    let material = Material {
+        restitution,
+        friction,
+        density: None,
+    };
+    let mut a = Ball::new(vec2(0., 0.), vec2(100., 100.), 5., 1., WHITE);
+    let mut b = Ball::new(vec2(10., 0.), Vec2::ZERO, 5., 1., WHITE);
+    a.material = material;
+    b.material = material;
+    let speed = a.collide(&mut b, 1. / 120.);
+    assert_eq!(speed, Some(100.));
+    assert!((a.velocity + b.velocity - vec2(100., 100.)).length() < 1e-3);
+    (a, b)
+}
+
+#[test]
+fn friction_slows_the_sliding() {
+    // A normal impulse of 75 along x
+    let (a, b) = oblique_collision(0.5, 0.);
+    assert_eq!((a.velocity, b.velocity), (vec2(25., 100.), vec2(75., 0.)));
+
+    // Coulomb friction: a tangential impulse of 0.2 times the normal one
+    let (a, b) = oblique_collision(0.5, 0.2);
+    assert!(
+        (a.velocity - vec2(25., 85.)).length() < 1e-3,
+        "{}",
+        a.velocity
+    );
+    assert!(
+        (b.velocity - vec2(75., 15.)).length() < 1e-3,
+        "{}",
+        b.velocity
+    );
+
+    // Strong friction stops the sliding, without reversing it
+    let (a, b) = oblique_collision(0.5, 1.);
+    assert!(
+        (a.velocity - vec2(25., 50.)).length() < 1e-3,
+        "{}",
+        a.velocity
+    );
+    assert!(
+        (b.velocity - vec2(75., 50.)).length() < 1e-3,
+        "{}",
+        b.velocity
+    );
+}
@@ -0,0 +1,127 @@
+use ::rand::{Rng, SeedableRng};
+use celestial_pong::barnes_hut::BarnesHutTree;
+use celestial_pong::gravity::{get_field_acceleration, Attractor, GravityField, GravitySolver};
+use celestial_pong::scenario::Scenario;
+use celestial_pong::world::World;
+use macroquad::prelude::*;
+use rand_chacha::ChaCha20Rng;
+
+const GRAVITY: f32 = 30000.;
+const SOFTENING: f32 = 2.;
+
+// Attractors of random masses spread over a square
+fn cloud(count: usize) -> Vec<Attractor> {
+    let mut rng = ChaCha20Rng::seed_from_u64(7);
+    (0..count)
+        .map(|_| Attractor {
+            position: vec2(rng.gen_range(-500.0..500.0), rng.gen_range(-500.0..500.0)),
+            mass: rng.gen_range(1.0..50.0),
+        })
+        .collect()
+}
+
+fn field<'a>(attractors: &'a [Attractor], tree: &'a BarnesHutTree, theta: f32) -> GravityField<'a> {
+    GravityField {
+        attractors,
+        tree,
+        solver: GravitySolver::BarnesHut,
+        theta,
+        gravity: GRAVITY,
+        softening: SOFTENING,
+    }
+}
+
+#[test]
+fn zero_theta_gives_the_direct_sum() {
+    let attractors = cloud(300);
+    let mut tree = BarnesHutTree::new();
+    tree.build(&attractors);
+    let field = field(&attractors, &tree, 0.);
+
+    for (index, attractor) in attractors.iter().enumerate() {
+        for skip in [Some(index), None] {
+            let position = attractor.position + vec2(0.5, -0.25);
+            let exact = get_field_acceleration(position, &attractors, skip, GRAVITY, SOFTENING);
+            let tree = field.acceleration(position, skip);
+            // Same terms, only summed in another order
+            assert!(
+                (tree - exact).length() <= exact.length() * 1e-5,
+                "{} {}",
+                tree,
+                exact
+            );
+        }
+    }
+    let error = field.force_error(
+        attractors
+            .iter()
+            .map(|attractor| (attractor.position, None)),
+    );
+    assert!(error.max < 1e-5, "{:?}", error);
+}
+
+#[test]
+fn approximation_error_stays_bounded() {
+    let attractors = cloud(500);
+    let mut tree = BarnesHutTree::new();
+    tree.build(&attractors);
+    let error = |theta: f32| {
+        field(&attractors, &tree, theta).force_error(
+            attractors
+                .iter()
+                .enumerate()
+                .map(|(index, attractor)| (attractor.position, Some(index))),
+        )
+    };
+    let coarse = error(0.5);
+    let fine = error(0.25);
+    // The largest errors are where the pulls of both sides almost cancel out
+    assert!(coarse.mean < 0.02 && coarse.max < 0.2, "{:?}", coarse);
+    assert!(
+        fine.mean < coarse.mean / 2. && fine.max < 0.05,
+        "{:?}",
+        fine
+    );
+}
+
+#[test]
+fn force_error_is_measured_on_the_balls() {
+    // A clump of bodies, seen as a single one from the balls far away
+    let scenario = Scenario::parse(
+        r#"
+        [physics]
+        solver = "barnes_hut"
+        theta = 1.0
+        report_force_error = true
+
+        [[static_bodies]]
+        position = [-5.0, -5.0]
+        radius = 2.0
+        mass = 100.0
+
+        [[static_bodies]]
+        position = [5.0, -4.0]
+        radius = 2.0
+        mass = 200.0
+
+        [[static_bodies]]
+        position = [0.0, 6.0]
+        radius = 2.0
+        mass = 300.0
+
+        [[balls]]
+        kind = "orbital"
+        count = 10
+        min_orbit = 150.0
+        max_orbit = 250.0
+        radius = 3.0
+        mass = 1.0
+        "#,
+    )
+    .unwrap();
+    let mut world = World::new(scenario);
+    world.step(world.physics.dt);
+    // The bodies see each other exactly, only the balls see an approximation
+    let error = world.force_error.unwrap();
+    assert!(error.mean > 1e-4 && error.max < 0.01, "{:?}", error);
+}
@@ -0,0 +1,177 @@
+use celestial_pong::ball::Ball;
+use celestial_pong::boundary::BoundaryPolicy;
+use celestial_pong::quad_tree::Rect;
+use celestial_pong::scenario::Scenario;
+use celestial_pong::world::{World, WorldEvent};
+use macroquad::prelude::*;
+
+// A ball flying into the bottom-right corner of a 200 by 200 field, 25 units per step
+// on each axis, and a small bouncing body on its way back
+fn corner_world() -> World {
+    let scenario = Scenario::parse(
+        r#"
+        field_size = [200.0, 200.0]
+
+        [[static_bodies]]
+        position = [80.0, 80.0]
+        radius = 5.0
+        mass = 0.001
+        contact = { kind = "bounce" }
+
+        [[balls]]
+        kind = "explicit"
+        position = [90.0, 90.0]
+        velocity = [3000.0, 3000.0]
+        radius = 5.0
+        mass = 1.0
+        "#,
+    )
+    .unwrap();
+    World::new(scenario)
+}
+
+#[test]
+fn reflected_balls_stay_inside_the_field() {
+    let mut world = corner_world();
+    let dt = world.physics.dt;
+    world.step(dt);
+
+    let ball = *world.balls.values().next().unwrap();
+    assert!(world.tree_area.contains(ball.position), "{}", ball.position);
+    assert!(ball.velocity.x < 0. && ball.velocity.y < 0.);
+    // Still known to the broad phase, with the body
+    assert_eq!(world.broad_phase.len(), 2);
+    assert_eq!(world.drain_events().count(), 0);
+
+    // So the body finds it on the way back
+    world.step(dt);
+    let events: Vec<WorldEvent> = world.drain_events().collect();
+    assert!(
+        matches!(events[..], [WorldEvent::Bounced { body: 0, .. }]),
+        "{:?}",
+        events
+    );
+    let ball = world.balls.values().next().unwrap();
+    assert!(ball.velocity.x > 0. && ball.velocity.y > 0.);
+}
+
+#[test]
+fn reflection_keeps_balls_off_every_edge() {
+    let field = Rect::from_edges(-100., 100., -100., 100.);
+    for velocity in [
+        vec2(500., 500.),
+        vec2(-500., -500.),
+        vec2(500., -500.),
+        vec2(-500., 500.),
+    ] {
+        let position = velocity.signum() * 101.;
+        let mut ball = Ball::new(position, velocity, 5., 1., WHITE);
+        BoundaryPolicy::Reflect.apply(&mut ball, &field, 1. / 120.);
+        assert!(field.contains(ball.position), "{}", ball.position);
+        assert_eq!(ball.velocity, -velocity);
+    }
+}
+
+// Balls of mass 1 in a 200 by 200 field, with gravity too weak to matter
+fn field_world(boundary: &str, balls: &[([f32; 2], [f32; 2])]) -> World {
+    let mut text = format!(
+        "field_size = [200.0, 200.0]\n\n[physics]\ngravity = 0.001\nboundary = \"{}\"\n",
+        boundary
+    );
+    for (position, velocity) in balls {
+        text += &format!(
+            "\n[[balls]]\nkind = \"explicit\"\nposition = {:?}\nvelocity = {:?}\n\
+             radius = 5.0\nmass = 1.0\n",
+            position, velocity
+        );
+    }
+    World::new(Scenario::parse(&text).unwrap())
+}
+
+#[test]
+fn wrapped_balls_keep_their_velocity() {
+    // 10 units per step, out on the right after the first one
+    let mut world = field_world("wrap", &[([95., 0.], [1200., 0.])]);
+    let dt = world.physics.dt;
+    world.step(dt);
+    let ball = *world.balls.values().next().unwrap();
+    assert!((ball.position.x + 95.).abs() < 1e-3, "{}", ball.position);
+    assert!(
+        (ball.velocity - vec2(1200., 0.)).length() < 0.1,
+        "{}",
+        ball.velocity
+    );
+    assert!((ball.prev_position - (ball.position - ball.velocity * dt)).length() < 1e-3);
+
+    // The next step does not see a jump across the field
+    world.step(dt);
+    let ball = *world.balls.values().next().unwrap();
+    assert!(
+        (ball.velocity - vec2(1200., 0.)).length() < 0.1,
+        "{}",
+        ball.velocity
+    );
+    assert!((ball.position.x + 85.).abs() < 1e-3, "{}", ball.position);
+    assert_eq!(world.drain_events().count(), 0);
+}
+
+#[test]
+fn despawned_balls_escape_once() {
+    let mut world = field_world("despawn", &[([95., 0.], [1200., 0.]), ([0., 0.], [0., 0.])]);
+    let id = world.balls.ids().next().unwrap();
+    let dt = world.physics.dt;
+
+    let mut events = Vec::new();
+    for _ in 0..5 {
+        world.step(dt);
+        events.extend(world.drain_events());
+    }
+    let [WorldEvent::Escaped { ball, position }] = events[..] else {
+        panic!("{:?}", events);
+    };
+    assert_eq!(ball, id);
+    assert!((position.x - 105.).abs() < 1e-3, "{}", position);
+    assert!(!world.balls.contains(id));
+    assert_eq!(world.balls.len(), 1);
+    assert_eq!(world.broad_phase.len(), 1);
+}
+
+#[test]
+fn unbounded_worlds_keep_colliding_outside_the_field() {
+    // The first ball leaves on the first step, then hits the second one outside
+    let mut world = field_world(
+        "unbounded",
+        &[([90., 0.], [1200., 0.]), ([300., 0.], [0., 0.])],
+    );
+    let ids: Vec<_> = world.balls.ids().collect();
+    let dt = world.physics.dt;
+
+    world.step(dt);
+    let events: Vec<WorldEvent> = world.drain_events().collect();
+    assert!(
+        matches!(events[..], [WorldEvent::Escaped { ball, .. }] if ball == ids[0]),
+        "{:?}",
+        events
+    );
+    let area = world.broad_phase.area().unwrap();
+    assert!(area.contains(vec2(300., 0.)), "{:?}", area);
+    assert_eq!(world.tree_area.right, 100.);
+
+    for _ in 0..40 {
+        world.step(dt);
+        assert_eq!(world.drain_events().count(), 0);
+    }
+    // Equal masses, the first one stopped and the second one took its speed
+    assert!(
+        world.balls[ids[0]].velocity.length() < 1.,
+        "{}",
+        world.balls[ids[0]].velocity
+    );
+    assert!((world.balls[ids[1]].velocity.x - 1200.).abs() < 1.);
+    assert!(world
+        .broad_phase
+        .area()
+        .unwrap()
+        .contains(world.balls[ids[1]].position));
+    assert_eq!(world.balls.len(), 2);
+}
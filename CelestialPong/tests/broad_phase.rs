@@ -0,0 +1,168 @@
+use ::rand::{Rng, SeedableRng};
+use celestial_pong::arena::ArenaId;
+use celestial_pong::broad_phase::{BroadPhase, BroadPhaseKind};
+use celestial_pong::quad_tree::{Collider, QuadTreeConfig, QuadTreeEntry, Rect};
+use celestial_pong::scenario::Scenario;
+use celestial_pong::world::World;
+use macroquad::prelude::*;
+use rand_chacha::ChaCha20Rng;
+
+const AREA: f32 = 1000.;
+
+fn kinds() -> [BroadPhaseKind; 4] {
+    [
+        BroadPhaseKind::QuadTree,
+        BroadPhaseKind::Grid { cell_size: 10. },
+        BroadPhaseKind::Grid { cell_size: 97. },
+        BroadPhaseKind::SortAndSweep,
+    ]
+}
+
+fn build(kind: BroadPhaseKind) -> Box<dyn BroadPhase> {
+    kind.build(Rect::new(0., 0., AREA, AREA), QuadTreeConfig::default())
+}
+
+fn payload(index: usize) -> Collider {
+    match index % 10 {
+        0 => Collider::Body(index),
+        _ => Collider::Ball(ArenaId::new(index as u32, 0)),
+    }
+}
+
+// Mostly small boxes, a few points and a few much larger ones
+fn random_bounds(rng: &mut ChaCha20Rng) -> Rect {
+    let half = AREA / 2.;
+    let center = Vec2::new(
+        rng.gen_range(-half..half - 1.),
+        rng.gen_range(-half..half - 1.),
+    );
+    let half_size = match rng.gen_range(0..10) {
+        0 => Vec2::ZERO,
+        1 => Vec2::new(rng.gen_range(0. ..AREA / 4.), rng.gen_range(0. ..AREA / 4.)),
+        _ => Vec2::new(rng.gen_range(0. ..8.), rng.gen_range(0. ..8.)),
+    };
+    Rect::around(center, half_size)
+}
+
+fn random_query(rng: &mut ChaCha20Rng) -> Rect {
+    let center = Vec2::new(
+        rng.gen_range(-AREA * 0.6..AREA * 0.6),
+        rng.gen_range(-AREA * 0.6..AREA * 0.6),
+    );
+    let size = Vec2::new(rng.gen_range(0. ..AREA / 2.), rng.gen_range(0. ..AREA / 2.));
+    Rect::new(center.x, center.y, size.x, size.y)
+}
+
+fn sorted_payloads(entries: &[QuadTreeEntry]) -> Vec<Collider> {
+    let mut payloads: Vec<Collider> = entries.iter().map(|entry| entry.payload).collect();
+    payloads.sort();
+    payloads
+}
+
+fn present(model: &[Option<Rect>]) -> Vec<QuadTreeEntry> {
+    model
+        .iter()
+        .enumerate()
+        .filter_map(|(index, bounds)| Some(QuadTreeEntry::new((*bounds)?, payload(index))))
+        .collect()
+}
+
+#[test]
+fn structures_match_brute_force() {
+    for kind in kinds() {
+        let mut rng = ChaCha20Rng::seed_from_u64(21);
+        let mut broad_phase = build(kind);
+        let mut model: Vec<Option<Rect>> = vec![None; 300];
+        let mut result = Vec::new();
+        for round in 0..3000 {
+            let index = rng.gen_range(0..model.len());
+            match rng.gen_range(0..4) {
+                0 => assert_eq!(
+                    broad_phase.remove(payload(index)),
+                    model[index].take().is_some()
+                ),
+                _ => {
+                    let bounds = random_bounds(&mut rng);
+                    assert!(broad_phase.update(payload(index), bounds));
+                    model[index] = Some(bounds);
+                }
+            }
+            if round % 50 != 0 {
+                continue;
+            }
+
+            broad_phase.refresh();
+            let entries = present(&model);
+            assert_eq!(broad_phase.len(), entries.len());
+
+            let query = random_query(&mut rng);
+            let expected: Vec<QuadTreeEntry> = entries
+                .iter()
+                .copied()
+                .filter(|entry| entry.bounds.overlap(&query))
+                .collect();
+            result.clear();
+            broad_phase.query_entries(&query, &mut result);
+            assert_eq!(
+                sorted_payloads(&result),
+                sorted_payloads(&expected),
+                "{}",
+                broad_phase.name()
+            );
+
+            let center = query.center();
+            let radius = query.half_width;
+            let expected: Vec<QuadTreeEntry> = entries
+                .iter()
+                .copied()
+                .filter(|entry| entry.bounds.distance_squared(center) <= radius * radius)
+                .collect();
+            result.clear();
+            broad_phase.query_radius_entries(center, radius, &mut result);
+            assert_eq!(sorted_payloads(&result), sorted_payloads(&expected));
+
+            let mut expected = Vec::new();
+            for (index, a) in entries.iter().enumerate() {
+                for b in &entries[index + 1..] {
+                    if a.bounds.overlap(&b.bounds) {
+                        expected.push((a.payload.min(b.payload), a.payload.max(b.payload)));
+                    }
+                }
+            }
+            expected.sort();
+            let mut pairs = Vec::new();
+            broad_phase.overlapping_pairs(&mut pairs);
+            pairs.sort();
+            assert_eq!(pairs, expected, "{}", broad_phase.name());
+        }
+
+        broad_phase.clear();
+        broad_phase.refresh();
+        assert!(broad_phase.is_empty());
+        result.clear();
+        broad_phase.query_entries(&Rect::new(0., 0., AREA * 2., AREA * 2.), &mut result);
+        assert!(result.is_empty());
+    }
+}
+
+#[test]
+fn worlds_do_not_depend_on_the_broad_phase() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/scenarios/binary_moons.toml");
+    let scenario = Scenario::load(std::path::Path::new(path)).unwrap();
+
+    let mut results = Vec::new();
+    for kind in kinds() {
+        let mut scenario = scenario.clone();
+        scenario.physics.broad_phase = kind;
+        let mut world = World::new(scenario);
+        for _ in 0..300 {
+            world.step(world.physics.dt);
+        }
+        let positions: Vec<Vec2> = world.balls.values().map(|ball| ball.position).collect();
+        results.push(positions);
+    }
+
+    for positions in &results[1..] {
+        assert_eq!(positions, &results[0]);
+    }
+}
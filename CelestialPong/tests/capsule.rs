@@ -0,0 +1,279 @@
+use celestial_pong::capsule::{closest_points, segments_distance_squared, Capsule};
+use celestial_pong::quad_tree::Rect;
+use celestial_pong::scenario::Scenario;
+use celestial_pong::world::{World, WorldEvent};
+use macroquad::prelude::*;
+
+// A single ball flying right towards a vertical wall, without any gravity source.
+// Both share the material, contacts use the highest restitution of the two.
+fn wall_world(speed: f32, restitution: f32, continuous: bool) -> World {
+    let scenario = Scenario::parse(&format!(
+        r#"
+        [physics]
+        continuous_collisions = {continuous}
+
+        [materials.wall]
+        restitution = {restitution}
+
+        [[capsules]]
+        p1 = [100.0, -50.0]
+        p2 = [100.0, 50.0]
+        radius = 2.0
+        material = "wall"
+
+        [[balls]]
+        kind = "explicit"
+        position = [0.0, 0.0]
+        velocity = [{speed}, 0.0]
+        radius = 5.0
+        mass = 1.0
+        material = "wall"
+        "#
+    ))
+    .unwrap();
+    World::new(scenario)
+}
+
+fn run(world: &mut World, steps: usize) -> Vec<WorldEvent> {
+    let mut events = Vec::new();
+    for _ in 0..steps {
+        world.step(world.physics.dt);
+        events.extend(world.drain_events());
+    }
+    events
+}
+
+fn ball(world: &World) -> celestial_pong::ball::Ball {
+    *world.balls.values().next().unwrap()
+}
+
+#[test]
+fn balls_bounce_off_walls_with_restitution() {
+    for continuous in [false, true] {
+        let mut world = wall_world(200., 0.5, continuous);
+        let events = run(&mut world, 120);
+
+        let ball = ball(&world);
+        assert!((ball.velocity.x + 100.).abs() < 1e-3, "{:?}", ball.velocity);
+        assert!(ball.velocity.y.abs() < 1e-3);
+        assert!(ball.position.x < 100. - 2. - 5.);
+        assert_eq!(events.len(), 1);
+        assert!(matches!(
+            events[0],
+            WorldEvent::BouncedOffCapsule { capsule: 0, speed, .. } if (speed - 200.).abs() < 1e-3
+        ));
+    }
+}
+
+#[test]
+fn fast_balls_do_not_tunnel_through_walls() {
+    // Moves 50 units per step, the wall is 4 thick
+    let mut world = wall_world(6000., 1., true);
+    run(&mut world, 10);
+    let ball = ball(&world);
+    assert!(ball.velocity.x < 0.);
+    assert!(ball.position.x < 100.);
+}
+
+#[test]
+fn moving_capsules_push_balls() {
+    let scenario = Scenario::parse(
+        r#"
+        [[capsules]]
+        p1 = [-50.0, 0.0]
+        p2 = [50.0, 0.0]
+        radius = 5.0
+        velocity = [0.0, -100.0]
+
+        [[balls]]
+        kind = "explicit"
+        position = [10.0, -100.0]
+        velocity = [0.0, 0.0]
+        radius = 5.0
+        mass = 1.0
+        "#,
+    )
+    .unwrap();
+    let mut world = World::new(scenario);
+    let events = run(&mut world, 120);
+
+    // An elastic hit from an infinitely heavy paddle sends the ball at twice its speed
+    let ball = ball(&world);
+    assert!((ball.velocity.y + 200.).abs() < 1e-2, "{:?}", ball.velocity);
+    assert_eq!(events.len(), 1);
+    let capsule = world.capsules[0];
+    assert!(capsule.contact(&ball).is_none());
+    assert!((capsule.p1.y + 100.).abs() < 1e-3);
+}
+
+#[test]
+fn fast_capsules_hit_balls_when_they_reach_them() {
+    // Moves 50 units per step, further than the 10 units of reach
+    let scenario = Scenario::parse(
+        r#"
+        [[capsules]]
+        p1 = [-50.0, 0.0]
+        p2 = [50.0, 0.0]
+        radius = 5.0
+        velocity = [0.0, -6000.0]
+
+        [[balls]]
+        kind = "explicit"
+        position = [10.0, -130.0]
+        velocity = [0.0, 0.0]
+        radius = 5.0
+        mass = 1.0
+        "#,
+    )
+    .unwrap();
+    let mut world = World::new(scenario);
+
+    // 70 units apart after two steps, still out of reach
+    assert!(run(&mut world, 2).is_empty());
+    assert_eq!(ball(&world).velocity, Vec2::ZERO);
+
+    // Touched 40% into the third step, then sent away at twice the capsule speed
+    let events = run(&mut world, 1);
+    assert_eq!(events.len(), 1);
+    let ball = ball(&world);
+    assert!((ball.velocity.y + 12000.).abs() < 1., "{:?}", ball.velocity);
+    let capsule = world.capsules[0];
+    assert!(ball.position.y < capsule.p1.y - 10., "{:?}", ball.position);
+}
+
+fn close(a: Vec2, b: Vec2) -> bool {
+    a.distance(b) < 1e-4
+}
+
+#[test]
+fn closest_points_between_segments() {
+    // Crossing segments touch where they cross
+    let points = closest_points(
+        Vec2::new(-1., 0.),
+        Vec2::new(1., 0.),
+        Vec2::new(0.5, -1.),
+        Vec2::new(0.5, 3.),
+    );
+    assert!((points.s - 0.75).abs() < 1e-5 && (points.t - 0.25).abs() < 1e-5);
+    assert!(close(points.c1, Vec2::new(0.5, 0.)) && close(points.c2, points.c1));
+    assert_eq!(points.distance_squared(), 0.);
+
+    // Past the end of one of them
+    let points = closest_points(
+        Vec2::ZERO,
+        Vec2::new(2., 0.),
+        Vec2::new(3., 1.),
+        Vec2::new(5., 4.),
+    );
+    assert_eq!((points.s, points.t), (1., 0.));
+    assert!((points.distance_squared() - 2.).abs() < 1e-5);
+
+    // Parallel segments overlapping along x: any pair across is closest, s is 0
+    let points = closest_points(
+        Vec2::ZERO,
+        Vec2::new(4., 0.),
+        Vec2::new(-2., 3.),
+        Vec2::new(2., 3.),
+    );
+    assert_eq!(points.s, 0.);
+    assert!(close(points.c1, Vec2::ZERO) && close(points.c2, Vec2::new(0., 3.)));
+    assert!((points.distance_squared() - 9.).abs() < 1e-5);
+
+    // Parallel segments following each other, the gap is between the facing ends
+    let distance =
+        segments_distance_squared(Vec2::ZERO, Vec2::X, Vec2::new(3., 0.), Vec2::new(5., 0.));
+    assert!((distance - 4.).abs() < 1e-5);
+}
+
+#[test]
+fn closest_points_of_degenerate_segments() {
+    let point = Vec2::new(1., 2.);
+    let segment = (Vec2::new(-2., 0.), Vec2::new(2., 0.));
+
+    // Both are points
+    let points = closest_points(point, point, Vec2::ZERO, Vec2::ZERO);
+    assert_eq!((points.s, points.t), (0., 0.));
+    assert_eq!(points.distance_squared(), 5.);
+
+    // The first one is a point
+    let points = closest_points(point, point, segment.0, segment.1);
+    assert_eq!(points.s, 0.);
+    assert!((points.t - 0.75).abs() < 1e-5);
+    assert!(close(points.c2, Vec2::new(1., 0.)));
+
+    // The second one is a point, beyond the end of the first
+    let points = closest_points(segment.0, segment.1, Vec2::new(4., 0.), Vec2::new(4., 0.));
+    assert_eq!((points.s, points.t), (1., 0.));
+    assert!(close(points.c1, segment.1));
+    assert!((points.distance_squared() - 4.).abs() < 1e-5);
+}
+
+#[test]
+fn capsule_point_circle_and_rect_tests() {
+    let capsule = Capsule::new(Vec2::new(-10., 0.), Vec2::new(10., 0.), 2., WHITE);
+    assert!(capsule.contains_point(Vec2::new(0., 1.9)));
+    assert!(capsule.contains_point(Vec2::new(-11., 1.)));
+    assert!(!capsule.contains_point(Vec2::new(0., 2.1)));
+    // Outside the rounded end although within the bounds
+    assert!(!capsule.contains_point(Vec2::new(11.9, 1.9)));
+
+    assert!(capsule.overlaps_circle(Vec2::new(0., 5.), 3.));
+    assert!(!capsule.overlaps_circle(Vec2::new(0., 5.), 2.9));
+    assert!(capsule.overlaps_circle(Vec2::new(13., 0.), 1.));
+
+    let bounds = capsule.bounds();
+    assert_eq!(
+        (bounds.left, bounds.right, bounds.up, bounds.down),
+        (-12., 12., -2., 2.)
+    );
+
+    assert!(capsule.overlaps_rect(&Rect::new(0., 0., 1., 1.)));
+    assert!(capsule.overlaps_rect(&Rect::from_edges(-1., 1., 1.9, 4.)));
+    assert!(!capsule.overlaps_rect(&Rect::from_edges(-1., 1., 2.1, 4.)));
+    // Containing the whole capsule
+    assert!(capsule.overlaps_rect(&Rect::new(0., 0., 100., 100.)));
+    // A corner near the rounded end
+    assert!(!capsule.overlaps_rect(&Rect::from_edges(11.5, 20., 1.5, 20.)));
+
+    // A capsule of length 0 is a circle
+    let circle = Capsule::new(Vec2::new(5., 5.), Vec2::new(5., 5.), 1., WHITE);
+    assert!(circle.contains_point(Vec2::new(5.5, 5.5)));
+    assert!(!circle.contains_point(Vec2::new(5.8, 5.8)));
+    assert!(circle.overlaps_circle(Vec2::new(7., 5.), 1.));
+    assert!(circle.overlaps_rect(&Rect::from_edges(5.9, 8., 0., 10.)));
+    assert!(!circle.overlaps_rect(&Rect::from_edges(5.75, 8., 5.75, 10.)));
+}
+
+#[test]
+fn rays_hit_capsules() {
+    let capsule = Capsule::new(Vec2::new(-10., 0.), Vec2::new(10., 0.), 2., WHITE);
+    let hit = |origin: Vec2, direction: Vec2, max_distance: f32| {
+        capsule.ray_distance(origin, direction, max_distance)
+    };
+
+    // The side, in multiples of the direction
+    let t = hit(Vec2::new(0., 10.), Vec2::new(0., -2.), 100.).unwrap();
+    assert!((t - 4.).abs() < 1e-5);
+    // A rounded end
+    let t = hit(Vec2::new(20., 0.), Vec2::new(-1., 0.), 100.).unwrap();
+    assert!((t - 8.).abs() < 1e-5);
+    let t = hit(Vec2::new(11., 10.), -Vec2::Y, 100.).unwrap();
+    assert!((t - (10. - 3f32.sqrt())).abs() < 1e-4);
+    // Along the length, parallel to the sides
+    let t = hit(Vec2::new(-20., 1.), Vec2::X, 100.).unwrap();
+    assert!((t - (10. - 3f32.sqrt())).abs() < 1e-4);
+
+    assert_eq!(hit(Vec2::new(0., 1.), Vec2::Y, 100.), Some(0.));
+    assert_eq!(hit(Vec2::new(0., 10.), Vec2::Y, 100.), None);
+    assert_eq!(hit(Vec2::new(0., 10.), -Vec2::Y, 7.), None);
+    assert_eq!(hit(Vec2::new(13., 10.), -Vec2::Y, 100.), None);
+    assert_eq!(hit(Vec2::new(0., 10.), Vec2::ZERO, 100.), None);
+
+    // A capsule of length 0 is a circle
+    let circle = Capsule::new(Vec2::ZERO, Vec2::ZERO, 1., WHITE);
+    let t = circle
+        .ray_distance(Vec2::new(-5., 0.), Vec2::X, 10.)
+        .unwrap();
+    assert!((t - 4.).abs() < 1e-5);
+    assert_eq!(circle.ray_distance(Vec2::new(-5., 2.), Vec2::X, 10.), None);
+}
@@ -0,0 +1,145 @@
+use celestial_pong::scenario::Scenario;
+use celestial_pong::world::{World, WorldEvent};
+use macroquad::prelude::*;
+
+// `extra` follows the physics table, gravity is too weak to matter unless it sets it
+fn world(continuous: bool, extra: &str) -> World {
+    let scenario = Scenario::parse(&format!(
+        r#"
+        [physics]
+        continuous_collisions = {continuous}
+        {extra}
+        "#
+    ))
+    .unwrap();
+    World::new(scenario)
+}
+
+fn ball(position: [f32; 2], velocity: [f32; 2]) -> String {
+    format!(
+        r#"
+        [[balls]]
+        kind = "explicit"
+        position = {position:?}
+        velocity = {velocity:?}
+        radius = 5.0
+        mass = 1.0
+        "#
+    )
+}
+
+const BODY: &str = r#"
+[[static_bodies]]
+radius = 20.0
+mass = 1000.0
+contact = { kind = "bounce" }
+"#;
+
+// 100 units per step, from 55 units before a resting ball to 45 units past it
+fn ball_pair(continuous: bool) -> World {
+    let balls = ball([-55., 0.], [12000., 0.]) + &ball([0., 0.], [0., 0.]);
+    world(continuous, &format!("gravity = 0.001\n{}", balls))
+}
+
+#[test]
+fn fast_balls_hit_other_balls() {
+    let mut tunneling = ball_pair(false);
+    let dt = tunneling.physics.dt;
+    tunneling.step(dt);
+    let balls: Vec<_> = tunneling.balls.values().copied().collect();
+    assert!(
+        (balls[0].position.x - 45.).abs() < 1e-2,
+        "{}",
+        balls[0].position
+    );
+    assert_eq!(balls[1].velocity, Vec2::ZERO);
+
+    // They touch 45% into the step, then the resting ball takes all the speed
+    let mut world = ball_pair(true);
+    world.step(dt);
+    let balls: Vec<_> = world.balls.values().copied().collect();
+    assert!(
+        (balls[0].position.x + 10.).abs() < 1e-2,
+        "{}",
+        balls[0].position
+    );
+    assert!(balls[0].velocity.length() < 1e-2, "{}", balls[0].velocity);
+    assert!(
+        (balls[1].position.x - 55.).abs() < 1e-2,
+        "{}",
+        balls[1].position
+    );
+    assert!(
+        (balls[1].velocity.x - 12000.).abs() < 1.,
+        "{}",
+        balls[1].velocity
+    );
+}
+
+// 120 units per step, from 55 units before the surface of a body to 15 units past its
+// center
+fn ball_and_body(continuous: bool) -> World {
+    let scenario = format!(
+        "gravity = 0.001\n{}{}",
+        BODY,
+        ball([-80., 0.], [14400., 0.])
+    );
+    world(continuous, &scenario)
+}
+
+#[test]
+fn fast_balls_hit_bodies() {
+    let mut tunneling = ball_and_body(false);
+    let dt = tunneling.physics.dt;
+    tunneling.step(dt);
+    let ball = *tunneling.balls.values().next().unwrap();
+    assert!((ball.position.x - 40.).abs() < 1e-2, "{}", ball.position);
+    assert_eq!(tunneling.drain_events().count(), 0);
+
+    // Bounces back 55 / 120 into the step, at the surface
+    let mut world = ball_and_body(true);
+    world.step(dt);
+    let events: Vec<WorldEvent> = world.drain_events().collect();
+    let [WorldEvent::Bounced { body: 0, speed, .. }] = events[..] else {
+        panic!("{:?}", events);
+    };
+    assert!((speed - 14400.).abs() < 1., "{}", speed);
+    let ball = *world.balls.values().next().unwrap();
+    assert!((ball.position.x + 90.).abs() < 1e-2, "{}", ball.position);
+    assert!((ball.velocity.x + 14400.).abs() < 1., "{}", ball.velocity);
+}
+
+#[test]
+fn overlaps_from_the_start_of_the_step_keep_the_integrated_path() {
+    // Under real gravity, so the path of a step is curved. Already touching when the step
+    // starts, the ball bounces where the integrator put it, as without sweeping.
+    let scenarios = [
+        format!("{}{}", BODY, ball([-23., 5.], [300., 200.])),
+        format!(
+            "{}{}{}",
+            BODY,
+            ball([-200., 0.], [300., 100.]),
+            ball([-192., 0.], [-100., 0.])
+        ),
+    ];
+    for scenario in scenarios {
+        let mut swept = world(true, &scenario);
+        let mut discrete = world(false, &scenario);
+        let dt = swept.physics.dt;
+        swept.step(dt);
+        discrete.step(dt);
+
+        let state = |world: &World| -> Vec<_> {
+            world
+                .balls
+                .values()
+                .map(|ball| (ball.position, ball.prev_position, ball.velocity))
+                .collect()
+        };
+        assert_eq!(state(&swept), state(&discrete));
+        assert_eq!(
+            swept.drain_events().count(),
+            discrete.drain_events().count()
+        );
+    }
+}
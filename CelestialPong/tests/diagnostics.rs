@@ -0,0 +1,158 @@
+use celestial_pong::diagnostics::{Diagnostics, DiagnosticsLog};
+use celestial_pong::scenario::Scenario;
+use celestial_pong::world::World;
+use macroquad::prelude::*;
+
+// A ball of mass 10 on a circular orbit of radius 200 around a moving star of mass 1000,
+// with the center of mass at rest
+fn two_bodies() -> World {
+    let gravity = 30000f32;
+    let relative_speed = (gravity * 1010. / 200.).sqrt();
+    let scenario = Scenario::parse(&format!(
+        r#"
+        [physics]
+        gravity = {gravity:?}
+        n_body = true
+        dynamic_bodies = true
+
+        [[static_bodies]]
+        velocity = [0.0, {:?}]
+        radius = 20.0
+        mass = 1000.0
+
+        [[balls]]
+        kind = "explicit"
+        position = [200.0, 0.0]
+        velocity = [0.0, {:?}]
+        radius = 5.0
+        mass = 10.0
+        "#,
+        -relative_speed * 10. / 1010.,
+        relative_speed * 1000. / 1010.,
+    ))
+    .unwrap();
+    World::new(scenario)
+}
+
+#[test]
+fn orbits_keep_their_energy_and_angular_momentum() {
+    let mut world = two_bodies();
+    world.diagnostics_log = Some(DiagnosticsLog::new());
+    let start = Diagnostics::measure(&world);
+    assert!(start.potential_energy < 0. && start.angular_momentum > 0.);
+    assert!(start.momentum.length() < 1e-3, "{}", start.momentum);
+
+    // About two turns
+    let dt = world.physics.dt;
+    for _ in 0..1200 {
+        world.step(dt);
+    }
+    let end = Diagnostics::measure(&world);
+    assert_eq!(end.step, 1200);
+
+    let log = world.diagnostics_log.as_ref().unwrap();
+    assert_eq!(log.samples.len(), 1200);
+    let drift = log.energy_drift().unwrap();
+    assert!(drift.abs() < 1e-3, "{}", drift);
+    let angular_drift = (end.angular_momentum - start.angular_momentum) / start.angular_momentum;
+    assert!(angular_drift.abs() < 1e-3, "{}", angular_drift);
+    let momentum = (end.momentum - start.momentum).length();
+    assert!(momentum < 1e-4 * 10. * 385., "{}", momentum);
+}
+
+#[test]
+fn elastic_collisions_keep_the_momentum() {
+    let scenario = Scenario::parse(
+        r#"
+        [physics]
+        gravity = 0.001
+
+        [materials.rubber]
+        restitution = 1.0
+
+        [[balls]]
+        kind = "explicit"
+        position = [-50.0, 0.0]
+        velocity = [300.0, 20.0]
+        radius = 5.0
+        mass = 1.0
+        material = "rubber"
+
+        [[balls]]
+        kind = "explicit"
+        position = [50.0, 0.0]
+        velocity = [-100.0, 0.0]
+        radius = 10.0
+        mass = 4.0
+        material = "rubber"
+        "#,
+    )
+    .unwrap();
+    let mut world = World::new(scenario);
+    let before = Diagnostics::measure(&world);
+    let first_velocity = world.balls.values().next().unwrap().velocity;
+
+    let dt = world.physics.dt;
+    for _ in 0..60 {
+        world.step(dt);
+    }
+    // They did collide
+    assert!(world.balls.values().next().unwrap().velocity.x < 0.);
+    assert_ne!(
+        world.balls.values().next().unwrap().velocity,
+        first_velocity
+    );
+
+    let after = Diagnostics::measure(&world);
+    let scale = 300. + 4. * 100.;
+    assert!((after.momentum - before.momentum).length() < 1e-4 * scale);
+    let energy = (after.kinetic_energy - before.kinetic_energy) / before.kinetic_energy;
+    assert!(energy.abs() < 1e-4, "{}", energy);
+}
+
+fn sample(step: u64, kinetic_energy: f64, potential_energy: f64) -> Diagnostics {
+    Diagnostics {
+        step,
+        kinetic_energy,
+        potential_energy,
+        momentum: DVec2::new(1., -2.),
+        angular_momentum: 3.,
+    }
+}
+
+#[test]
+fn energy_drift_compares_the_ends() {
+    let mut log = DiagnosticsLog::new();
+    assert_eq!(log.energy_drift(), None);
+
+    // Total energy going from -10 to -12, through anything
+    log.record(sample(0, 10., -20.));
+    log.record(sample(1, 50., -20.));
+    log.record(sample(2, 8., -20.));
+    assert_eq!(log.energy_drift(), Some(-0.2));
+
+    // No drift relative to a total energy of 0
+    let mut log = DiagnosticsLog::new();
+    log.record(sample(0, 10., -10.));
+    log.record(sample(1, 11., -10.));
+    assert_eq!(log.energy_drift(), None);
+}
+
+#[test]
+fn csv_has_a_header_and_a_row_per_sample() {
+    let mut log = DiagnosticsLog::new();
+    for step in 0..5 {
+        log.record(sample(step, 1.5, -4.));
+    }
+    let mut csv = Vec::new();
+    log.write_csv(&mut csv).unwrap();
+    let csv = String::from_utf8(csv).unwrap();
+
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(lines.len(), 6);
+    assert_eq!(
+        lines[0],
+        "step,kinetic_energy,potential_energy,total_energy,momentum_x,momentum_y,angular_momentum"
+    );
+    assert_eq!(lines[3], "2,1.5,-4,-2.5,1,-2,3");
+}
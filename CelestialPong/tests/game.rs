@@ -0,0 +1,342 @@
+use celestial_pong::boundary::BoundaryPolicy;
+use celestial_pong::game::{Game, GamePhase};
+use celestial_pong::scenario::Scenario;
+use celestial_pong::world::World;
+
+// Two paddles guarding the left and right halves of a star, and a ball dropped
+// towards it from `position` with `velocity`
+fn match_world(rules: &str, boundary: &str, position: [f32; 2], velocity: [f32; 2]) -> World {
+    let scenario = Scenario::parse(&format!(
+        r#"
+        [physics]
+        boundary = "{boundary}"
+
+        [game]
+        serve_delay = 0.1
+        point_delay = 0.1
+        {rules}
+
+        [[static_bodies]]
+        radius = 30.0
+        mass = 1000.0
+
+        [[paddles]]
+        orbit = 200.0
+        angle = 180.0
+        arc = [90.0, 270.0]
+        length = 40.0
+        radius = 5.0
+        speed = 100.0
+
+        [[paddles]]
+        orbit = 200.0
+        angle = 0.0
+        arc = [-90.0, 90.0]
+        length = 40.0
+        radius = 5.0
+        speed = 100.0
+
+        [[balls]]
+        kind = "explicit"
+        position = {position:?}
+        velocity = {velocity:?}
+        radius = 5.0
+        mass = 1.0
+        "#
+    ))
+    .unwrap();
+    World::new(scenario)
+}
+
+fn step(game: &mut Game, world: &mut World) {
+    let dt = world.physics.dt;
+    game.step(world, dt);
+}
+
+// Steps until the phase is not `phase` any more, returns the new phase
+fn run_through(game: &mut Game, world: &mut World, phase: fn(&GamePhase) -> bool) -> GamePhase {
+    for _ in 0..10_000 {
+        if !phase(&game.phase) {
+            return game.phase;
+        }
+        step(game, world);
+        world.drain_events();
+    }
+    panic!("still in {:?}", game.phase);
+}
+
+fn serving(phase: &GamePhase) -> bool {
+    matches!(phase, GamePhase::Serve { .. })
+}
+
+fn rallying(phase: &GamePhase) -> bool {
+    *phase == GamePhase::Rally
+}
+
+fn point_scored(phase: &GamePhase) -> bool {
+    matches!(phase, GamePhase::PointScored { .. })
+}
+
+#[test]
+fn matches_go_through_every_phase() {
+    // Falls into the star through the top left, guarded by the first player
+    let mut world = match_world("lives = 2", "reflect", [-300., -300.], [150., 150.]);
+    let mut game = Game::for_world(&world);
+    assert_eq!(game.players(), 2);
+
+    // Nothing moves before the match starts, nor while serving
+    let start = world.balls.values().next().unwrap().position;
+    step(&mut game, &mut world);
+    assert_eq!(game.phase, GamePhase::Title);
+    game.start();
+    assert!(serving(&game.phase));
+    step(&mut game, &mut world);
+    assert_eq!(world.balls.values().next().unwrap().position, start);
+
+    assert_eq!(
+        run_through(&mut game, &mut world, serving),
+        GamePhase::Rally
+    );
+    let phase = run_through(&mut game, &mut world, rallying);
+    assert!(matches!(phase, GamePhase::PointScored { against: 0, .. }));
+    assert_eq!(game.conceded, vec![1, 0]);
+    assert_eq!((game.score(0), game.score(1)), (0, 1));
+    assert_eq!(game.lives_left(0), Some(1));
+    assert!(game.duration > 0.);
+
+    // The only ball was lost, the next rally starts with a fresh world
+    assert!(serving(&run_through(&mut game, &mut world, point_scored)));
+    run_through(&mut game, &mut world, serving);
+    assert_eq!(world.balls.len(), 1);
+    assert_eq!(world.balls.values().next().unwrap().position, start);
+
+    // Out of lives
+    run_through(&mut game, &mut world, rallying);
+    let phase = run_through(&mut game, &mut world, point_scored);
+    assert_eq!(phase, GamePhase::MatchOver { winner: Some(1) });
+    assert_eq!(game.score(1), 2);
+    step(&mut game, &mut world);
+    assert_eq!(game.phase, phase);
+
+    game.restart(&mut world);
+    assert_eq!(game.phase, GamePhase::Title);
+    assert_eq!(game.conceded, vec![0, 0]);
+}
+
+#[test]
+fn escaping_balls_score_against_the_sector_they_leave() {
+    // Flies out of the right side of the field, far from the paddle
+    let mut world = match_world("", "despawn", [300., 1000.], [2000., 0.]);
+    let mut game = Game::for_world(&world);
+    game.start();
+    run_through(&mut game, &mut world, serving);
+    let phase = run_through(&mut game, &mut world, rallying);
+    assert!(matches!(phase, GamePhase::PointScored { against: 1, .. }));
+    assert_eq!(game.conceded, vec![0, 1]);
+    assert!(world.balls.is_empty());
+
+    // Balls leaving an unbounded world are taken out of play
+    let mut world = match_world("", "unbounded", [300., 1000.], [2000., 0.]);
+    let mut game = Game::for_world(&world);
+    game.start();
+    run_through(&mut game, &mut world, serving);
+    run_through(&mut game, &mut world, rallying);
+    assert_eq!(game.conceded, vec![0, 1]);
+    assert_eq!(world.balls_in_play(), 0);
+}
+
+#[test]
+fn scoring_rules_can_be_turned_off() {
+    let mut world = match_world(
+        "absorb_scores = false",
+        "reflect",
+        [-300., -300.],
+        [150., 150.],
+    );
+    let mut game = Game::for_world(&world);
+    game.start();
+    run_through(&mut game, &mut world, serving);
+    for _ in 0..1000 {
+        step(&mut game, &mut world);
+    }
+    assert!(world.balls.is_empty());
+    assert_eq!(game.phase, GamePhase::Rally);
+    assert_eq!(game.conceded, vec![0, 0]);
+}
+
+#[test]
+fn matches_end_after_their_points() {
+    // Without paddles, a single player loses every ball
+    let scenario = Scenario::parse(
+        r#"
+        [game]
+        match_points = 1
+        serve_delay = 0.0
+        point_delay = 0.0
+
+        [[static_bodies]]
+        radius = 30.0
+        mass = 1000.0
+
+        [[balls]]
+        kind = "explicit"
+        position = [100.0, 0.0]
+        velocity = [0.0, 0.0]
+        radius = 5.0
+        mass = 1.0
+        "#,
+    )
+    .unwrap();
+    let mut world = World::new(scenario);
+    let mut game = Game::for_world(&world);
+    assert_eq!(game.players(), 1);
+    game.start();
+    run_through(&mut game, &mut world, serving);
+    run_through(&mut game, &mut world, rallying);
+    let phase = run_through(&mut game, &mut world, point_scored);
+    assert_eq!(phase, GamePhase::MatchOver { winner: None });
+    assert_eq!(game.conceded, vec![1]);
+}
+
+#[test]
+fn countdowns_follow_the_frame_time() {
+    let frame_time = 1. / 60.;
+    let mut countdowns = Vec::new();
+    for steps in [1, 8] {
+        // Falling straight into the star
+        let mut world = match_world("", "reflect", [0., -300.], [0., 0.]);
+        let dt = world.physics.dt;
+        let mut game = Game::for_world(&world);
+        game.start();
+
+        // Serves and points last as many frames whatever the simulation speed
+        let mut frames = 0;
+        while serving(&game.phase) {
+            game.frame(&mut world, frame_time, steps, dt, |_| {});
+            frames += 1;
+        }
+        countdowns.push(frames);
+
+        // Rallies run every sub-step
+        let (start, mut calls) = (world.step_count, 0);
+        game.frame(&mut world, frame_time, steps, dt, |_| calls += 1);
+        assert_eq!(calls, steps);
+        assert_eq!(world.step_count - start, steps as u64);
+
+        while rallying(&game.phase) {
+            game.frame(&mut world, frame_time, steps, dt, |_| {});
+        }
+        assert!(point_scored(&game.phase));
+        let mut frames = 0;
+        while point_scored(&game.phase) {
+            game.frame(&mut world, frame_time, steps, dt, |_| {});
+            frames += 1;
+        }
+        countdowns.push(frames);
+    }
+    // 0.1 seconds are 6 frames, give or take rounding
+    assert_eq!(countdowns[..2], countdowns[2..]);
+    assert!(countdowns.iter().all(|frames| (6..=7).contains(frames)));
+}
+
+#[test]
+fn game_rules_are_checked() {
+    for (rules, field) in [
+        ("lives = 0", "game.lives"),
+        ("match_points = 0", "game.match_points"),
+        ("serve_delay = -1.0", "game.serve_delay"),
+        ("point_delay = nan", "game.point_delay"),
+    ] {
+        let err = Scenario::parse(&format!("[game]\n{}", rules)).unwrap_err();
+        assert!(err.to_string().contains(field), "{}", err);
+    }
+}
+
+#[test]
+fn wiped_out_fields_get_new_balls() {
+    let scenario = Scenario::parse(
+        r#"
+        [game]
+        serve_delay = 0.1
+
+        [[static_bodies]]
+        radius = 30.0
+        mass = 1000.0
+
+        [[balls]]
+        kind = "orbital"
+        count = 3
+        min_orbit = 100.0
+        max_orbit = 300.0
+        radius = 5.0
+        mass = 1.0
+        "#,
+    )
+    .unwrap();
+    let mut world = World::new(scenario);
+    let mut game = Game::for_world(&world);
+    let positions =
+        |world: &World| -> Vec<_> { world.balls.values().map(|ball| ball.position).collect() };
+
+    let mut rallies = vec![positions(&world)];
+    for _ in 0..2 {
+        let ids: Vec<_> = world.balls.ids().collect();
+        for id in ids {
+            world.despawn(id);
+        }
+        world.step(world.physics.dt);
+        assert!(world.balls.is_empty());
+
+        game.phase = GamePhase::Serve { remaining: 0.1 };
+        run_through(&mut game, &mut world, serving);
+        assert_eq!(world.balls.len(), 3);
+        rallies.push(positions(&world));
+    }
+    // Every rally plays out differently
+    assert_ne!(rallies[1], rallies[0]);
+    assert_ne!(rallies[2], rallies[1]);
+}
+
+#[test]
+fn bundled_games_let_balls_escape() {
+    let escapes = |boundary| {
+        matches!(
+            boundary,
+            BoundaryPolicy::Despawn | BoundaryPolicy::Unbounded
+        )
+    };
+    assert!(escapes(Scenario::default().physics.boundary));
+    for name in ["default", "paddles"] {
+        let path = format!("{}/scenarios/{}.toml", env!("CARGO_MANIFEST_DIR"), name);
+        let scenario = Scenario::load(std::path::Path::new(&path)).unwrap();
+        assert!(scenario.game.escape_scores);
+        assert!(escapes(scenario.physics.boundary), "{}", name);
+    }
+}
+
+#[test]
+fn worlds_run_freely_before_the_match() {
+    // Falling straight into the star
+    let mut world = match_world("", "despawn", [0., -300.], [0., 0.]);
+    let dt = world.physics.dt;
+    let mut game = Game::for_world(&world);
+
+    let mut calls = 0;
+    let mut absorbed = 0;
+    for _ in 0..100 {
+        game.frame(&mut world, 1. / 60., 4, dt, |_| calls += 1);
+        absorbed += world.drain_events().count();
+    }
+    assert_eq!(calls, 400);
+    assert_eq!(world.step_count, 400);
+    // The ball fell in without scoring or stopping anything
+    assert_eq!(absorbed, 1);
+    assert!(world.balls.is_empty());
+    assert_eq!(game.phase, GamePhase::Title);
+    assert_eq!(game.conceded, vec![0, 0]);
+
+    // Starting the match serves new balls
+    game.start();
+    run_through(&mut game, &mut world, serving);
+    assert_eq!(world.balls.len(), 1);
+}
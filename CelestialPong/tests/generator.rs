@@ -0,0 +1,262 @@
+use ::rand::SeedableRng;
+use celestial_pong::ball::Ball;
+use celestial_pong::generator::OrbitDistribution;
+use celestial_pong::orbit::{orbit_state, OrbitKind, OrbitalElements};
+use celestial_pong::scenario::{BallPopulation, Scenario};
+use celestial_pong::static_body::{ContactPolicy, StaticBody};
+use celestial_pong::world::World;
+use macroquad::prelude::*;
+use rand_chacha::ChaCha20Rng;
+
+const GRAVITY: f32 = 30000.;
+
+// A star moving down, and a planet going around it
+fn bodies() -> Vec<StaticBody> {
+    let body = |position: Vec2, velocity: Vec2, mass: f32| {
+        StaticBody::new(
+            Ball::new(position, velocity, 10., mass, WHITE),
+            ContactPolicy::default(),
+        )
+    };
+    vec![
+        body(vec2(100., 50.), vec2(0., -5.), 1000.),
+        body(vec2(700., 50.), vec2(0., 220.), 30.),
+    ]
+}
+
+// Orbits of `count` balls drawn from `orbits` around the star
+fn sample(orbits: &OrbitDistribution, count: usize) -> Vec<(Vec2, OrbitalElements)> {
+    let bodies = bodies();
+    let mut rng = ChaCha20Rng::seed_from_u64(1);
+    (0..count)
+        .map(|_| {
+            let (position, velocity) = orbits.sample(&bodies, 0, GRAVITY, &mut rng);
+            let ball = Ball::new(position, velocity, 1., 1., WHITE);
+            let elements = OrbitalElements::new(&ball, &bodies[0].ball, GRAVITY);
+            (position - bodies[0].ball.position, elements)
+        })
+        .collect()
+}
+
+#[test]
+fn states_give_back_their_elements() {
+    let mu = GRAVITY * 1000.;
+    for (semi_major_axis, eccentricity) in [(300., 0.), (300., 0.5), (200., 2.)] {
+        for retrograde in [false, true] {
+            let (position, velocity) =
+                orbit_state(mu, semi_major_axis, eccentricity, 1., -0.5, retrograde);
+            let ball = Ball::new(position, velocity, 1., 1., WHITE);
+            let star = Ball::new(Vec2::ZERO, Vec2::ZERO, 10., 1000., WHITE);
+            let elements = OrbitalElements::new(&ball, &star, GRAVITY);
+
+            let expected = match eccentricity > 1. {
+                true => -semi_major_axis,
+                false => semi_major_axis,
+            };
+            assert!((elements.semi_major_axis - expected).abs() < 0.5);
+            assert!((elements.eccentricity - eccentricity).abs() < 1e-3);
+            if eccentricity > 0. {
+                assert!((elements.argument_of_periapsis - 1.).abs() < 1e-3);
+            }
+            assert_eq!(elements.specific_angular_momentum < 0., retrograde);
+        }
+    }
+
+    // Past the asymptotes of a hyperbola, the ball is put back on it
+    let (position, _) = orbit_state(mu, 200., 2., 0., 3., false);
+    assert!(position.is_finite() && position.x < 0.);
+}
+
+#[test]
+fn ranges_bound_the_elements() {
+    let orbits = OrbitDistribution::Ranges {
+        semi_major_axis: [200., 300.],
+        eccentricity: [0.1, 0.4],
+        argument_of_periapsis: [30., 60.],
+        true_anomaly: [0., 360.],
+        retrograde: 0.5,
+    };
+    let orbits = sample(&orbits, 200);
+    for (_, elements) in &orbits {
+        assert!((199.0..301.0).contains(&elements.semi_major_axis));
+        assert!((0.099..0.401).contains(&elements.eccentricity));
+        let argument = elements.argument_of_periapsis.to_degrees();
+        assert!((29.9..60.1).contains(&argument), "{}", argument);
+    }
+    let retrograde = orbits
+        .iter()
+        .filter(|(_, elements)| elements.specific_angular_momentum < 0.)
+        .count();
+    assert!((70..130).contains(&retrograde), "{}", retrograde);
+}
+
+#[test]
+fn presets_look_like_their_names() {
+    let belt = OrbitDistribution::AsteroidBelt {
+        inner: 250.,
+        outer: 400.,
+        max_eccentricity: 0.1,
+    };
+    for (_, elements) in sample(&belt, 100) {
+        assert_eq!(elements.kind, OrbitKind::Bound);
+        assert!((249.0..401.0).contains(&elements.semi_major_axis));
+        assert!(elements.eccentricity < 0.101);
+        assert!(elements.specific_angular_momentum > 0.);
+    }
+
+    let ring = OrbitDistribution::Ring {
+        inner: 100.,
+        outer: 120.,
+    };
+    for (position, elements) in sample(&ring, 100) {
+        assert!(elements.eccentricity < 1e-3);
+        assert!((99.9..120.1).contains(&position.length()));
+    }
+
+    let comets = OrbitDistribution::CometSwarm {
+        periapsis: [100., 200.],
+        eccentricity: [1.1, 1.5],
+        distance: [1200., 1600.],
+        retrograde: 0.5,
+    };
+    for (position, elements) in sample(&comets, 100) {
+        assert_eq!(elements.kind, OrbitKind::Escape);
+        assert!((99.0..201.0).contains(&elements.periapsis));
+        assert!((1.099..1.501).contains(&elements.eccentricity));
+        assert!((1199.0..1601.0).contains(&position.length()));
+    }
+
+    // The planet is at 0 degrees from the star and turns towards increasing angles
+    let trojans = OrbitDistribution::Trojans {
+        planet: 1,
+        spread: 5.,
+        width: 20.,
+        retrograde: None,
+    };
+    let orbits = sample(&trojans, 100);
+    let leading = orbits
+        .iter()
+        .filter(|(position, _)| position.y > 0.)
+        .count();
+    assert!((30..70).contains(&leading), "{}", leading);
+    for (position, elements) in orbits {
+        let angle = position.y.atan2(position.x).to_degrees().abs();
+        assert!((54.9..65.1).contains(&angle), "{}", angle);
+        assert!((589.9..610.1).contains(&position.length()));
+        assert!(elements.eccentricity < 0.05);
+        assert!(elements.specific_angular_momentum > 0.);
+    }
+
+    // Told to turn the other way, whatever the planet does
+    let retrograde = OrbitDistribution::Trojans {
+        planet: 1,
+        spread: 5.,
+        width: 20.,
+        retrograde: Some(true),
+    };
+    for (position, elements) in sample(&retrograde, 100) {
+        let angle = position.y.atan2(position.x).to_degrees().abs();
+        assert!((54.9..65.1).contains(&angle), "{}", angle);
+        assert!(elements.specific_angular_momentum < 0.);
+    }
+}
+
+#[test]
+fn scenarios_generate_balls_from_elements() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/scenarios/solar_system.toml");
+    let scenario = Scenario::load(std::path::Path::new(path)).unwrap();
+    let expected: usize = scenario
+        .balls
+        .iter()
+        .map(|population| match population {
+            BallPopulation::Elements { count, .. } => *count,
+            _ => 0,
+        })
+        .sum();
+    let world = World::new(scenario.clone());
+    assert_eq!(world.balls.len(), expected);
+
+    // Same seed, same balls
+    let again = World::new(scenario);
+    let positions =
+        |world: &World| -> Vec<Vec2> { world.balls.values().map(|ball| ball.position).collect() };
+    assert_eq!(positions(&again), positions(&world));
+
+    let population = |orbits: &str| {
+        Scenario::parse(&format!(
+            "[[static_bodies]]\nradius = 10.0\nmass = 1.0\n\n\
+             [[balls]]\nkind = \"elements\"\ncount = 1\nradius = 1.0\nmass = 1.0\norbits = {}\n",
+            orbits
+        ))
+    };
+    assert!(population(r#"{ kind = "ranges", semi_major_axis = [100.0, 200.0] }"#).is_ok());
+    assert!(population(
+        r#"{ kind = "ranges", semi_major_axis = [100.0, 200.0], eccentricity = [1.01, 1.5] }"#
+    )
+    .is_ok());
+    for (orbits, field) in [
+        (
+            r#"{ kind = "ranges", semi_major_axis = [100.0, 200.0], eccentricity = [0.5, 1.5] }"#,
+            "balls[0].orbits.eccentricity",
+        ),
+        // Parabolas, which have no semi-major axis
+        (
+            r#"{ kind = "ranges", semi_major_axis = [100.0, 200.0], eccentricity = [0.5, 1.0] }"#,
+            "balls[0].orbits.eccentricity",
+        ),
+        (
+            r#"{ kind = "ranges", semi_major_axis = [100.0, 200.0], eccentricity = [1.0, 1.5] }"#,
+            "balls[0].orbits.eccentricity",
+        ),
+        (
+            r#"{ kind = "comet_swarm", periapsis = [100.0, 200.0], eccentricity = [1.0, 1.5], distance = [1200.0, 1600.0] }"#,
+            "balls[0].orbits.eccentricity",
+        ),
+        (
+            r#"{ kind = "ranges", semi_major_axis = [200.0, 100.0] }"#,
+            "balls[0].orbits.semi_major_axis",
+        ),
+        (
+            r#"{ kind = "asteroid_belt", inner = 100.0, outer = 200.0, max_eccentricity = 1.0 }"#,
+            "balls[0].orbits.max_eccentricity",
+        ),
+        (
+            r#"{ kind = "comet_swarm", periapsis = [100.0, 200.0], distance = [150.0, 300.0] }"#,
+            "balls[0].orbits.distance",
+        ),
+        (
+            r#"{ kind = "trojans", planet = 0 }"#,
+            "balls[0].orbits.planet",
+        ),
+    ] {
+        let err = population(orbits).unwrap_err();
+        assert!(err.to_string().contains(field), "{}", err);
+    }
+
+    // Trojans only know which way to turn from a moving planet
+    let trojans = |physics: &str, orbits: &str| {
+        Scenario::parse(&format!(
+            "[physics]\n{}\n\n\
+             [[static_bodies]]\nradius = 10.0\nmass = 1.0\n\n\
+             [[static_bodies]]\nposition = [100.0, 0.0]\nradius = 1.0\nmass = 0.1\n\n\
+             [[balls]]\nkind = \"elements\"\ncount = 1\nradius = 1.0\nmass = 1.0\norbits = {}\n",
+            physics, orbits
+        ))
+    };
+    let err = trojans("", r#"{ kind = "trojans", planet = 1 }"#).unwrap_err();
+    assert!(
+        err.to_string().contains("balls[0].orbits.retrograde"),
+        "{}",
+        err
+    );
+    assert!(trojans(
+        "",
+        r#"{ kind = "trojans", planet = 1, retrograde = false }"#
+    )
+    .is_ok());
+    assert!(trojans(
+        "dynamic_bodies = true",
+        r#"{ kind = "trojans", planet = 1 }"#
+    )
+    .is_ok());
+}
@@ -0,0 +1,69 @@
+use celestial_pong::integrator::IntegratorKind;
+use celestial_pong::scenario::Scenario;
+use celestial_pong::world::World;
+use macroquad::prelude::*;
+
+const MU: f32 = 30000. * 1000.;
+
+// A ball on a circular orbit of radius 250 around a star at the origin
+fn circular_orbit() -> World {
+    let scenario = Scenario::parse(&format!(
+        r#"
+        [[static_bodies]]
+        radius = 30.0
+        mass = 1000.0
+
+        [[balls]]
+        kind = "explicit"
+        position = [250.0, 0.0]
+        velocity = [0.0, {}]
+        radius = 5.0
+        mass = 1.0
+        "#,
+        (MU / 250.).sqrt()
+    ))
+    .unwrap();
+    World::new(scenario)
+}
+
+fn specific_energy(world: &World) -> f32 {
+    let ball = world.balls.values().next().unwrap();
+    ball.velocity.length_squared() / 2. - MU / ball.position.length()
+}
+
+#[test]
+fn integrators_can_be_switched_mid_run() {
+    let mut world = circular_orbit();
+    let dt = world.physics.dt;
+    let energy = specific_energy(&world);
+    // Largest change of velocity in a step, from the pull of the star
+    let kick = MU / 250f32.powi(2) * dt;
+
+    for integrator in [
+        IntegratorKind::PositionVerlet,
+        IntegratorKind::Rk4,
+        IntegratorKind::PositionVerlet,
+        IntegratorKind::Rk4,
+    ] {
+        world.physics.integrator = integrator;
+        let before = *world.balls.values().next().unwrap();
+        world.step(dt);
+        let after = *world.balls.values().next().unwrap();
+
+        // Previous positions are consistent with the velocity whichever integrator ran
+        assert!(
+            after
+                .prev_position
+                .distance(after.position - after.velocity * dt)
+                < 1e-3
+        );
+        let jump = after.velocity.distance(before.velocity);
+        assert!(jump < kick * 1.5, "{:?}: {} {}", integrator, jump, kick);
+
+        for _ in 0..500 {
+            world.step(dt);
+        }
+        let drift = (specific_energy(&world) - energy).abs() / energy.abs();
+        assert!(drift < 1e-2, "{:?}: {}", integrator, drift);
+    }
+}
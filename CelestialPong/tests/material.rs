@@ -0,0 +1,115 @@
+use celestial_pong::material::{Contact, Material};
+use celestial_pong::scenario::Scenario;
+use celestial_pong::world::World;
+
+fn material(restitution: f32, friction: f32) -> Material {
+    Material {
+        restitution,
+        friction,
+        density: None,
+    }
+}
+
+#[test]
+fn contacts_combine_both_materials() {
+    let rubber = material(0.9, 1.);
+    let ice = material(0.2, 0.);
+    let wood = material(0.5, 0.25);
+
+    // The bounciest restitution, the geometric mean of the frictions
+    assert_eq!(
+        rubber.combine(&wood),
+        Contact {
+            restitution: 0.9,
+            friction: 0.5,
+        }
+    );
+    assert_eq!(wood.combine(&rubber), rubber.combine(&wood));
+    // Frictionless whatever touches it
+    assert_eq!(ice.combine(&rubber).friction, 0.);
+    assert_eq!(ice.combine(&wood).restitution, 0.5);
+}
+
+#[test]
+fn densities_give_masses() {
+    let dense = Material {
+        density: Some(2.),
+        ..Material::default()
+    };
+    assert_eq!(dense.mass(3.), Some(2. * std::f32::consts::PI * 9.));
+    assert_eq!(Material::default().mass(3.), None);
+
+    let scenario = Scenario::parse(
+        r#"
+        [materials.rock]
+        density = 0.5
+
+        [[static_bodies]]
+        radius = 20.0
+        material = "rock"
+
+        [[balls]]
+        kind = "explicit"
+        position = [100.0, 0.0]
+        radius = 2.0
+        material = "rock"
+
+        [[balls]]
+        kind = "explicit"
+        position = [-100.0, 0.0]
+        radius = 2.0
+        mass = 7.0
+        material = "rock"
+        "#,
+    )
+    .unwrap();
+    let world = World::new(scenario);
+    let area = |radius: f32| std::f32::consts::PI * radius * radius;
+    assert_eq!(world.static_bodies[0].ball.mass, 0.5 * area(20.));
+    let masses: Vec<f32> = world.balls.values().map(|ball| ball.mass).collect();
+    // An explicit mass wins over the density
+    assert_eq!(masses, vec![0.5 * area(2.), 7.]);
+    assert_eq!(
+        world.balls.values().next().unwrap().material.density,
+        Some(0.5)
+    );
+}
+
+#[test]
+fn materials_must_exist() {
+    let err = Scenario::parse(
+        r#"
+        [materials.rock]
+        density = 0.5
+
+        [[balls]]
+        kind = "explicit"
+        position = [100.0, 0.0]
+        radius = 2.0
+        mass = 1.0
+        material = "steel"
+        "#,
+    )
+    .unwrap_err();
+    let message = err.to_string();
+    assert!(message.contains("`balls[0].material`"), "{}", message);
+    assert!(message.contains("`steel`"), "{}", message);
+
+    // Nor can a mass come from a material without density
+    let err = Scenario::parse(
+        r#"
+        [materials.rock]
+        friction = 0.5
+
+        [[static_bodies]]
+        radius = 20.0
+        material = "rock"
+        "#,
+    )
+    .unwrap_err();
+    assert!(
+        err.to_string().contains("`static_bodies[0].mass`"),
+        "{}",
+        err
+    );
+}
@@ -0,0 +1,213 @@
+use celestial_pong::ball::Ball;
+use celestial_pong::diagnostics::Diagnostics;
+use celestial_pong::integrator::IntegratorKind;
+use celestial_pong::scenario::{BallPopulation, Scenario};
+use celestial_pong::world::World;
+use macroquad::prelude::*;
+
+// Two balls orbiting each other far from any static body, with unequal masses
+// so a force applied as an acceleration would show up as a momentum drift.
+const BINARY: &str = r#"
+[physics]
+n_body = true
+
+[[balls]]
+kind = "explicit"
+position = [-40.0, 0.0]
+velocity = [0.0, -54.8]
+radius = 5.0
+mass = 400.0
+
+[[balls]]
+kind = "explicit"
+position = [160.0, 0.0]
+velocity = [0.0, 219.1]
+radius = 5.0
+mass = 100.0
+"#;
+
+fn momentum(world: &World) -> DVec2 {
+    Diagnostics::measure(world).momentum
+}
+
+// Sum of the momentum magnitudes, used to express errors relative to the system
+fn momentum_scale(world: &World) -> f64 {
+    let balls = world.balls.values().map(|b| b.velocity.length() * b.mass);
+    let bodies = world
+        .static_bodies
+        .iter()
+        .map(|b| b.ball.velocity.length() * b.ball.mass);
+    balls.chain(bodies).sum::<f32>() as f64
+}
+
+fn run(scenario: Scenario, steps: usize) -> (DVec2, DVec2, World) {
+    let mut world = World::new(scenario);
+    let start = momentum(&world);
+    for _ in 0..steps {
+        world.step(world.physics.dt);
+    }
+    let end = momentum(&world);
+    (start, end, world)
+}
+
+#[test]
+fn binary_system_conserves_momentum() {
+    // Integrators evaluating the field only at the start of the step see the other
+    // bodies exactly where they are, multi-stage ones see them frozen
+    let single_evaluation = [
+        IntegratorKind::ExplicitEuler,
+        IntegratorKind::SemiImplicitEuler,
+        IntegratorKind::PositionVerlet,
+    ];
+
+    for integrator in single_evaluation {
+        let mut scenario = Scenario::parse(BINARY).unwrap();
+        scenario.physics.integrator = integrator;
+        let (start, end, world) = run(scenario, 2000);
+
+        assert_eq!(world.balls.len(), 2);
+        assert!(
+            (end - start).length() < 1e-4 * momentum_scale(&world),
+            "{:?}: momentum went from {} to {}",
+            integrator,
+            start,
+            end
+        );
+    }
+}
+
+#[test]
+fn binary_system_stays_bound() {
+    let (_, _, world) = run(Scenario::parse(BINARY).unwrap(), 2000);
+
+    let balls: Vec<&Ball> = world.balls.values().collect();
+    let distance = balls[0].position.distance(balls[1].position);
+    assert!(distance > 100. && distance < 400., "distance {}", distance);
+}
+
+#[test]
+fn pairwise_forces_are_opposite() {
+    let mut scenario = Scenario::parse(BINARY).unwrap();
+    scenario.physics.integrator = IntegratorKind::ExplicitEuler;
+    let mut world = World::new(scenario);
+    let before: Vec<Vec2> = world.balls.values().map(|b| b.velocity).collect();
+    world.step(world.physics.dt);
+
+    let balls: Vec<&Ball> = world.balls.values().collect();
+    let impulse_0 = (balls[0].velocity - before[0]) * balls[0].mass;
+    let impulse_1 = (balls[1].velocity - before[1]) * balls[1].mass;
+    assert!(impulse_0.length() > 0.);
+    assert!((impulse_0 + impulse_1).length() < 1e-4 * impulse_0.length());
+}
+
+#[test]
+fn dynamic_bodies_conserve_momentum() {
+    let mut scenario = Scenario::parse(
+        r#"
+        [physics]
+        n_body = true
+        dynamic_bodies = true
+
+        [[static_bodies]]
+        position = [0.0, 0.0]
+        radius = 10.0
+        mass = 100.0
+
+        [[balls]]
+        kind = "orbital"
+        count = 1
+        min_orbit = 200.0
+        max_orbit = 200.0
+        radius = 5.0
+        mass = 20.0
+        "#,
+    )
+    .unwrap();
+    for integrator in [
+        IntegratorKind::ExplicitEuler,
+        IntegratorKind::PositionVerlet,
+    ] {
+        scenario.physics.integrator = integrator;
+        let (start, end, world) = run(scenario.clone(), 2000);
+
+        assert!(world.static_bodies[0].ball.position.length() > 1.);
+        assert!(
+            (end - start).length() < 1e-3 * momentum_scale(&world),
+            "{:?}: momentum went from {} to {}",
+            integrator,
+            start,
+            end
+        );
+    }
+}
+
+#[test]
+fn static_bodies_do_not_move_by_default() {
+    let (_, _, world) = run(Scenario::default(), 500);
+
+    assert_eq!(world.static_bodies[0].ball.position, Vec2::ZERO);
+}
+
+#[test]
+fn gravity_does_not_depend_on_the_ball_mass() {
+    let scenario = Scenario::parse(
+        r#"
+        [[static_bodies]]
+        radius = 30.0
+        mass = 1000.0
+
+        [[balls]]
+        kind = "explicit"
+        position = [-200.0, 0.0]
+        velocity = [0.0, 0.0]
+        radius = 5.0
+        mass = 1.0
+
+        [[balls]]
+        kind = "explicit"
+        position = [200.0, 0.0]
+        velocity = [0.0, 0.0]
+        radius = 5.0
+        mass = 10.0
+        "#,
+    )
+    .unwrap();
+    let mut world = World::new(scenario);
+    let dt = world.physics.dt;
+    world.step(dt);
+    let balls: Vec<Ball> = world.balls.values().copied().collect();
+    assert_eq!(balls[0].position.x, -balls[1].position.x);
+    assert_eq!(balls[0].velocity.x, -balls[1].velocity.x);
+    // Pulled at G * M / r², 750 units per second squared
+    let acceleration = balls[0].velocity.x / dt;
+    assert!((acceleration - 750.).abs() < 10., "{}", acceleration);
+}
+
+#[test]
+fn bundled_scenarios_start_on_their_orbits() {
+    let directory = concat!(env!("CARGO_MANIFEST_DIR"), "/scenarios");
+    let mut checked = 0;
+    for entry in std::fs::read_dir(directory).unwrap() {
+        let path = entry.unwrap().path();
+        let scenario = Scenario::load(&path).unwrap();
+        let world = World::new(scenario.clone());
+
+        // Balls are created population by population
+        let mut balls = world.balls.ids();
+        for population in &scenario.balls {
+            let (count, circular) = match population {
+                BallPopulation::Explicit { .. } => (1, false),
+                BallPopulation::Orbital { count, .. } => (*count, true),
+                BallPopulation::Elements { count, .. } => (*count, false),
+            };
+            for id in balls.by_ref().take(count) {
+                if circular {
+                    let (_, orbit) = world.orbital_elements(id).unwrap();
+                    assert!(orbit.eccentricity < 0.05, "{}", path.display());
+                    checked += 1;
+                }
+            }
+        }
+    }
+    assert!(checked > 0);
+}
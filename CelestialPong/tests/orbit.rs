@@ -0,0 +1,147 @@
+use std::f32::consts::{FRAC_PI_2, TAU};
+
+use celestial_pong::ball::Ball;
+use celestial_pong::orbit::{dominant_body, OrbitKind, OrbitalElements};
+use celestial_pong::scenario::Scenario;
+use celestial_pong::static_body::{ContactPolicy, StaticBody};
+use celestial_pong::world::World;
+use macroquad::prelude::*;
+
+const GRAVITY: f32 = 30000.;
+const MU: f32 = GRAVITY * 1000.;
+
+fn star() -> Ball {
+    Ball::new(Vec2::ZERO, Vec2::ZERO, 30., 1000., WHITE)
+}
+
+fn elements(position: Vec2, velocity: Vec2) -> OrbitalElements {
+    let ball = Ball::new(position, velocity, 5., 1., WHITE);
+    OrbitalElements::new(&ball, &star(), GRAVITY)
+}
+
+fn close(a: f32, b: f32, tolerance: f32) -> bool {
+    (a - b).abs() <= tolerance * b.abs().max(1.)
+}
+
+#[test]
+fn circular_orbits() {
+    let speed = (MU / 250.).sqrt();
+    let orbit = elements(vec2(250., 0.), vec2(0., speed));
+    assert_eq!(orbit.kind, OrbitKind::Bound);
+    assert!(orbit.eccentricity < 1e-3, "{}", orbit.eccentricity);
+    assert!(close(orbit.semi_major_axis, 250., 1e-3));
+    assert!(close(orbit.periapsis, 250., 1e-3));
+    assert!(close(orbit.apoapsis.unwrap(), 250., 1e-3));
+    let period = TAU * (250f32.powi(3) / MU).sqrt();
+    assert!(close(orbit.period.unwrap(), period, 1e-3));
+    assert!(close(orbit.specific_energy, -MU / 500., 1e-3));
+    assert!(orbit.specific_angular_momentum > 0.);
+
+    // The other way around
+    let orbit = elements(vec2(250., 0.), vec2(0., -speed));
+    assert!(orbit.specific_angular_momentum < 0.);
+    assert!(close(orbit.period.unwrap(), period, 1e-3));
+}
+
+#[test]
+fn eccentric_orbits_match_the_world() {
+    let scenario = Scenario::parse(
+        r#"
+        [[static_bodies]]
+        radius = 30.0
+        mass = 1000.0
+
+        [[balls]]
+        kind = "explicit"
+        position = [0.0, -250.0]
+        velocity = [400.0, 0.0]
+        radius = 5.0
+        mass = 1.0
+        "#,
+    )
+    .unwrap();
+    let mut world = World::new(scenario);
+    let id = world.balls.ids().next().unwrap();
+    let (body, orbit) = world.orbital_elements(id).unwrap();
+    assert_eq!(body, 0);
+
+    // Launched faster than a circular orbit, at its periapsis
+    assert_eq!(orbit.kind, OrbitKind::Bound);
+    assert!(close(orbit.periapsis, 250., 1e-3));
+    assert!(close(orbit.argument_of_periapsis, -FRAC_PI_2, 1e-3));
+    assert!(close(orbit.semi_major_axis, 375., 1e-3));
+    assert!(close(orbit.eccentricity, 1. / 3., 1e-3));
+    let apoapsis = orbit.apoapsis.unwrap();
+    assert!(close(apoapsis, 500., 1e-3));
+
+    let start = world.balls[id].position;
+    let dt = world.physics.dt;
+    let steps = (orbit.period.unwrap() / dt).round() as usize;
+    let mut farthest: f32 = 0.;
+    for _ in 0..steps {
+        world.step(dt);
+        farthest = farthest.max(world.balls[id].position.length());
+    }
+    assert!(close(farthest, apoapsis, 1e-2), "{}", farthest);
+    let end = world.balls[id].position;
+    assert!(end.distance(start) < 5., "{} {}", start, end);
+}
+
+#[test]
+fn escapes_and_impacts() {
+    let escape = elements(vec2(0., -250.), vec2(600., 0.));
+    assert_eq!(escape.kind, OrbitKind::Escape);
+    assert!(escape.eccentricity > 1.);
+    assert!(escape.semi_major_axis < 0.);
+    assert!(escape.specific_energy > 0.);
+    assert_eq!(escape.apoapsis, None);
+    assert_eq!(escape.period, None);
+
+    // Too slow to miss the star
+    let falling = elements(vec2(0., -250.), vec2(50., 0.));
+    assert_eq!(falling.kind, OrbitKind::Impact);
+    assert!(falling.periapsis < 35.);
+
+    // Heading for the star fast enough to escape, or leaving it
+    let diving = elements(vec2(0., -250.), vec2(5., 600.));
+    assert_eq!(diving.kind, OrbitKind::Impact);
+    let leaving = elements(vec2(0., -250.), vec2(5., -600.));
+    assert_eq!(leaving.kind, OrbitKind::Escape);
+}
+
+#[test]
+fn nearest_heavy_body_dominates() {
+    let body = |x: f32, mass: f32| {
+        StaticBody::new(
+            Ball::new(vec2(x, 0.), Vec2::ZERO, 10., mass, WHITE),
+            ContactPolicy::Absorb { accrete: false },
+        )
+    };
+    let bodies = [body(-100., 100.), body(300., 1000.)];
+    assert_eq!(dominant_body(&bodies, vec2(-50., 0.)), Some(0));
+    assert_eq!(dominant_body(&bodies, vec2(100., 0.)), Some(1));
+    assert_eq!(dominant_body(&[], Vec2::ZERO), None);
+}
+
+#[test]
+fn conics_stay_between_the_apsides() {
+    let orbit = elements(vec2(0., -250.), vec2(400., 0.));
+    let mut points = Vec::new();
+    orbit.conic_points(vec2(10., 20.), 100, 1000., &mut points);
+    assert_eq!(points.len(), 101);
+    assert!(points[0].distance(points[100]) < 1e-2);
+    for point in &points {
+        let radius = point.distance(vec2(10., 20.));
+        assert!((249.9..500.1).contains(&radius), "{}", radius);
+    }
+
+    // Hyperbolas are cut where they get too far
+    let escape = elements(vec2(0., -250.), vec2(600., 0.));
+    points.clear();
+    escape.conic_points(Vec2::ZERO, 100, 1000., &mut points);
+    assert!(!points.is_empty() && points.len() < 101);
+    assert!(points.iter().all(|point| point.length() <= 1000.));
+    for segment in points.windows(2) {
+        assert!(segment[0].distance(segment[1]) < 200.);
+    }
+}
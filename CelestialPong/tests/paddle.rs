@@ -0,0 +1,149 @@
+use celestial_pong::scenario::Scenario;
+use celestial_pong::snapshot::Snapshot;
+use celestial_pong::world::{World, WorldEvent};
+use macroquad::prelude::*;
+
+// A paddle right of a body too light to attract anything, and a ball flying at it
+fn paddle_world(ball_velocity: [f32; 2]) -> World {
+    let scenario = Scenario::parse(&format!(
+        r#"
+        [[static_bodies]]
+        radius = 10.0
+        mass = 0.000001
+
+        [[paddles]]
+        orbit = 100.0
+        arc = [-45.0, 45.0]
+        length = 40.0
+        radius = 5.0
+        speed = 120.0
+
+        [[balls]]
+        kind = "explicit"
+        position = [40.0, 0.0]
+        velocity = {ball_velocity:?}
+        radius = 5.0
+        mass = 1.0
+        "#
+    ))
+    .unwrap();
+    World::new(scenario)
+}
+
+fn middle(world: &World) -> Vec2 {
+    let capsule = world.capsules[world.paddles[0].capsule];
+    (capsule.p1 + capsule.p2) / 2.
+}
+
+#[test]
+fn paddles_slide_along_their_arc() {
+    let mut world = paddle_world([0., 0.]);
+    assert!(middle(&world).distance(Vec2::new(100., 0.)) < 1e-4);
+
+    // Steered for half a second, the paddle covers half its speed along the circle
+    world.paddles[0].input = 1.;
+    for _ in 0..60 {
+        world.step(world.physics.dt);
+    }
+    let paddle = world.paddles[0];
+    assert!((paddle.angle - 0.6).abs() < 1e-3, "{}", paddle.angle);
+    assert!((middle(&world).length() - 100.).abs() < 1e-2);
+    let capsule = world.capsules[paddle.capsule];
+    assert!((capsule.p1.distance(capsule.p2) - 40.).abs() < 1e-3);
+    // The chord stays perpendicular to the direction of the body
+    assert!((capsule.p2 - capsule.p1).dot(middle(&world)).abs() < 1e-2);
+    assert!((capsule.velocity.length() - 120.).abs() < 0.5);
+
+    // It stops at the end of its arc
+    for _ in 0..240 {
+        world.step(world.physics.dt);
+    }
+    let paddle = world.paddles[0];
+    assert_eq!(paddle.angle, 45f32.to_radians());
+    assert!(middle(&world).distance(Vec2::from_angle(paddle.angle) * 100.) < 1e-2);
+    assert_eq!(world.capsules[paddle.capsule].velocity, Vec2::ZERO);
+
+    // Steering the other way brings it back
+    world.paddles[0].input = -1.;
+    world.step(world.physics.dt);
+    assert!(world.paddles[0].angle < 45f32.to_radians());
+}
+
+#[test]
+fn moving_paddles_give_their_velocity_to_balls() {
+    // The ball hits the paddle after 50 steps, it starts moving just before
+    let mut world = paddle_world([120., 0.]);
+    let mut events = Vec::new();
+    for step in 0..60 {
+        world.paddles[0].input = (step >= 40) as i32 as f32;
+        world.step(world.physics.dt);
+        events.extend(world.drain_events());
+    }
+
+    assert_eq!(events.len(), 1);
+    assert!(matches!(
+        events[0],
+        WorldEvent::BouncedOffCapsule { capsule: 0, .. }
+    ));
+    // Bounced back, and carried along the paddle the way it was moving
+    let ball = *world.balls.values().next().unwrap();
+    assert!(ball.velocity.x < -100., "{:?}", ball.velocity);
+    assert!(ball.velocity.y > 80., "{:?}", ball.velocity);
+
+    // A paddle at rest only bounces it
+    let mut world = paddle_world([120., 0.]);
+    for _ in 0..60 {
+        world.step(world.physics.dt);
+    }
+    let ball = *world.balls.values().next().unwrap();
+    assert!((ball.velocity - Vec2::new(-120., 0.)).length() < 1e-2);
+}
+
+#[test]
+fn paddles_survive_snapshots() {
+    let mut world = paddle_world([120., 0.]);
+    world.paddles[0].input = -1.;
+    for _ in 0..10 {
+        world.step(world.physics.dt);
+    }
+
+    let mut bytes = Vec::new();
+    world.snapshot().write_to(&mut bytes).unwrap();
+    let snapshot = Snapshot::read_from(bytes.as_slice()).unwrap();
+    let mut restored = paddle_world([0., 0.]);
+    restored.restore(&snapshot);
+    assert_eq!(restored.paddles, world.paddles);
+
+    for _ in 0..60 {
+        world.step(world.physics.dt);
+        restored.step(restored.physics.dt);
+    }
+    assert_eq!(restored.paddles, world.paddles);
+    let positions =
+        |world: &World| -> Vec<Vec2> { world.balls.values().map(|ball| ball.position).collect() };
+    assert_eq!(positions(&restored), positions(&world));
+}
+
+#[test]
+fn paddle_scenarios_are_checked() {
+    let paddle = |fields: &str| {
+        Scenario::parse(&format!(
+            "[[static_bodies]]\nradius = 10.0\nmass = 1.0\n\n[[paddles]]\n{}",
+            fields
+        ))
+    };
+    let base = "orbit = 100.0\nlength = 40.0\nradius = 5.0\nspeed = 100.0\n";
+    assert!(paddle(base).is_ok());
+
+    for (extra, field) in [
+        ("around = 1\n", "paddles[0].around"),
+        ("arc = [10.0, -10.0]\n", "paddles[0].arc"),
+        ("arc = [10.0, 20.0]\n", "paddles[0].angle"),
+        ("transfer = 2.0\n", "paddles[0].transfer"),
+    ] {
+        let err = paddle(&format!("{}{}", base, extra)).unwrap_err();
+        assert!(err.to_string().contains(field), "{}", err);
+    }
+    let err = paddle("orbit = -1.0\nlength = 40.0\nradius = 5.0\nspeed = 100.0\n").unwrap_err();
+    assert!(err.to_string().contains("paddles[0].orbit"), "{}", err);
+}
@@ -0,0 +1,142 @@
+use celestial_pong::prediction::{predict_path, PathEnd, Predictor};
+use celestial_pong::scenario::Scenario;
+use celestial_pong::world::World;
+
+fn star_world(balls: &str) -> World {
+    let scenario = Scenario::parse(&format!(
+        r#"
+        [[static_bodies]]
+        radius = 30.0
+        mass = 1000.0
+
+        {balls}
+        "#
+    ))
+    .unwrap();
+    World::new(scenario)
+}
+
+#[test]
+fn predicted_paths_follow_the_world() {
+    let mut world = star_world(
+        r#"
+        [[balls]]
+        kind = "explicit"
+        position = [0.0, -250.0]
+        velocity = [300.0, 0.0]
+        radius = 5.0
+        mass = 1.0
+        "#,
+    );
+    let ball = *world.balls.values().next().unwrap();
+    let mut path = Vec::new();
+    let end = predict_path(&world, &ball, 300, &mut path);
+    assert_eq!(end, PathEnd::Horizon);
+    assert_eq!(path.len(), 301);
+    assert_eq!(path[0], ball.position);
+
+    for expected in &path[1..] {
+        world.step(world.physics.dt);
+        let position = world.balls.values().next().unwrap().position;
+        assert!(
+            position.distance(*expected) < 1e-2,
+            "{} {}",
+            position,
+            expected
+        );
+    }
+}
+
+#[test]
+fn predicted_paths_stop_on_bodies() {
+    let world = star_world(
+        r#"
+        [[balls]]
+        kind = "explicit"
+        position = [0.0, -250.0]
+        velocity = [0.0, 0.0]
+        radius = 5.0
+        mass = 1.0
+        "#,
+    );
+    let ball = *world.balls.values().next().unwrap();
+    let mut path = Vec::new();
+    let end = predict_path(&world, &ball, 10_000, &mut path);
+    assert!(path.len() < 10_000);
+    let last = *path.last().unwrap();
+    assert_eq!(
+        end,
+        PathEnd::Body {
+            body: 0,
+            position: last
+        }
+    );
+    assert!(last.length() <= 35.);
+    assert!(path[path.len() - 2].length() > 35.);
+}
+
+#[test]
+fn predicted_paths_stop_at_the_field_edge() {
+    let world = star_world(
+        r#"
+        [[balls]]
+        kind = "explicit"
+        position = [0.0, -250.0]
+        velocity = [3000.0, 0.0]
+        radius = 5.0
+        mass = 1.0
+        "#,
+    );
+    let ball = *world.balls.values().next().unwrap();
+    let mut path = Vec::new();
+    let end = predict_path(&world, &ball, 10_000, &mut path);
+    let last = *path.last().unwrap();
+    assert_eq!(end, PathEnd::Exit { position: last });
+    assert!(!world.tree_area.contains(last));
+    assert!(world.tree_area.contains(path[path.len() - 2]));
+}
+
+#[test]
+fn predictions_can_include_collisions() {
+    // Two balls meeting head on above the star
+    let mut world = star_world(
+        r#"
+        [[balls]]
+        kind = "explicit"
+        position = [-100.0, -300.0]
+        velocity = [200.0, 0.0]
+        radius = 5.0
+        mass = 1.0
+
+        [[balls]]
+        kind = "explicit"
+        position = [100.0, -300.0]
+        velocity = [-200.0, 0.0]
+        radius = 5.0
+        mass = 1.0
+        "#,
+    );
+    let id = world.balls.iter().next().unwrap().0;
+
+    // Gravity alone goes through the other ball
+    let mut path = Vec::new();
+    predict_path(&world, &world.balls[id], 120, &mut path);
+    assert!(path.last().unwrap().x > 0.);
+
+    let mut collided = Vec::new();
+    let mut predictor = Predictor::new();
+    let end = predictor.predict(&world, id, 120, &mut collided);
+    assert_eq!(end, PathEnd::Horizon);
+    assert!(collided.last().unwrap().x < 0.);
+
+    // The prediction is what the world does
+    for expected in &collided[1..] {
+        world.step(world.physics.dt);
+        assert_eq!(world.balls[id].position, *expected);
+    }
+
+    // Predicting again reuses the copy
+    let mut again = Vec::new();
+    predictor.predict(&world, id, 10, &mut again);
+    assert_eq!(again[0], world.balls[id].position);
+}
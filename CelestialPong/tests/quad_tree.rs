@@ -0,0 +1,440 @@
+use ::rand::{Rng, SeedableRng};
+use celestial_pong::arena::ArenaId;
+use celestial_pong::quad_tree::{Collider, QuadTree, QuadTreeConfig, QuadTreeEntry, Rect};
+use macroquad::prelude::*;
+use rand_chacha::ChaCha20Rng;
+
+const AREA: f32 = 1000.;
+
+fn configs() -> Vec<QuadTreeConfig> {
+    let mut configs = Vec::new();
+    for node_capacity in [1, 2, 4, 16] {
+        for max_depth in [0, 1, 4, 12] {
+            configs.push(QuadTreeConfig {
+                node_capacity,
+                max_depth,
+            });
+        }
+    }
+    configs
+}
+
+fn ball(index: usize) -> Collider {
+    Collider::Ball(ArenaId::new(index as u32, 0))
+}
+
+fn entry(position: Vec2, index: usize) -> QuadTreeEntry {
+    QuadTreeEntry::new(Rect::around(position, Vec2::ZERO), ball(index))
+}
+
+// Mostly small boxes, some points and a few larger than a quarter of the area
+fn random_bounds(rng: &mut ChaCha20Rng, position: Vec2) -> Rect {
+    let half_size = match rng.gen_range(0..10) {
+        0 => Vec2::ZERO,
+        1 => Vec2::new(rng.gen_range(0. ..AREA / 4.), rng.gen_range(0. ..AREA / 4.)),
+        _ => Vec2::new(rng.gen_range(0. ..10.), rng.gen_range(0. ..10.)),
+    };
+    Rect::around(position, half_size)
+}
+
+fn random_boxes(rng: &mut ChaCha20Rng, count: usize) -> Vec<QuadTreeEntry> {
+    random_entries(rng, count)
+        .into_iter()
+        .map(|entry| QuadTreeEntry::new(random_bounds(rng, entry.bounds.center()), entry.payload))
+        .collect()
+}
+
+// Uniform points, tight clusters, exact duplicates and points on the split lines
+fn random_entries(rng: &mut ChaCha20Rng, count: usize) -> Vec<QuadTreeEntry> {
+    let half = AREA / 2.;
+    let mut positions: Vec<Vec2> = Vec::new();
+    while positions.len() < count {
+        let position = match rng.gen_range(0..4) {
+            0 => Vec2::new(rng.gen_range(-half..half), rng.gen_range(-half..half)),
+            1 => {
+                let center = Vec2::new(rng.gen_range(-half..half), rng.gen_range(-half..half));
+                let offset = Vec2::new(rng.gen_range(-1. ..1.), rng.gen_range(-1. ..1.));
+                (center + offset * 0.01).clamp(Vec2::splat(-half), Vec2::splat(half - 0.01))
+            }
+            2 if !positions.is_empty() => positions[rng.gen_range(0..positions.len())],
+            _ => {
+                let split = [-half, -half / 2., 0., half / 2., half / 4.];
+                Vec2::new(
+                    split[rng.gen_range(0..split.len())],
+                    split[rng.gen_range(0..split.len())],
+                )
+            }
+        };
+        positions.push(position);
+    }
+
+    positions
+        .into_iter()
+        .enumerate()
+        .map(|(index, position)| entry(position, index))
+        .collect()
+}
+
+fn random_query(rng: &mut ChaCha20Rng) -> Rect {
+    // Some queries stick out of the tree area
+    let center = Vec2::new(
+        rng.gen_range(-AREA * 0.6..AREA * 0.6),
+        rng.gen_range(-AREA * 0.6..AREA * 0.6),
+    );
+    let size = Vec2::new(rng.gen_range(0. ..AREA / 2.), rng.gen_range(0. ..AREA / 2.));
+    Rect::new(center.x, center.y, size.x, size.y)
+}
+
+fn sorted_payloads(entries: &[QuadTreeEntry]) -> Vec<Collider> {
+    let mut payloads: Vec<Collider> = entries.iter().map(|entry| entry.payload).collect();
+    payloads.sort();
+    payloads
+}
+
+fn brute_force(entries: &[QuadTreeEntry], query: &Rect) -> Vec<Collider> {
+    let inside: Vec<QuadTreeEntry> = entries
+        .iter()
+        .copied()
+        .filter(|entry| query.overlap(&entry.bounds))
+        .collect();
+    sorted_payloads(&inside)
+}
+
+#[test]
+fn queries_match_brute_force() {
+    let mut rng = ChaCha20Rng::seed_from_u64(13);
+    for config in configs() {
+        for _ in 0..10 {
+            let count = rng.gen_range(0..300);
+            let entries = random_boxes(&mut rng, count);
+            let mut tree = QuadTree::with_config(Rect::new(0., 0., AREA, AREA), config);
+            for entry in &entries {
+                assert!(tree.add(*entry));
+            }
+
+            let mut result = Vec::new();
+            for _ in 0..50 {
+                let query = random_query(&mut rng);
+                result.clear();
+                tree.query_entries(&query, &mut result);
+                assert_eq!(
+                    sorted_payloads(&result),
+                    brute_force(&entries, &query),
+                    "{:?}, query {:?}",
+                    config,
+                    query
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn every_entry_is_stored_once() {
+    let mut rng = ChaCha20Rng::seed_from_u64(14);
+    for config in configs() {
+        let entries = random_boxes(&mut rng, 500);
+        let mut tree = QuadTree::with_config(Rect::new(0., 0., AREA, AREA), config);
+        for entry in &entries {
+            tree.add(*entry);
+        }
+
+        let mut result = Vec::new();
+        tree.query_entries(&Rect::new(0., 0., AREA * 2., AREA * 2.), &mut result);
+        assert_eq!(tree.len(), entries.len());
+        assert_eq!(sorted_payloads(&result), sorted_payloads(&entries));
+        assert!(tree.depth() <= config.max_depth);
+    }
+}
+
+#[test]
+fn empty_tree_has_no_phantom_entries() {
+    let tree = QuadTree::new(Rect::new(0., 0., AREA, AREA));
+    let mut result = Vec::new();
+    tree.query_entries(&Rect::new(0., 0., 10., 10.), &mut result);
+    assert!(result.is_empty());
+
+    let mut tree = QuadTree::new(Rect::new(0., 0., AREA, AREA));
+    tree.add(entry(Vec2::new(100., 100.), 7));
+    tree.query_entries(&Rect::new(0., 0., 10., 10.), &mut result);
+    assert!(result.is_empty());
+}
+
+#[test]
+fn identical_positions_fill_a_bucket() {
+    let config = QuadTreeConfig {
+        node_capacity: 1,
+        max_depth: 8,
+    };
+    let mut tree = QuadTree::with_config(Rect::new(0., 0., AREA, AREA), config);
+    let position = Vec2::new(12.5, -3.25);
+    for index in 0..10_000 {
+        assert!(tree.add(entry(position, index)));
+    }
+
+    let mut result = Vec::new();
+    tree.query_entries(&Rect::new(position.x, position.y, 1., 1.), &mut result);
+    assert_eq!(result.len(), 10_000);
+    assert!(tree.depth() <= 8);
+}
+
+#[test]
+fn entries_outside_are_rejected() {
+    let mut tree = QuadTree::new(Rect::new(0., 0., AREA, AREA));
+    let half = AREA / 2.;
+    assert!(tree.add(entry(Vec2::new(-half, -half), 0)));
+    // Right and bottom edges are excluded, like in `Rect::contains`
+    assert!(!tree.add(entry(Vec2::new(half, 0.), 1)));
+    assert!(!tree.add(entry(Vec2::new(0., half), 2)));
+    assert!(!tree.add(entry(Vec2::new(f32::NAN, 0.), 3)));
+    assert_eq!(tree.len(), 1);
+}
+
+#[test]
+fn edits_match_brute_force() {
+    let mut rng = ChaCha20Rng::seed_from_u64(15);
+    for config in configs() {
+        let mut tree = QuadTree::with_config(Rect::new(0., 0., AREA, AREA), config);
+        // Expected content, indexed by payload
+        let mut model: Vec<Option<Rect>> = vec![None; 200];
+        let mut result = Vec::new();
+        for round in 0..2000 {
+            let index = rng.gen_range(0..model.len());
+            let id = ball(index);
+            let position = random_entries(&mut rng, 1)[0].bounds.center();
+            let bounds = random_bounds(&mut rng, position);
+            match rng.gen_range(0..3) {
+                0 => {
+                    assert!(tree.add(QuadTreeEntry::new(bounds, id)));
+                    model[index] = Some(bounds);
+                }
+                1 => assert_eq!(tree.remove(id), model[index].take().is_some()),
+                _ => {
+                    assert!(tree.update(id, bounds));
+                    model[index] = Some(bounds);
+                }
+            }
+
+            if round % 20 == 0 {
+                let entries: Vec<QuadTreeEntry> = model
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(index, bounds)| Some(QuadTreeEntry::new((*bounds)?, ball(index))))
+                    .collect();
+                let query = random_query(&mut rng);
+                result.clear();
+                tree.query_entries(&query, &mut result);
+                assert_eq!(sorted_payloads(&result), brute_force(&entries, &query));
+                assert_eq!(tree.len(), entries.len());
+            }
+        }
+    }
+}
+
+#[test]
+fn moving_outside_removes_the_entry() {
+    let mut tree = QuadTree::new(Rect::new(0., 0., AREA, AREA));
+    let id = ball(0);
+    assert!(tree.update(id, Rect::new(10., 10., 0., 0.)));
+    assert!(!tree.update(id, Rect::new(AREA, 0., 0., 0.)));
+    assert!(!tree.contains(id));
+    assert!(tree.is_empty());
+}
+
+#[test]
+fn clear_keeps_the_nodes() {
+    let mut rng = ChaCha20Rng::seed_from_u64(16);
+    let entries = random_entries(&mut rng, 1000);
+    let mut tree = QuadTree::new(Rect::new(0., 0., AREA, AREA));
+    for entry in &entries {
+        tree.add(*entry);
+    }
+    let node_count = tree.node_count();
+
+    for _ in 0..3 {
+        tree.clear();
+        assert!(tree.is_empty());
+        for entry in &entries {
+            tree.add(*entry);
+        }
+        assert_eq!(tree.node_count(), node_count);
+    }
+
+    // Removing everything merges the nodes back without freeing them
+    for entry in &entries {
+        assert!(tree.remove(entry.payload));
+    }
+    assert_eq!(tree.depth(), 0);
+    for entry in &entries {
+        tree.add(*entry);
+    }
+    assert_eq!(tree.node_count(), node_count);
+}
+
+#[test]
+fn large_entries_are_found_away_from_their_center() {
+    let mut rng = ChaCha20Rng::seed_from_u64(17);
+    let mut tree = QuadTree::new(Rect::new(0., 0., AREA, AREA));
+    for entry in random_entries(&mut rng, 500) {
+        tree.add(entry);
+    }
+    // A body larger than the whole area, and a long thin one across it
+    let body = Collider::Body(0);
+    let long = Collider::Body(1);
+    assert!(tree.add(QuadTreeEntry::new(
+        Rect::new(10., 10., AREA * 3., AREA * 3.),
+        body
+    )));
+    assert!(tree.add(QuadTreeEntry::new(Rect::new(0., 100., AREA, 2.), long)));
+
+    let mut result = Vec::new();
+    tree.query_entries(&Rect::new(-AREA, -AREA, 1., 1.), &mut result);
+    let payloads = sorted_payloads(&result);
+    assert!(payloads.contains(&body));
+    assert!(!payloads.contains(&long));
+
+    result.clear();
+    tree.query_entries(&Rect::new(AREA * 0.49, 100.5, 1., 1.), &mut result);
+    let payloads = sorted_payloads(&result);
+    assert!(payloads.contains(&body));
+    assert!(payloads.contains(&long));
+}
+
+fn random_point(rng: &mut ChaCha20Rng) -> Vec2 {
+    Vec2::new(
+        rng.gen_range(-AREA * 0.6..AREA * 0.6),
+        rng.gen_range(-AREA * 0.6..AREA * 0.6),
+    )
+}
+
+fn random_tree(rng: &mut ChaCha20Rng, config: QuadTreeConfig) -> (QuadTree, Vec<QuadTreeEntry>) {
+    let count = rng.gen_range(0..300);
+    let entries = random_boxes(rng, count);
+    let mut tree = QuadTree::with_config(Rect::new(0., 0., AREA, AREA), config);
+    for entry in &entries {
+        tree.add(*entry);
+    }
+    (tree, entries)
+}
+
+#[test]
+fn shape_queries_match_brute_force() {
+    let mut rng = ChaCha20Rng::seed_from_u64(18);
+    for config in configs() {
+        for _ in 0..5 {
+            let (tree, entries) = random_tree(&mut rng, config);
+            let mut result = Vec::new();
+            for _ in 0..20 {
+                let center = random_point(&mut rng);
+                let radius = rng.gen_range(0. ..AREA / 4.);
+                let expected: Vec<QuadTreeEntry> = entries
+                    .iter()
+                    .copied()
+                    .filter(|entry| entry.bounds.distance_squared(center) <= radius * radius)
+                    .collect();
+                result.clear();
+                tree.query_radius_entries(center, radius, &mut result);
+                assert_eq!(sorted_payloads(&result), sorted_payloads(&expected));
+
+                let (a, b) = (random_point(&mut rng), random_point(&mut rng));
+                let expected: Vec<QuadTreeEntry> = entries
+                    .iter()
+                    .copied()
+                    .filter(|entry| entry.bounds.segment_distance_squared(a, b) <= radius * radius)
+                    .collect();
+                result.clear();
+                tree.query_capsule_entries(a, b, radius, &mut result);
+                assert_eq!(sorted_payloads(&result), sorted_payloads(&expected));
+            }
+        }
+    }
+}
+
+#[test]
+fn ray_cast_finds_the_first_hit() {
+    let mut rng = ChaCha20Rng::seed_from_u64(19);
+    for config in configs() {
+        for _ in 0..5 {
+            let (tree, entries) = random_tree(&mut rng, config);
+            for _ in 0..20 {
+                let origin = random_point(&mut rng);
+                let direction = random_point(&mut rng);
+                let max_distance = rng.gen_range(0. ..AREA);
+                let unit = direction.normalize_or_zero();
+                let expected = entries
+                    .iter()
+                    .filter_map(|entry| entry.bounds.ray_distance(origin, unit, max_distance))
+                    .fold(None, |best: Option<f32>, distance| {
+                        Some(best.map_or(distance, |best| best.min(distance)))
+                    });
+
+                let hits = tree.ray_hits(origin, direction, max_distance).count();
+                let expected_hits = entries
+                    .iter()
+                    .filter(|entry| {
+                        entry
+                            .bounds
+                            .ray_distance(origin, unit, max_distance)
+                            .is_some()
+                    })
+                    .count();
+                assert_eq!(hits, expected_hits);
+                let hit = tree.ray_cast(origin, direction, max_distance);
+                assert_eq!(hit.map(|(distance, _)| distance), expected);
+            }
+        }
+    }
+}
+
+#[test]
+fn nearest_entries_are_the_closest() {
+    let mut rng = ChaCha20Rng::seed_from_u64(20);
+    let mut result = Vec::new();
+    for config in configs() {
+        for _ in 0..5 {
+            let (tree, entries) = random_tree(&mut rng, config);
+            for _ in 0..20 {
+                let point = random_point(&mut rng);
+                let count = rng.gen_range(0..10);
+                let mut expected: Vec<f32> = entries
+                    .iter()
+                    .map(|entry| entry.bounds.distance_squared(point).sqrt())
+                    .collect();
+                expected.sort_by(f32::total_cmp);
+                let closest = expected.first().copied();
+                expected.truncate(count);
+
+                tree.nearest_entries(point, count, &mut result);
+                let distances: Vec<f32> = result.iter().map(|(distance, _)| *distance).collect();
+                assert_eq!(distances, expected);
+                assert_eq!(tree.nearest(point).map(|(distance, _)| distance), closest);
+            }
+        }
+    }
+}
+
+#[test]
+fn rect_distances() {
+    let rect = Rect::new(0., 0., 2., 2.);
+    assert_eq!(rect.distance_squared(Vec2::new(0.5, 0.5)), 0.);
+    assert_eq!(rect.distance_squared(Vec2::new(4., 5.)), 25.);
+
+    // Crossing the rect without an end inside it
+    assert_eq!(
+        rect.segment_distance_squared(Vec2::new(-5., 0.), Vec2::new(5., 0.)),
+        0.
+    );
+    assert_eq!(
+        rect.segment_distance_squared(Vec2::new(-5., 3.), Vec2::new(5., 3.)),
+        4.
+    );
+
+    assert_eq!(
+        rect.ray_distance(Vec2::new(-5., 0.), Vec2::X, 10.),
+        Some(4.)
+    );
+    assert_eq!(rect.ray_distance(Vec2::ZERO, Vec2::X, 10.), Some(0.));
+    assert_eq!(rect.ray_distance(Vec2::new(-5., 0.), Vec2::X, 3.), None);
+    assert_eq!(rect.ray_distance(Vec2::new(-5., 0.), -Vec2::X, 10.), None);
+    assert_eq!(rect.ray_distance(Vec2::new(-5., 2.), Vec2::X, 10.), None);
+}
@@ -0,0 +1,44 @@
+use celestial_pong::scenario::{Scenario, ScenarioError};
+
+fn error(text: &str) -> String {
+    Scenario::parse(text).unwrap_err().to_string()
+}
+
+#[test]
+fn errors_name_the_field() {
+    for (text, field) in [
+        (
+            "[[static_bodies]]\nradius = -30.0\nmass = 1000.0\n",
+            "`static_bodies[0].radius`",
+        ),
+        (
+            "[[static_bodies]]\nradius = 30.0\nmass = 1000.0\n\n\
+             [[static_bodies]]\nradius = 10.0\nmass = 1.0\nspin = 2.0\n",
+            "`static_bodies[1].spin`",
+        ),
+        ("[physics]\ngravty = 30000.0\n", "`physics.gravty`"),
+        ("[physics]\nintegrator = \"rk5\"\n", "`physics.integrator`"),
+        (
+            "[[balls]]\nkind = \"explicit\"\nposition = [0.0, 0.0]\nradius = -1.0\nmass = 1.0\n",
+            "`balls[0].radius`",
+        ),
+    ] {
+        let message = error(text);
+        assert!(message.contains(field), "{}", message);
+    }
+
+    // The offending value is named too
+    assert!(error("[physics]\ngravty = 30000.0\n").contains("unknown field `gravty`"));
+    assert!(error("[physics]\nintegrator = \"rk5\"\n").contains("unknown variant `rk5`"));
+}
+
+#[test]
+fn syntax_errors_have_no_field() {
+    let err = Scenario::parse("[physics\ngravity = 1.0\n").unwrap_err();
+    assert!(
+        matches!(&err, ScenarioError::Parse(field, _) if field.is_empty()),
+        "{}",
+        err
+    );
+    assert!(err.to_string().contains("line 1"), "{}", err);
+}
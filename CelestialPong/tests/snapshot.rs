@@ -0,0 +1,116 @@
+use celestial_pong::scenario::Scenario;
+use celestial_pong::snapshot::{Snapshot, SnapshotError, SNAPSHOT_VERSION};
+use celestial_pong::world::World;
+use macroquad::prelude::*;
+
+// Mutually attracting balls at random places around a star
+fn n_body_world() -> World {
+    let scenario = Scenario::parse(
+        r#"
+        seed = 42
+
+        [physics]
+        n_body = true
+        softening = 2.0
+
+        [[static_bodies]]
+        radius = 30.0
+        mass = 1000.0
+
+        [[balls]]
+        kind = "orbital"
+        count = 12
+        min_orbit = 80.0
+        max_orbit = 300.0
+        radius = 6.0
+        mass = 5.0
+        "#,
+    )
+    .unwrap();
+    World::new(scenario)
+}
+
+fn step(world: &mut World, steps: usize) {
+    let dt = world.physics.dt;
+    for _ in 0..steps {
+        world.step(dt);
+    }
+}
+
+fn bits(vector: Vec2) -> [u32; 2] {
+    [vector.x.to_bits(), vector.y.to_bits()]
+}
+
+fn snapshot_bytes(world: &World) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    world.snapshot().write_to(&mut bytes).unwrap();
+    bytes
+}
+
+#[test]
+fn restored_worlds_resume_bit_for_bit() {
+    let mut world = n_body_world();
+    step(&mut world, 100);
+
+    let bytes = snapshot_bytes(&world);
+    let snapshot = Snapshot::read_from(bytes.as_slice()).unwrap();
+    // A world made from another scenario, everything simulated comes from the snapshot
+    let mut restored = World::new(Scenario::default());
+    restored.restore(&snapshot);
+
+    step(&mut world, 300);
+    step(&mut restored, 300);
+
+    let state = |world: &World| -> Vec<_> {
+        world
+            .balls
+            .iter()
+            .map(|(id, ball)| {
+                (
+                    id,
+                    bits(ball.position),
+                    bits(ball.prev_position),
+                    bits(ball.velocity),
+                )
+            })
+            .collect()
+    };
+    assert!(!world.balls.is_empty());
+    assert_eq!(state(&restored), state(&world));
+    assert_eq!(restored.step_count, world.step_count);
+    assert_eq!(restored.rng.get_word_pos(), world.rng.get_word_pos());
+}
+
+#[test]
+fn damaged_snapshots_are_rejected() {
+    let mut world = n_body_world();
+    step(&mut world, 10);
+    let bytes = snapshot_bytes(&world);
+    assert!(Snapshot::read_from(bytes.as_slice()).is_ok());
+
+    for length in (0..bytes.len()).step_by(7) {
+        assert!(
+            Snapshot::read_from(&bytes[..length]).is_err(),
+            "{} bytes",
+            length
+        );
+    }
+
+    let mut bad_magic = bytes.clone();
+    bad_magic[0] = b'X';
+    let err = Snapshot::read_from(bad_magic.as_slice()).unwrap_err();
+    assert!(matches!(err, SnapshotError::NotASnapshot));
+    assert_eq!(err.to_string(), "not a snapshot file");
+
+    let mut bad_version = bytes;
+    bad_version[4..8].copy_from_slice(&(SNAPSHOT_VERSION + 1).to_le_bytes());
+    let err = Snapshot::read_from(bad_version.as_slice()).unwrap_err();
+    assert!(matches!(
+        err,
+        SnapshotError::UnsupportedVersion(version) if version == SNAPSHOT_VERSION + 1
+    ));
+    assert_eq!(
+        err.to_string(),
+        "snapshot version 3 is not supported (expected 2)"
+    );
+}
@@ -0,0 +1,220 @@
+use celestial_pong::diagnostics::Diagnostics;
+use celestial_pong::scenario::Scenario;
+use celestial_pong::static_body::Landing;
+use celestial_pong::world::{World, WorldEvent};
+use macroquad::prelude::*;
+
+// A ball of mass 2 flying right at 600 towards a body of mass 10 at the origin, with
+// gravity too weak to matter. `body` is the rest of the body description.
+fn collision_world(body: &str, dynamic_bodies: bool) -> World {
+    let scenario = Scenario::parse(&format!(
+        r#"
+        [physics]
+        gravity = 0.001
+        dynamic_bodies = {dynamic_bodies}
+
+        [[static_bodies]]
+        radius = 20.0
+        mass = 10.0
+        {body}
+
+        [[balls]]
+        kind = "explicit"
+        position = [-100.0, 0.0]
+        velocity = [600.0, 0.0]
+        radius = 5.0
+        mass = 2.0
+        "#
+    ))
+    .unwrap();
+    World::new(scenario)
+}
+
+// Steps until something happens, returning the events of that step
+fn step_until_event(world: &mut World) -> Vec<WorldEvent> {
+    let dt = world.physics.dt;
+    for _ in 0..100 {
+        world.step(dt);
+        let events: Vec<WorldEvent> = world.drain_events().collect();
+        if !events.is_empty() {
+            return events;
+        }
+    }
+    panic!("nothing happened");
+}
+
+#[test]
+fn absorbed_balls_are_removed() {
+    let mut world = collision_world(r#"contact = { kind = "absorb" }"#, true);
+    let id = world.balls.ids().next().unwrap();
+
+    let events = step_until_event(&mut world);
+    let [WorldEvent::Absorbed {
+        ball,
+        body: 0,
+        position,
+    }] = events[..]
+    else {
+        panic!("{:?}", events);
+    };
+    assert_eq!(ball, id);
+    // Where the surfaces touch
+    assert!(
+        (position.x + 25.).abs() < 0.1 && position.y.abs() < 1e-3,
+        "{}",
+        position
+    );
+    assert!(world.balls.is_empty());
+
+    // Nothing given to the body
+    let body = world.static_bodies[0].ball;
+    assert_eq!(body.mass, 10.);
+    assert!(body.velocity.length() < 1e-3);
+}
+
+#[test]
+fn accreting_bodies_take_the_mass_and_momentum() {
+    let mut world = collision_world(r#"contact = { kind = "absorb", accrete = true }"#, true);
+    let before = Diagnostics::measure(&world).momentum;
+
+    let events = step_until_event(&mut world);
+    assert!(
+        matches!(events[..], [WorldEvent::Absorbed { body: 0, .. }]),
+        "{:?}",
+        events
+    );
+    assert!(world.balls.is_empty());
+    let body = world.static_bodies[0].ball;
+    assert_eq!(body.mass, 12.);
+    assert!(
+        (body.velocity - vec2(100., 0.)).length() < 0.1,
+        "{}",
+        body.velocity
+    );
+    let after = Diagnostics::measure(&world).momentum;
+    assert!((after - before).length() < 0.1, "{} {}", before, after);
+
+    // Bodies that do not move only take the mass
+    let mut world = collision_world(r#"contact = { kind = "absorb", accrete = true }"#, false);
+    step_until_event(&mut world);
+    assert_eq!(world.static_bodies[0].ball.mass, 12.);
+    assert_eq!(world.static_bodies[0].ball.velocity, Vec2::ZERO);
+}
+
+#[test]
+fn balls_bounce_off_fixed_bodies() {
+    let mut world = collision_world(r#"contact = { kind = "bounce" }"#, false);
+    let id = world.balls.ids().next().unwrap();
+
+    let events = step_until_event(&mut world);
+    let [WorldEvent::Bounced {
+        ball,
+        body: 0,
+        speed,
+    }] = events[..]
+    else {
+        panic!("{:?}", events);
+    };
+    assert_eq!(ball, id);
+    assert!((speed - 600.).abs() < 0.1, "{}", speed);
+    let ball = world.balls[id];
+    assert!(
+        (ball.velocity - vec2(-600., 0.)).length() < 0.1,
+        "{}",
+        ball.velocity
+    );
+    assert!(ball.position.length() >= 25. - 1e-3);
+    assert_eq!(world.static_bodies[0].ball.velocity, Vec2::ZERO);
+}
+
+#[test]
+fn balls_push_dynamic_bodies_back() {
+    let mut world = collision_world(r#"contact = { kind = "bounce" }"#, true);
+    let id = world.balls.ids().next().unwrap();
+    let before = Diagnostics::measure(&world);
+
+    let events = step_until_event(&mut world);
+    assert!(
+        matches!(events[..], [WorldEvent::Bounced { body: 0, .. }]),
+        "{:?}",
+        events
+    );
+
+    // Elastic collision of masses 2 and 10
+    let ball = world.balls[id];
+    let body = world.static_bodies[0].ball;
+    assert!(
+        (ball.velocity - vec2(-400., 0.)).length() < 0.5,
+        "{}",
+        ball.velocity
+    );
+    assert!(
+        (body.velocity - vec2(200., 0.)).length() < 0.5,
+        "{}",
+        body.velocity
+    );
+    let after = Diagnostics::measure(&world);
+    assert!((after.momentum - before.momentum).length() < 0.1);
+    let energy = (after.kinetic_energy - before.kinetic_energy).abs();
+    assert!(energy < before.kinetic_energy * 1e-3, "{}", energy);
+}
+
+#[test]
+fn landed_balls_follow_their_body() {
+    let mut world = collision_world(
+        r#"velocity = [-300.0, 0.0]
+        contact = { kind = "land" }"#,
+        true,
+    );
+    let id = world.balls.ids().next().unwrap();
+
+    let events = step_until_event(&mut world);
+    assert_eq!(events, vec![WorldEvent::Landed { ball: id, body: 0 }]);
+    let Some(Landing { body: 0, offset }) = world.balls[id].landed_on else {
+        panic!("{:?}", world.balls[id].landed_on);
+    };
+    assert!((offset.length() - 25.).abs() < 1e-3, "{}", offset);
+
+    let dt = world.physics.dt;
+    for _ in 0..50 {
+        world.step(dt);
+        let ball = world.balls[id];
+        let body = world.static_bodies[0].ball;
+        assert_eq!(ball.position, body.position + offset);
+        assert_eq!(ball.velocity, body.velocity);
+    }
+    assert_eq!(world.drain_events().count(), 0);
+    assert!(world.static_bodies[0].ball.position.x < -100.);
+}
+
+#[test]
+fn passing_balls_are_reported_once() {
+    let mut world = collision_world(r#"contact = { kind = "pass_through" }"#, true);
+    let id = world.balls.ids().next().unwrap();
+    let dt = world.physics.dt;
+
+    let mut events = Vec::new();
+    let mut steps_inside = 0;
+    for _ in 0..60 {
+        world.step(dt);
+        events.extend(world.drain_events());
+        let ball = world.balls[id];
+        if ball.position.length() <= 25. {
+            steps_inside += 1;
+        }
+    }
+    // Several steps in the body, one event
+    assert!(steps_inside > 2, "{}", steps_inside);
+    assert_eq!(
+        events,
+        vec![WorldEvent::PassedThrough { ball: id, body: 0 }]
+    );
+    let ball = world.balls[id];
+    assert!(ball.position.x > 100.);
+    assert!(
+        (ball.velocity - vec2(600., 0.)).length() < 0.1,
+        "{}",
+        ball.velocity
+    );
+    assert_eq!(world.static_bodies[0].ball.velocity, Vec2::ZERO);
+}